@@ -1,6 +1,16 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::eventsub::MessageIdCache;
+use crate::youtube_chat::LiveChatItem;
+use crate::youtube_feed::{parse_youtube_feed, YouTubeFeedEntry};
+
+const MAX_NAME_LEN: usize = 100;
+const MAX_USER_MESSAGE_LEN: usize = 500;
+/// The hub notifies again on every edit of a video, so announced ids are remembered this long
+const YOUTUBE_ANNOUNCED_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+const YOUTUBE_ANNOUNCED_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AlertPayload {
     pub platform: String,
     pub alert_type: String,
@@ -8,7 +18,29 @@ pub struct AlertPayload {
     pub message: String,
     pub amount: Option<String>,
     pub currency: Option<String>,
+    /// `amount` and `currency` in Russian words, e.g. "пять долларов"
+    pub spoken_amount: Option<String>,
     pub count: Option<u32>,
+    /// Human readable subscription tier, e.g. "Tier 1"
+    pub tier: Option<String>,
+    pub is_gift: Option<bool>,
+    pub cumulative_months: Option<u32>,
+    pub streak_months: Option<u32>,
+    /// Text the viewer attached to the alert, sanitized and safe to read aloud
+    pub user_message: Option<String>,
+}
+
+impl AlertPayload {
+    /// Swap in another sender name, e.g. a censored one. Every alert message opens with the name,
+    /// so the message is rebuilt as the new name plus the rest; names inside the rest (a reward title) stay.
+    pub fn set_user_name(&mut self, user_name: String) {
+        self.message = match self.message.strip_prefix(self.user_name.as_str()) {
+            Some(rest) => format!("{}{}", user_name, rest),
+            // Not built here; nothing is known about it but that it carries the old name
+            None => user_name.clone(),
+        };
+        self.user_name = user_name;
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +60,10 @@ pub struct TwitchSubscription {
 
 /// Sanitize a string for safe display - strip control chars and HTML-significant chars
 fn sanitize(input: &str) -> String {
+    sanitize_with_limit(input, MAX_NAME_LEN)
+}
+
+fn sanitize_with_limit(input: &str, max_len: usize) -> String {
     input
         .chars()
         .filter(|c| !c.is_control() || *c == ' ')
@@ -35,12 +71,32 @@ fn sanitize(input: &str) -> String {
             '<' | '>' | '&' | '"' | '\'' => '_',
             _ => c,
         })
-        .take(100)
+        .take(max_len)
         .collect::<String>()
         .trim()
         .to_string()
 }
 
+/// Sanitize optional viewer-supplied text, mapping empty results to `None`
+fn sanitize_user_message(input: Option<&str>) -> Option<String> {
+    let text = sanitize_with_limit(input?, MAX_USER_MESSAGE_LEN);
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// Map an EventSub tier id ("1000", "2000", "3000") to a display label
+fn tier_label(tier: &str) -> Option<String> {
+    match tier {
+        "1000" => Some("Tier 1".to_string()),
+        "2000" => Some("Tier 2".to_string()),
+        "3000" => Some("Tier 3".to_string()),
+        _ => None,
+    }
+}
+
 pub fn process_twitch_event(payload: TwitchEventSubPayload) -> Option<AlertPayload> {
     let event = &payload.event;
     let r#type = &payload.subscription.r#type;
@@ -56,9 +112,30 @@ pub fn process_twitch_event(payload: TwitchEventSubPayload) -> Option<AlertPaylo
                 alert_type: "sub".to_string(),
                 user_name: user_name.clone(),
                 message: format!("{} just subscribed!", user_name),
-                amount: None,
-                currency: None,
-                count: None,
+                tier: event["tier"].as_str().and_then(tier_label),
+                is_gift: event["is_gift"].as_bool(),
+                ..Default::default()
+            })
+        }
+        "channel.subscription.message" => {
+            let user_name = sanitize(event["user_name"].as_str()?);
+            if user_name.is_empty() {
+                return None;
+            }
+            let cumulative_months = event["cumulative_months"].as_u64()? as u32;
+            // streak_months is null when the viewer chose not to share it
+            let streak_months = event["streak_months"].as_u64().map(|m| m as u32);
+            Some(AlertPayload {
+                platform: "twitch".to_string(),
+                alert_type: "resub".to_string(),
+                user_name: user_name.clone(),
+                message: format!("{} resubscribed for {} months!", user_name, cumulative_months),
+                tier: event["tier"].as_str().and_then(tier_label),
+                is_gift: Some(false),
+                cumulative_months: Some(cumulative_months),
+                streak_months,
+                user_message: sanitize_user_message(event["message"]["text"].as_str()),
+                ..Default::default()
             })
         }
         "channel.subscription.gift" => {
@@ -72,9 +149,10 @@ pub fn process_twitch_event(payload: TwitchEventSubPayload) -> Option<AlertPaylo
                 alert_type: "gift".to_string(),
                 user_name: user_name.clone(),
                 message: format!("{} gifted {} subscriptions!", user_name, total),
-                amount: None,
-                currency: None,
                 count: Some(total),
+                tier: event["tier"].as_str().and_then(tier_label),
+                is_gift: Some(true),
+                ..Default::default()
             })
         }
         "channel.channel_points_custom_reward_redemption.add" => {
@@ -91,26 +169,715 @@ pub fn process_twitch_event(payload: TwitchEventSubPayload) -> Option<AlertPaylo
                 alert_type: "redemption".to_string(),
                 user_name: user_name.clone(),
                 message: format!("{} redeemed {}!", user_name, reward_title),
-                amount: None,
-                currency: None,
-                count: None,
+                user_message: sanitize_user_message(event["user_input"].as_str()),
+                ..Default::default()
+            })
+        }
+        "channel.follow" => {
+            let user_name = sanitize(event["user_name"].as_str()?);
+            if user_name.is_empty() {
+                return None;
+            }
+            Some(AlertPayload {
+                platform: "twitch".to_string(),
+                alert_type: "follow".to_string(),
+                user_name: user_name.clone(),
+                message: format!("{} just followed!", user_name),
+                ..Default::default()
+            })
+        }
+        "channel.cheer" => {
+            // Anonymous cheers carry null user fields
+            let user_name = if event["is_anonymous"].as_bool().unwrap_or(false) {
+                "Anonymous".to_string()
+            } else {
+                sanitize(event["user_name"].as_str()?)
+            };
+            if user_name.is_empty() {
+                return None;
+            }
+            let bits = event["bits"].as_u64()?;
+            if bits == 0 {
+                return None;
+            }
+            Some(AlertPayload {
+                platform: "twitch".to_string(),
+                alert_type: "cheer".to_string(),
+                user_name: user_name.clone(),
+                message: format!("{} cheered {} bits!", user_name, bits),
+                amount: Some(bits.to_string()),
+                currency: Some("bits".to_string()),
+                user_message: sanitize_user_message(event["message"].as_str()),
+                ..Default::default()
+            })
+        }
+        "channel.raid" => {
+            let user_name = sanitize(event["from_broadcaster_user_name"].as_str()?);
+            if user_name.is_empty() {
+                return None;
+            }
+            let viewers = event["viewers"].as_u64()? as u32;
+            Some(AlertPayload {
+                platform: "twitch".to_string(),
+                alert_type: "raid".to_string(),
+                user_name: user_name.clone(),
+                message: format!("{} is raiding with {} viewers!", user_name, viewers),
+                count: Some(viewers),
+                ..Default::default()
             })
         }
         _ => None,
     }
 }
 
-pub fn process_youtube_alert(xml_content: &str) -> Option<AlertPayload> {
-    if xml_content.contains("<yt:videoId>") {
-        return Some(AlertPayload {
-            platform: "youtube".to_string(),
-            alert_type: "live".to_string(),
-            user_name: "Channel".to_string(),
-            message: "A new stream or video is live!".to_string(),
-            amount: None,
-            currency: None,
-            count: None,
-        });
+/// Digits after the decimal point for an ISO 4217 currency code
+fn minor_units(currency: &str) -> u32 {
+    match currency.to_ascii_uppercase().as_str() {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX" | "VND" | "VUV"
+        | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+/// Format a Data API `amountMicros` value ("5000000") as a decimal amount in the currency's minor units
+/// ("5.00" for USD, "500" for JPY)
+fn format_micros(micros: &serde_json::Value, currency: Option<&str>) -> Option<String> {
+    // The API documents amountMicros as a string, but accept a number too
+    let micros = match micros {
+        serde_json::Value::String(s) => s.parse::<u64>().ok()?,
+        other => other.as_u64()?,
+    };
+    let decimals = currency.map_or(2, minor_units);
+    let whole = micros / 1_000_000;
+    if decimals == 0 {
+        return Some(whole.to_string());
+    }
+    let fraction = (micros % 1_000_000) / 10u64.pow(6 - decimals);
+    Some(format!("{}.{:0width$}", whole, fraction, width = decimals as usize))
+}
+
+/// Turn a paid or membership live chat event into an alert; plain chat and other types yield `None`
+pub fn process_youtube_chat_event(item: &LiveChatItem) -> Option<AlertPayload> {
+    let snippet = &item.snippet;
+    let user_name = sanitize(&item.author_details.as_ref()?.display_name);
+    if user_name.is_empty() {
+        return None;
+    }
+
+    match snippet.r#type.as_str() {
+        "superChatEvent" => {
+            let details = snippet.super_chat_details.as_ref()?;
+            let display = sanitize(details["amountDisplayString"].as_str()?);
+            Some(AlertPayload {
+                platform: "youtube".to_string(),
+                alert_type: "superchat".to_string(),
+                user_name: user_name.clone(),
+                message: format!("{} sent a {} Super Chat!", user_name, display),
+                amount: format_micros(&details["amountMicros"], details["currency"].as_str()),
+                currency: details["currency"].as_str().map(sanitize),
+                tier: details["tier"].as_u64().map(|t| format!("Tier {}", t)),
+                user_message: sanitize_user_message(details["userComment"].as_str()),
+                ..Default::default()
+            })
+        }
+        "superStickerEvent" => {
+            let details = snippet.super_sticker_details.as_ref()?;
+            let display = sanitize(details["amountDisplayString"].as_str()?);
+            Some(AlertPayload {
+                platform: "youtube".to_string(),
+                alert_type: "supersticker".to_string(),
+                user_name: user_name.clone(),
+                message: format!("{} sent a {} Super Sticker!", user_name, display),
+                amount: format_micros(&details["amountMicros"], details["currency"].as_str()),
+                currency: details["currency"].as_str().map(sanitize),
+                tier: details["tier"].as_u64().map(|t| format!("Tier {}", t)),
+                // Stickers have no comment; the alt text says what was sent
+                user_message: sanitize_user_message(details["superStickerMetadata"]["altText"].as_str()),
+                ..Default::default()
+            })
+        }
+        "newSponsorEvent" => {
+            let details = snippet.new_sponsor_details.clone().unwrap_or_default();
+            let level = details["memberLevelName"].as_str().map(sanitize).filter(|l| !l.is_empty());
+            let message = match (&level, details["isUpgrade"].as_bool().unwrap_or(false)) {
+                (Some(level), true) => format!("{} upgraded their membership to {}!", user_name, level),
+                _ => format!("{} just became a member!", user_name),
+            };
+            Some(AlertPayload {
+                platform: "youtube".to_string(),
+                alert_type: "membership".to_string(),
+                user_name,
+                message,
+                tier: level,
+                is_gift: Some(false),
+                ..Default::default()
+            })
+        }
+        "memberMilestoneChatEvent" => {
+            let details = snippet.member_milestone_chat_details.as_ref()?;
+            let months = details["memberMonth"].as_u64()? as u32;
+            Some(AlertPayload {
+                platform: "youtube".to_string(),
+                alert_type: "membership_milestone".to_string(),
+                user_name: user_name.clone(),
+                message: format!("{} has been a member for {} months!", user_name, months),
+                tier: details["memberLevelName"].as_str().map(sanitize).filter(|l| !l.is_empty()),
+                is_gift: Some(false),
+                cumulative_months: Some(months),
+                user_message: sanitize_user_message(details["userComment"].as_str()),
+                ..Default::default()
+            })
+        }
+        "membershipGiftingEvent" => {
+            let details = snippet.membership_gifting_details.as_ref()?;
+            let total = details["giftMembershipsCount"].as_u64()? as u32;
+            Some(AlertPayload {
+                platform: "youtube".to_string(),
+                alert_type: "membership_gift".to_string(),
+                user_name: user_name.clone(),
+                message: format!("{} gifted {} memberships!", user_name, total),
+                count: Some(total),
+                tier: details["giftMembershipsLevelName"].as_str().map(sanitize).filter(|l| !l.is_empty()),
+                is_gift: Some(true),
+                ..Default::default()
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Video ids already announced from the YouTube hub
+pub fn youtube_announced_videos() -> MessageIdCache {
+    MessageIdCache::with_limits(YOUTUBE_ANNOUNCED_CAPACITY, YOUTUBE_ANNOUNCED_TTL_SECS)
+}
+
+/// Announce the first video in a hub notification that has not been announced before.
+///
+/// The feed does not say whether a video is a live stream, a premiere or an upload, so the wording covers all three.
+pub fn process_youtube_alert(xml_content: &str, announced: &mut MessageIdCache, now: u64) -> Option<AlertPayload> {
+    let entries = match parse_youtube_feed(xml_content) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("Failed to parse YouTube notification: {}", e);
+            return None;
+        }
+    };
+
+    // The hub batches at most a handful of entries; announce the first new one
+    for entry in entries {
+        match entry {
+            YouTubeFeedEntry::Video(video) => {
+                let channel_title = sanitize(&video.channel_title);
+                let user_name = if channel_title.is_empty() {
+                    sanitize(&video.channel_id)
+                } else {
+                    channel_title
+                };
+                if user_name.is_empty() {
+                    continue;
+                }
+                if !announced.check_and_record(&video.video_id, now) {
+                    log::info!("YouTube video {} was already announced", video.video_id);
+                    continue;
+                }
+                let title = sanitize(&video.title);
+                let message = if title.is_empty() {
+                    format!("{} posted a new stream or video!", user_name)
+                } else {
+                    format!("{} posted a new stream or video: {}", user_name, title)
+                };
+                return Some(AlertPayload {
+                    platform: "youtube".to_string(),
+                    alert_type: "live".to_string(),
+                    user_name,
+                    message,
+                    ..Default::default()
+                });
+            }
+            YouTubeFeedEntry::Deleted(deleted) => {
+                log::info!("YouTube video {} was deleted, no alert sent", deleted.video_id);
+            }
+        }
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(body: &str) -> TwitchEventSubPayload {
+        serde_json::from_str(body).expect("valid EventSub body")
+    }
+
+    const FOLLOW_BODY: &str = r#"{
+        "subscription": {
+            "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+            "type": "channel.follow",
+            "version": "2",
+            "status": "enabled",
+            "cost": 0,
+            "condition": {
+                "broadcaster_user_id": "1337",
+                "moderator_user_id": "1337"
+            },
+            "transport": {
+                "method": "webhook",
+                "callback": "https://example.com/webhooks/callback"
+            },
+            "created_at": "2019-11-16T10:11:12.634234626Z"
+        },
+        "event": {
+            "user_id": "1234",
+            "user_login": "cool_user",
+            "user_name": "Cool_User",
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cooler_user",
+            "broadcaster_user_name": "Cooler_User",
+            "followed_at": "2020-07-15T18:16:11.17106713Z"
+        }
+    }"#;
+
+    const CHEER_BODY: &str = r#"{
+        "subscription": {
+            "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+            "type": "channel.cheer",
+            "version": "1",
+            "status": "enabled",
+            "cost": 0,
+            "condition": {
+                "broadcaster_user_id": "1337"
+            },
+            "transport": {
+                "method": "webhook",
+                "callback": "https://example.com/webhooks/callback"
+            },
+            "created_at": "2019-11-16T10:11:12.634234626Z"
+        },
+        "event": {
+            "is_anonymous": false,
+            "user_id": "1234",
+            "user_login": "cool_user",
+            "user_name": "Cool_User",
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cooler_user",
+            "broadcaster_user_name": "Cooler_User",
+            "message": "pogchamp",
+            "bits": 1000
+        }
+    }"#;
+
+    const ANONYMOUS_CHEER_BODY: &str = r#"{
+        "subscription": {
+            "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+            "type": "channel.cheer",
+            "version": "1",
+            "status": "enabled",
+            "cost": 0,
+            "condition": {
+                "broadcaster_user_id": "1337"
+            },
+            "transport": {
+                "method": "webhook",
+                "callback": "https://example.com/webhooks/callback"
+            },
+            "created_at": "2019-11-16T10:11:12.634234626Z"
+        },
+        "event": {
+            "is_anonymous": true,
+            "user_id": null,
+            "user_login": null,
+            "user_name": null,
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cooler_user",
+            "broadcaster_user_name": "Cooler_User",
+            "message": "Cheer100",
+            "bits": 100
+        }
+    }"#;
+
+    const SUBSCRIBE_BODY: &str = r#"{
+        "subscription": {
+            "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+            "type": "channel.subscribe",
+            "version": "1",
+            "status": "enabled",
+            "cost": 0,
+            "condition": {
+                "broadcaster_user_id": "1337"
+            },
+            "transport": {
+                "method": "webhook",
+                "callback": "https://example.com/webhooks/callback"
+            },
+            "created_at": "2019-11-16T10:11:12.634234626Z"
+        },
+        "event": {
+            "user_id": "1234",
+            "user_login": "cool_user",
+            "user_name": "Cool_User",
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cooler_user",
+            "broadcaster_user_name": "Cooler_User",
+            "tier": "2000",
+            "is_gift": true
+        }
+    }"#;
+
+    const RESUB_BODY: &str = r#"{
+        "subscription": {
+            "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+            "type": "channel.subscription.message",
+            "version": "1",
+            "status": "enabled",
+            "cost": 0,
+            "condition": {
+                "broadcaster_user_id": "1337"
+            },
+            "transport": {
+                "method": "webhook",
+                "callback": "https://example.com/webhooks/callback"
+            },
+            "created_at": "2019-11-16T10:11:12.634234626Z"
+        },
+        "event": {
+            "user_id": "1234",
+            "user_login": "cool_user",
+            "user_name": "Cool_User",
+            "broadcaster_user_id": "1337",
+            "broadcaster_user_login": "cooler_user",
+            "broadcaster_user_name": "Cooler_User",
+            "tier": "1000",
+            "message": {
+                "text": "Love the stream! FevziGG",
+                "emotes": [
+                    {
+                        "begin": 23,
+                        "end": 30,
+                        "id": "302976485"
+                    }
+                ]
+            },
+            "cumulative_months": 15,
+            "streak_months": 1,
+            "duration_months": 6
+        }
+    }"#;
+
+    const RAID_BODY: &str = r#"{
+        "subscription": {
+            "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+            "type": "channel.raid",
+            "version": "1",
+            "status": "enabled",
+            "cost": 0,
+            "condition": {
+                "to_broadcaster_user_id": "1337"
+            },
+            "transport": {
+                "method": "webhook",
+                "callback": "https://example.com/webhooks/callback"
+            },
+            "created_at": "2019-11-16T10:11:12.634234626Z"
+        },
+        "event": {
+            "from_broadcaster_user_id": "1234",
+            "from_broadcaster_user_login": "cool_user",
+            "from_broadcaster_user_name": "Cool_User",
+            "to_broadcaster_user_id": "1337",
+            "to_broadcaster_user_login": "cooler_user",
+            "to_broadcaster_user_name": "Cooler_User",
+            "viewers": 9001
+        }
+    }"#;
+
+    #[test]
+    fn renaming_rebuilds_only_the_opening_name() {
+        let mut redemption = parse(FOLLOW_BODY);
+        redemption.subscription.r#type = "channel.channel_points_custom_reward_redemption.add".to_string();
+        redemption.event = serde_json::json!({ "user_name": "bob", "reward": { "title": "Say hi to bob" } });
+        let mut alert = process_twitch_event(redemption).unwrap();
+        alert.set_user_name("b**".to_string());
+        assert_eq!(alert.user_name, "b**");
+        assert_eq!(alert.message, "b** redeemed Say hi to bob!");
+
+        let mut foreign = AlertPayload { user_name: "bob".to_string(), message: "Hello from bob".to_string(), ..Default::default() };
+        foreign.set_user_name("b**".to_string());
+        assert_eq!(foreign.message, "b**");
+    }
+
+    #[test]
+    fn follow_produces_alert() {
+        let alert = process_twitch_event(parse(FOLLOW_BODY)).unwrap();
+        assert_eq!(alert.platform, "twitch");
+        assert_eq!(alert.alert_type, "follow");
+        assert_eq!(alert.user_name, "Cool_User");
+        assert_eq!(alert.message, "Cool_User just followed!");
+        assert_eq!(alert.amount, None);
+        assert_eq!(alert.count, None);
+    }
+
+    #[test]
+    fn cheer_carries_bits_amount() {
+        let alert = process_twitch_event(parse(CHEER_BODY)).unwrap();
+        assert_eq!(alert.alert_type, "cheer");
+        assert_eq!(alert.user_name, "Cool_User");
+        assert_eq!(alert.amount.as_deref(), Some("1000"));
+        assert_eq!(alert.currency.as_deref(), Some("bits"));
+        assert_eq!(alert.message, "Cool_User cheered 1000 bits!");
+    }
+
+    #[test]
+    fn anonymous_cheer_is_not_dropped() {
+        let alert = process_twitch_event(parse(ANONYMOUS_CHEER_BODY)).unwrap();
+        assert_eq!(alert.user_name, "Anonymous");
+        assert_eq!(alert.amount.as_deref(), Some("100"));
+    }
+
+    #[test]
+    fn subscribe_carries_tier_and_gift_flag() {
+        let alert = process_twitch_event(parse(SUBSCRIBE_BODY)).unwrap();
+        assert_eq!(alert.alert_type, "sub");
+        assert_eq!(alert.tier.as_deref(), Some("Tier 2"));
+        assert_eq!(alert.is_gift, Some(true));
+    }
+
+    #[test]
+    fn resub_carries_months_tier_and_message() {
+        let alert = process_twitch_event(parse(RESUB_BODY)).unwrap();
+        assert_eq!(alert.alert_type, "resub");
+        assert_eq!(alert.user_name, "Cool_User");
+        assert_eq!(alert.tier.as_deref(), Some("Tier 1"));
+        assert_eq!(alert.cumulative_months, Some(15));
+        assert_eq!(alert.streak_months, Some(1));
+        assert_eq!(alert.user_message.as_deref(), Some("Love the stream! FevziGG"));
+        assert_eq!(alert.message, "Cool_User resubscribed for 15 months!");
+    }
+
+    #[test]
+    fn resub_without_shared_streak_or_text() {
+        let body = RESUB_BODY
+            .replace("\"streak_months\": 1", "\"streak_months\": null")
+            .replace("Love the stream! FevziGG", "  ");
+        let alert = process_twitch_event(parse(&body)).unwrap();
+        assert_eq!(alert.streak_months, None);
+        assert_eq!(alert.user_message, None);
+    }
+
+    #[test]
+    fn resub_message_is_sanitized() {
+        let body = RESUB_BODY.replace("Love the stream! FevziGG", "<script>hi</script>");
+        let alert = process_twitch_event(parse(&body)).unwrap();
+        assert_eq!(alert.user_message.as_deref(), Some("_script_hi_/script_"));
+    }
+
+    #[test]
+    fn tier_labels() {
+        assert_eq!(tier_label("1000").as_deref(), Some("Tier 1"));
+        assert_eq!(tier_label("2000").as_deref(), Some("Tier 2"));
+        assert_eq!(tier_label("3000").as_deref(), Some("Tier 3"));
+        assert_eq!(tier_label("prime"), None);
+    }
+
+    #[test]
+    fn raid_carries_viewer_count() {
+        let alert = process_twitch_event(parse(RAID_BODY)).unwrap();
+        assert_eq!(alert.alert_type, "raid");
+        assert_eq!(alert.user_name, "Cool_User");
+        assert_eq!(alert.count, Some(9001));
+        assert_eq!(alert.message, "Cool_User is raiding with 9001 viewers!");
+    }
+
+    #[test]
+    fn follow_with_html_in_name_is_sanitized() {
+        let body = FOLLOW_BODY.replace("\"user_name\": \"Cool_User\"", "\"user_name\": \"<b>Evil</b>\"");
+        let alert = process_twitch_event(parse(&body)).unwrap();
+        assert_eq!(alert.user_name, "_b_Evil_/b_");
+    }
+
+    #[test]
+    fn youtube_alert_names_channel_and_video() {
+        let xml = r#"<feed xmlns:yt="http://www.youtube.com/xml/schemas/2015" xmlns="http://www.w3.org/2005/Atom">
+  <entry>
+    <yt:videoId>abc</yt:videoId>
+    <yt:channelId>UC1</yt:channelId>
+    <title>Evening stream</title>
+    <author><name>Echo Channel</name></author>
+  </entry>
+</feed>"#;
+        let alert = process_youtube_alert(xml, &mut youtube_announced_videos(), 0).unwrap();
+        assert_eq!(alert.platform, "youtube");
+        assert_eq!(alert.user_name, "Echo Channel");
+        assert_eq!(alert.message, "Echo Channel posted a new stream or video: Evening stream");
+    }
+
+    #[test]
+    fn youtube_video_is_announced_once() {
+        let entry = |video_id: &str, title: &str, published: &str, updated: &str| {
+            format!(
+                r#"<feed xmlns:yt="http://www.youtube.com/xml/schemas/2015" xmlns="http://www.w3.org/2005/Atom">
+  <entry>
+    <yt:videoId>{}</yt:videoId>
+    <yt:channelId>UC1</yt:channelId>
+    <title>{}</title>
+    <author><name>Echo Channel</name></author>
+    <published>{}</published>
+    <updated>{}</updated>
+  </entry>
+</feed>"#,
+                video_id, title, published, updated
+            )
+        };
+        let mut announced = youtube_announced_videos();
+        // A stream scheduled days ago still gets announced when its first push arrives
+        let scheduled = entry("abc", "Evening stream", "2024-03-09T19:00:00+00:00", "2024-03-11T19:00:00+00:00");
+        assert!(process_youtube_alert(&scheduled, &mut announced, 100).is_some());
+        // Edits within the first minutes are pushed again and stay quiet
+        let edited = entry("abc", "Evening stream (fixed title)", "2024-03-09T19:00:00+00:00", "2024-03-11T19:05:00+00:00");
+        assert!(process_youtube_alert(&edited, &mut announced, 400).is_none());
+        let other = entry("def", "Morning upload", "2024-03-12T08:00:00+00:00", "2024-03-12T08:00:00+00:00");
+        assert!(process_youtube_alert(&other, &mut announced, 500).is_some());
+    }
+
+    #[test]
+    fn youtube_deletion_does_not_alert() {
+        let xml = r#"<feed xmlns:at="http://purl.org/atompub/tombstones/1.0" xmlns="http://www.w3.org/2005/Atom">
+  <at:deleted-entry ref="yt:video:abc" when="2024-03-10T08:00:00+00:00"/>
+</feed>"#;
+        assert!(process_youtube_alert(xml, &mut youtube_announced_videos(), 0).is_none());
+    }
+
+    #[test]
+    fn unknown_type_is_ignored() {
+        let body = FOLLOW_BODY.replace("channel.follow", "channel.update");
+        assert!(process_twitch_event(parse(&body)).is_none());
+    }
+
+    fn live_chat_item(snippet: serde_json::Value) -> LiveChatItem {
+        serde_json::from_value(serde_json::json!({
+            "kind": "youtube#liveChatMessage",
+            "id": "LCC.abc",
+            "snippet": snippet,
+            "authorDetails": {
+                "channelId": "UC123",
+                "displayName": "@Supporter",
+                "isChatSponsor": true
+            }
+        }))
+        .expect("valid liveChatMessage")
+    }
+
+    #[test]
+    fn youtube_super_chat_carries_amount_and_comment() {
+        let item = live_chat_item(serde_json::json!({
+            "type": "superChatEvent",
+            "liveChatId": "chat-1",
+            "publishedAt": "2024-05-01T12:00:00Z",
+            "displayMessage": "$5.00 from @Supporter: <b>great stream</b>",
+            "superChatDetails": {
+                "amountMicros": "5000000",
+                "currency": "USD",
+                "amountDisplayString": "$5.00",
+                "userComment": "<b>great stream</b>",
+                "tier": 2
+            }
+        }));
+        let alert = process_youtube_chat_event(&item).unwrap();
+        assert_eq!(alert.platform, "youtube");
+        assert_eq!(alert.alert_type, "superchat");
+        assert_eq!(alert.user_name, "@Supporter");
+        assert_eq!(alert.message, "@Supporter sent a $5.00 Super Chat!");
+        assert_eq!(alert.amount.as_deref(), Some("5.00"));
+        assert_eq!(alert.currency.as_deref(), Some("USD"));
+        assert_eq!(alert.tier.as_deref(), Some("Tier 2"));
+        assert_eq!(alert.user_message.as_deref(), Some("_b_great stream_/b_"));
+    }
+
+    #[test]
+    fn youtube_super_sticker_uses_alt_text() {
+        let item = live_chat_item(serde_json::json!({
+            "type": "superStickerEvent",
+            "liveChatId": "chat-1",
+            "superStickerDetails": {
+                "superStickerMetadata": {"stickerId": "s1", "altText": "Dancing cat", "language": "en"},
+                "amountMicros": "1990000",
+                "currency": "EUR",
+                "amountDisplayString": "€1.99",
+                "tier": 1
+            }
+        }));
+        let alert = process_youtube_chat_event(&item).unwrap();
+        assert_eq!(alert.alert_type, "supersticker");
+        assert_eq!(alert.amount.as_deref(), Some("1.99"));
+        assert_eq!(alert.currency.as_deref(), Some("EUR"));
+        assert_eq!(alert.user_message.as_deref(), Some("Dancing cat"));
+    }
+
+    #[test]
+    fn youtube_amounts_use_currency_minor_units() {
+        let cases = [
+            ("500000000", "JPY", "500"),
+            ("1500000", "KWD", "1.500"),
+            ("5000000", "USD", "5.00"),
+            ("199000000", "RUB", "199.00"),
+        ];
+        for (micros, currency, expected) in cases {
+            let item = live_chat_item(serde_json::json!({
+                "type": "superChatEvent",
+                "liveChatId": "chat-1",
+                "superChatDetails": {
+                    "amountMicros": micros,
+                    "currency": currency,
+                    "amountDisplayString": "¥500",
+                    "tier": 1
+                }
+            }));
+            let alert = process_youtube_chat_event(&item).unwrap();
+            assert_eq!(alert.amount.as_deref(), Some(expected), "{} {}", micros, currency);
+        }
+    }
+
+    #[test]
+    fn youtube_memberships() {
+        let upgrade = live_chat_item(serde_json::json!({
+            "type": "newSponsorEvent",
+            "liveChatId": "chat-1",
+            "newSponsorDetails": {"memberLevelName": "Gold", "isUpgrade": true}
+        }));
+        let alert = process_youtube_chat_event(&upgrade).unwrap();
+        assert_eq!(alert.alert_type, "membership");
+        assert_eq!(alert.message, "@Supporter upgraded their membership to Gold!");
+        assert_eq!(alert.tier.as_deref(), Some("Gold"));
+
+        let milestone = live_chat_item(serde_json::json!({
+            "type": "memberMilestoneChatEvent",
+            "liveChatId": "chat-1",
+            "memberMilestoneChatDetails": {"userComment": "a year already", "memberMonth": 12, "memberLevelName": "Gold"}
+        }));
+        let alert = process_youtube_chat_event(&milestone).unwrap();
+        assert_eq!(alert.alert_type, "membership_milestone");
+        assert_eq!(alert.cumulative_months, Some(12));
+        assert_eq!(alert.user_message.as_deref(), Some("a year already"));
+
+        let gift = live_chat_item(serde_json::json!({
+            "type": "membershipGiftingEvent",
+            "liveChatId": "chat-1",
+            "membershipGiftingDetails": {"giftMembershipsCount": 5, "giftMembershipsLevelName": "Gold"}
+        }));
+        let alert = process_youtube_chat_event(&gift).unwrap();
+        assert_eq!(alert.alert_type, "membership_gift");
+        assert_eq!(alert.count, Some(5));
+        assert_eq!(alert.is_gift, Some(true));
+    }
+
+    #[test]
+    fn youtube_text_message_is_not_an_alert() {
+        let item = live_chat_item(serde_json::json!({
+            "type": "textMessageEvent",
+            "liveChatId": "chat-1",
+            "textMessageDetails": {"messageText": "hi"}
+        }));
+        assert!(process_youtube_chat_event(&item).is_none());
+    }
+}
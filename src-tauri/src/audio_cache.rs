@@ -0,0 +1,288 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use tokio::sync::Mutex;
+
+pub const DEFAULT_CACHE_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Everything that changes the produced audio; two requests with equal parts share a cache entry
+pub struct CacheKeyParts<'a> {
+    pub provider: &'a str,
+    pub voice: &'a str,
+    pub model: &'a str,
+    pub settings: &'a str,
+    pub text: &'a str,
+}
+
+/// Whitespace differences never change the spoken result
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub fn cache_key(parts: &CacheKeyParts) -> String {
+    let mut hasher = Sha256::new();
+    // Length-prefix each field so ("ab", "c") and ("a", "bc") hash differently
+    for field in [parts.provider, parts.voice, parts.model, parts.settings, &normalize_text(parts.text)] {
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field.as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn extension_for(mime_type: &str) -> &'static str {
+    match mime_type {
+        "audio/wav" => "wav",
+        "audio/ogg" => "ogg",
+        _ => "mp3",
+    }
+}
+
+fn mime_for(extension: &str) -> &'static str {
+    match extension {
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        _ => "audio/mpeg",
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AudioCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub entries: usize,
+    pub total_bytes: u64,
+    pub max_bytes: u64,
+}
+
+struct CacheEntry {
+    path: PathBuf,
+    size: u64,
+    /// Higher is more recent
+    last_used: u64,
+}
+
+struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+    total_bytes: u64,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl CacheIndex {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.total_bytes -= entry.size;
+            if let Err(e) = std::fs::remove_file(&entry.path) {
+                log::warn!("Failed to remove cached audio {}: {}", entry.path.display(), e);
+            }
+        }
+    }
+}
+
+fn evict_over_limit(index: &mut CacheIndex, max_bytes: u64) {
+    while index.total_bytes > max_bytes {
+        let Some(oldest) = index
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(key, _)| key.clone())
+        else {
+            break;
+        };
+        index.remove(&oldest);
+        index.evictions += 1;
+    }
+}
+
+/// Content-addressed on-disk cache of synthesized audio with least-recently-used eviction.
+///
+/// Files are named `<sha256>.<ext>`; recency survives restarts through file modification times.
+pub struct AudioCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<CacheIndex>,
+}
+
+impl AudioCache {
+    pub fn open(dir: PathBuf, max_bytes: u64) -> Self {
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            log::warn!("Failed to create audio cache directory {}: {}", dir.display(), e);
+        }
+
+        let mut found: Vec<(String, PathBuf, u64, SystemTime)> = std::fs::read_dir(&dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let key = path.file_stem()?.to_str()?.to_string();
+                if key.len() != 64 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return None;
+                }
+                let metadata = std::fs::metadata(&path).ok()?;
+                Some((key, path, metadata.len(), metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)))
+            })
+            .collect();
+        found.sort_by_key(|(_, _, _, modified)| *modified);
+
+        let mut index = CacheIndex {
+            entries: HashMap::new(),
+            total_bytes: 0,
+            clock: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        };
+        for (key, path, size, _) in found {
+            let last_used = index.tick();
+            index.total_bytes += size;
+            index.entries.insert(key, CacheEntry { path, size, last_used });
+        }
+
+        // The limit may have shrunk since the last run
+        evict_over_limit(&mut index, max_bytes);
+
+        AudioCache {
+            dir,
+            max_bytes,
+            index: Mutex::new(index),
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> Option<(Vec<u8>, &'static str)> {
+        let mut index = self.index.lock().await;
+        let tick = index.tick();
+        let Some(entry) = index.entries.get_mut(key) else {
+            index.misses += 1;
+            return None;
+        };
+        entry.last_used = tick;
+        let path = entry.path.clone();
+
+        match std::fs::read(&path) {
+            Ok(data) => {
+                index.hits += 1;
+                // Keep the on-disk recency in step for the next start
+                if let Ok(file) = std::fs::File::options().append(true).open(&path) {
+                    let _ = file.set_modified(SystemTime::now());
+                }
+                let mime = mime_for(path.extension().and_then(|e| e.to_str()).unwrap_or(""));
+                Some((data, mime))
+            }
+            Err(e) => {
+                log::warn!("Dropping unreadable cached audio {}: {}", path.display(), e);
+                index.misses += 1;
+                index.remove(key);
+                None
+            }
+        }
+    }
+
+    pub async fn put(&self, key: &str, data: &[u8], mime_type: &str) {
+        let size = data.len() as u64;
+        if size > self.max_bytes {
+            return;
+        }
+
+        let mut index = self.index.lock().await;
+        index.remove(key);
+
+        let path = self.dir.join(format!("{}.{}", key, extension_for(mime_type)));
+        if let Err(e) = std::fs::write(&path, data) {
+            log::warn!("Failed to cache audio at {}: {}", path.display(), e);
+            return;
+        }
+        let last_used = index.tick();
+        index.total_bytes += size;
+        index.entries.insert(key.to_string(), CacheEntry { path, size, last_used });
+        evict_over_limit(&mut index, self.max_bytes);
+    }
+
+    pub async fn stats(&self) -> AudioCacheStats {
+        let index = self.index.lock().await;
+        AudioCacheStats {
+            hits: index.hits,
+            misses: index.misses,
+            evictions: index.evictions,
+            entries: index.entries.len(),
+            total_bytes: index.total_bytes,
+            max_bytes: self.max_bytes,
+        }
+    }
+
+    /// Delete every cached file; metrics are kept
+    pub async fn purge(&self) {
+        let mut index = self.index.lock().await;
+        let keys: Vec<String> = index.entries.keys().cloned().collect();
+        for key in keys {
+            index.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("streamtts-audio-cache-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn key(text: &str) -> String {
+        cache_key(&CacheKeyParts { provider: "elevenlabs", voice: "v1", model: "m1", settings: "0.5/0.5", text })
+    }
+
+    #[test]
+    fn key_covers_every_part_but_not_whitespace() {
+        assert_eq!(key("X just subscribed!"), key("  X   just subscribed!\n"));
+        assert_ne!(key("X just subscribed!"), key("Y just subscribed!"));
+        let other_voice = cache_key(&CacheKeyParts {
+            provider: "elevenlabs",
+            voice: "v2",
+            model: "m1",
+            settings: "0.5/0.5",
+            text: "X just subscribed!",
+        });
+        assert_ne!(key("X just subscribed!"), other_voice);
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used_and_counts() {
+        let dir = scratch_dir("lru");
+        let cache = AudioCache::open(dir.clone(), 10);
+
+        cache.put(&key("a"), b"aaaa", "audio/mpeg").await;
+        cache.put(&key("b"), b"bbbb", "audio/wav").await;
+        assert_eq!(cache.get(&key("a")).await, Some((b"aaaa".to_vec(), "audio/mpeg")));
+        // "b" is now the least recently used and makes room for "c"
+        cache.put(&key("c"), b"cccc", "audio/mpeg").await;
+
+        assert!(cache.get(&key("b")).await.is_none());
+        assert_eq!(cache.get(&key("c")).await.unwrap().0, b"cccc");
+
+        let stats = cache.stats().await;
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (2, 1, 1));
+        assert_eq!((stats.entries, stats.total_bytes), (2, 8));
+
+        // Entries survive a restart
+        drop(cache);
+        let reopened = AudioCache::open(dir.clone(), 10);
+        assert_eq!(reopened.get(&key("a")).await.unwrap().0, b"aaaa");
+
+        reopened.purge().await;
+        assert_eq!(reopened.stats().await.entries, 0);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A chat badge such as `subscriber/12` or `moderator/1`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatBadge {
+    pub name: String,
+    pub version: String,
+}
+
+/// Position of an emote inside the message text, in characters
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatEmote {
+    pub id: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// What the sender is in the channel, as far as the platform tells us
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChatRoles {
+    #[serde(default)]
+    pub broadcaster: bool,
+    #[serde(default)]
+    pub moderator: bool,
+    #[serde(default)]
+    pub vip: bool,
+    /// Twitch subscriber or YouTube channel member
+    #[serde(default)]
+    pub subscriber: bool,
+    /// Only looked up when a permission needs it; `None` when unknown or not following
+    #[serde(default)]
+    pub followed_at: Option<DateTime<Utc>>,
+}
+
+/// A chat message received by one of the native chat clients
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub platform: String,
+    /// Platform message id, used for deduplication and moderation
+    pub id: String,
+    pub channel: String,
+    pub user_id: String,
+    /// Login or handle, stable and lowercase where the platform provides one
+    pub user_name: String,
+    pub display_name: String,
+    pub text: String,
+    pub color: Option<String>,
+    pub badges: Vec<ChatBadge>,
+    #[serde(default)]
+    pub roles: ChatRoles,
+    pub emotes: Vec<ChatEmote>,
+    pub bits: Option<u32>,
+    /// Twitch `msg-id` tag, e.g. `highlighted-message`
+    pub msg_id: Option<String>,
+    /// `/me` messages
+    pub is_action: bool,
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
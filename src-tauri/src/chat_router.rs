@@ -0,0 +1,236 @@
+use crate::chat::ChatMessage;
+use crate::message_filter::FilterDecision;
+use crate::moderation::AuditEntry;
+use crate::spam_guard::SuppressReason;
+use crate::ssml;
+use crate::tts_queue::{NewQueueItem, QueuePriority};
+use crate::viewer_voices::VoiceCommandOutcome;
+use crate::{prepare_queue_item, AppState};
+
+/// What became of one chat message
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatOutcome {
+    /// The sender lacks the role the message needs
+    NotPermitted,
+    /// A moderation command; it acts on the queue and is not read out
+    Moderation(Result<AuditEntry, String>),
+    /// A `!voice` command; it changes the viewer's voice and is not read out
+    Voice(Result<VoiceCommandOutcome, String>),
+    /// Denied by a filter rule, `rule` being its name
+    Filtered { rule: Option<String> },
+    Suppressed(SuppressReason),
+    Queued,
+}
+
+/// Decide what to do with a chat message and queue it for reading when it passes every check.
+///
+/// Permissions come first, then moderation commands, so moderators are heard even while muted.
+/// A muted viewer gets nothing else, their voice commands included.
+/// The permission check may fill in the sender's follow date, hence `&mut`.
+pub async fn route_chat_message(state: &AppState, message: &mut ChatMessage) -> ChatOutcome {
+    if !state.permissions.check(message).await {
+        return ChatOutcome::NotPermitted;
+    }
+    if let Some(result) = state.moderation.handle_command(message).await {
+        return ChatOutcome::Moderation(result);
+    }
+    if let Some(until) = state.moderation.muted_until(&message.platform, &message.user_name).await {
+        return ChatOutcome::Suppressed(SuppressReason::Muted { until });
+    }
+    if let Some(result) = state.viewer_voices.handle_command(&message.platform, &message.user_name, &message.text).await {
+        return ChatOutcome::Voice(result);
+    }
+
+    let text = match state.message_filter.evaluate(message).await {
+        FilterDecision::Allow { text, .. } => text,
+        FilterDecision::Deny { rule } => return ChatOutcome::Filtered { rule },
+    };
+    let (Some(name), Some(text)) = (
+        state.profanity.apply_name(&message.display_name).await,
+        state.profanity.apply(&text).await,
+    ) else {
+        return ChatOutcome::Suppressed(SuppressReason::Profanity);
+    };
+    if let Err(reason) = state.spam_guard.check(&message.platform, &message.user_name, &text).await {
+        return ChatOutcome::Suppressed(reason);
+    }
+
+    let voice = state.viewer_voices.voice_for(&message.platform, &message.user_name).await;
+    let mut item = NewQueueItem {
+        platform: message.platform.clone(),
+        speech: Some(ssml::chat_speech(&name, &text)),
+        user_name: name,
+        text,
+        priority: QueuePriority::Chat,
+        voice,
+    };
+    prepare_queue_item(state, &mut item).await;
+    state.tts_queue.lock().await.enqueue(item);
+    ChatOutcome::Queued
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::ChatRoles;
+    use crate::elevenlabs::{ElevenLabsClient, ElevenLabsSettingsStore};
+    use crate::eventsub::{EventSubSinks, SubscriptionHealthRegistry};
+    use crate::eventsub_subscriptions::EventSubManager;
+    use crate::eventsub_ws::EventSubWebSocket;
+    use crate::local_tts::LocalTts;
+    use crate::message_filter::MessageFilter;
+    use crate::moderation::{AuditStore, ModerationAction, Moderation, MuteStore};
+    use crate::permissions::{FollowerLookup, PermissionStore, Permissions};
+    use crate::profanity::ProfanityFilter;
+    use crate::pronunciation::{PronunciationDictionary, PronunciationRule, RuleKind};
+    use crate::spam_guard::{SpamGuard, SpamGuardStore};
+    use crate::tts::{TtsChainStore, TtsRegistry};
+    use crate::tts_queue::TtsQueue;
+    use crate::twitch_api::{HelixClient, TwitchCredentialStore};
+    use crate::twitch_chat::TwitchChatClient;
+    use crate::viewer_voices::{ViewerVoiceStore, ViewerVoices, VoiceChoice};
+    use crate::youtube_chat::{YouTubeChatPoller, YouTubeCredentialStore};
+    use std::sync::Arc;
+    use tokio::sync::{broadcast, Mutex, RwLock};
+
+    fn app_state() -> AppState {
+        let (oauth_sender, _) = broadcast::channel(4);
+        let (alert_sender, _) = broadcast::channel(4);
+        let (revocation_sender, _) = broadcast::channel(4);
+        let (chat_sender, _) = broadcast::channel(4);
+        let (queue_sender, _) = broadcast::channel(64);
+        let eventsub_health = Arc::new(RwLock::new(SubscriptionHealthRegistry::new()));
+        let eventsub_ws = Arc::new(EventSubWebSocket::new(EventSubSinks {
+            alert_sender: alert_sender.clone(),
+            revocation_sender: revocation_sender.clone(),
+            health: eventsub_health.clone(),
+        }));
+        let twitch_credentials = Arc::new(TwitchCredentialStore::load(None));
+        let youtube_credentials = Arc::new(YouTubeCredentialStore::load(None));
+        let tts_queue = Arc::new(Mutex::new(TtsQueue::new(queue_sender)));
+        AppState {
+            eventsub_manager: Arc::new(EventSubManager::new(
+                HelixClient::new(),
+                twitch_credentials.clone(),
+                eventsub_ws.clone(),
+                eventsub_health.clone(),
+            )),
+            twitch_chat: Arc::new(TwitchChatClient::new(chat_sender.clone(), twitch_credentials.clone())),
+            youtube_chat: Arc::new(YouTubeChatPoller::new(chat_sender, alert_sender.clone(), youtube_credentials.clone())),
+            youtube_credentials,
+            elevenlabs: ElevenLabsClient::new(Arc::new(ElevenLabsSettingsStore::load(None))),
+            local_tts: LocalTts::new(None),
+            tts: Arc::new(TtsRegistry::new(Vec::new(), TtsChainStore::load(None))),
+            audio_cache: None,
+            pronunciation: PronunciationDictionary::load(None),
+            viewer_voices: Arc::new(ViewerVoices::new(Arc::new(ViewerVoiceStore::load(None)))),
+            message_filter: Arc::new(MessageFilter::load(None)),
+            permissions: Arc::new(Permissions::new(
+                Arc::new(PermissionStore::load(None)),
+                FollowerLookup::new(HelixClient::new(), twitch_credentials),
+            )),
+            spam_guard: Arc::new(SpamGuard::new(Arc::new(SpamGuardStore::load(None)))),
+            profanity: Arc::new(ProfanityFilter::load(None)),
+            moderation: Arc::new(Moderation::new(
+                tts_queue.clone(),
+                Arc::new(MuteStore::load(None)),
+                Arc::new(AuditStore::load(None)),
+            )),
+            tts_queue,
+            oauth_sender,
+            alert_sender,
+            revocation_sender,
+            eventsub_health,
+            eventsub_ws,
+        }
+    }
+
+    fn chat(user_name: &str, text: &str, moderator: bool) -> ChatMessage {
+        ChatMessage {
+            platform: "twitch".to_string(),
+            user_name: user_name.to_string(),
+            display_name: user_name.to_string(),
+            text: text.to_string(),
+            roles: ChatRoles { moderator, ..Default::default() },
+            ..Default::default()
+        }
+    }
+
+    async fn route(state: &AppState, user_name: &str, text: &str, moderator: bool) -> ChatOutcome {
+        route_chat_message(state, &mut chat(user_name, text, moderator)).await
+    }
+
+    #[tokio::test]
+    async fn routes_commands_filters_and_chat() {
+        let state = app_state();
+
+        assert_eq!(route(&state, "viewer", "!skip", false).await, ChatOutcome::NotPermitted);
+        let ChatOutcome::Moderation(Ok(entry)) = route(&state, "a_mod", "!skip", true).await else { panic!() };
+        assert_eq!(entry.action, ModerationAction::Skip { skipped: None });
+        assert!(matches!(route(&state, "viewer", "!voice robot", false).await, ChatOutcome::Voice(Ok(VoiceCommandOutcome::Unknown { .. }))));
+        assert_eq!(route(&state, "viewer", "hello chat", false).await, ChatOutcome::Filtered { rule: None });
+
+        assert_eq!(route(&state, "alice", "привет всем", false).await, ChatOutcome::Queued);
+        assert_eq!(route(&state, "alice", "как дела", false).await, ChatOutcome::Suppressed(SuppressReason::UserCooldown { retry_after_secs: 5 }));
+        let current = state.tts_queue.lock().await.snapshot().current.unwrap();
+        assert_eq!((current.user_name.as_str(), current.text.as_str()), ("alice", "привет всем"));
+    }
+
+    #[tokio::test]
+    async fn queued_chat_is_normalized_for_every_provider() {
+        let state = app_state();
+
+        assert_eq!(route(&state, "alice", "у меня 5 котов", false).await, ChatOutcome::Queued);
+        let current = state.tts_queue.lock().await.snapshot().current.unwrap();
+        assert_eq!(current.text, "у меня 5 котов");
+        assert_eq!(current.speech.unwrap().plain_text(), "alice у меня пять котов");
+    }
+
+    #[tokio::test]
+    async fn queued_chat_has_pronunciation_rules_applied() {
+        let state = app_state();
+        state
+            .pronunciation
+            .add(PronunciationRule {
+                id: 0,
+                pattern: "кот".to_string(),
+                replacement: "котик".to_string(),
+                kind: RuleKind::Literal,
+                case_sensitive: false,
+                language: None,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(route(&state, "alice", "у меня кот", false).await, ChatOutcome::Queued);
+        let current = state.tts_queue.lock().await.snapshot().current.unwrap();
+        assert_eq!(current.text, "у меня кот");
+        assert_eq!(current.speech.unwrap().plain_text(), "alice у меня котик");
+    }
+
+    #[tokio::test]
+    async fn muted_viewers_cannot_use_voice_commands() {
+        let state = app_state();
+        state
+            .viewer_voices
+            .set_choices(vec![VoiceChoice { name: "robot".to_string(), voice: "robot-voice".to_string() }])
+            .await
+            .unwrap();
+
+        let ChatOutcome::Moderation(Ok(entry)) = route(&state, "a_mod", "!ttsmute spammer", true).await else { panic!() };
+        let ModerationAction::Mute { until, .. } = entry.action else { panic!("{:?}", entry.action) };
+        let muted = ChatOutcome::Suppressed(SuppressReason::Muted { until });
+        assert_eq!(route(&state, "spammer", "!voice robot", false).await, muted);
+        assert_eq!(route(&state, "spammer", "привет всем", false).await, muted);
+        assert_eq!(state.viewer_voices.voice_for("twitch", "spammer").await, None);
+        assert!(state.tts_queue.lock().await.snapshot().current.is_none());
+
+        // A muted moderator can still moderate
+        route(&state, "a_mod", "!ttsmute a_mod", true).await;
+        assert!(matches!(route(&state, "a_mod", "!ttsunmute spammer", true).await, ChatOutcome::Moderation(Ok(_))));
+        assert_eq!(
+            route(&state, "spammer", "!voice robot", false).await,
+            ChatOutcome::Voice(Ok(VoiceCommandOutcome::Selected { choice: "robot".to_string() }))
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::storage::JsonStore;
+
+const DEFAULT_ELEVENLABS_BASE_URL: &str = "https://api.elevenlabs.io";
+pub const DEFAULT_VOICE_ID: &str = "IKne3meq5aSn9XLyUdCD";
+pub const DEFAULT_MODEL_ID: &str = "eleven_multilingual_v2";
+pub const DEFAULT_STABILITY: f32 = 0.5;
+pub const DEFAULT_SIMILARITY_BOOST: f32 = 0.5;
+pub const OUTPUT_FORMAT: &str = "mp3_44100_128";
+
+fn get_elevenlabs_base_url() -> String {
+    std::env::var("ELEVENLABS_BASE_URL")
+        .unwrap_or_else(|_| DEFAULT_ELEVENLABS_BASE_URL.to_string())
+}
+
+/// The ElevenLabs API key lives only in the backend; the webview can set it but never read it back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElevenLabsSettings {
+    pub api_key: String,
+}
+
+/// ElevenLabs settings persisted in the app data directory
+pub type ElevenLabsSettingsStore = JsonStore<ElevenLabsSettings>;
+
+/// Failures the UI reacts to differently, serialized as `{"kind": "...", ...}`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ElevenLabsError {
+    MissingApiKey,
+    Unauthorized { message: String },
+    QuotaExceeded { message: String },
+    RateLimited { message: String, retry_after_secs: Option<u64> },
+    InvalidRequest { message: String },
+    Api { status: u16, message: String },
+    Network { message: String },
+}
+
+impl fmt::Display for ElevenLabsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElevenLabsError::MissingApiKey => write!(f, "ElevenLabs API key is not set"),
+            ElevenLabsError::Unauthorized { message } => write!(f, "ElevenLabs rejected the API key: {}", message),
+            ElevenLabsError::QuotaExceeded { message } => write!(f, "ElevenLabs character quota exceeded: {}", message),
+            ElevenLabsError::RateLimited { message, .. } => write!(f, "ElevenLabs rate limit reached: {}", message),
+            ElevenLabsError::InvalidRequest { message } => write!(f, "Invalid ElevenLabs request: {}", message),
+            ElevenLabsError::Api { status, message } => write!(f, "ElevenLabs request failed ({}): {}", status, message),
+            ElevenLabsError::Network { message } => write!(f, "Failed to reach ElevenLabs: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ElevenLabsError {}
+
+/// ElevenLabs errors look like `{"detail": {"status": "quota_exceeded", "message": "..."}}`,
+/// or carry a list of validation errors for 422s
+async fn error_from_response(response: reqwest::Response) -> ElevenLabsError {
+    let status = response.status();
+    let retry_after_secs = response
+        .headers()
+        .get("retry-after")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok());
+    let body = response.text().await.unwrap_or_default();
+    let detail = serde_json::from_str::<serde_json::Value>(&body)
+        .map(|v| v["detail"].clone())
+        .unwrap_or(serde_json::Value::Null);
+
+    let detail_status = detail["status"].as_str().unwrap_or("");
+    let message = match &detail {
+        serde_json::Value::String(message) => message.clone(),
+        serde_json::Value::Array(errors) => errors
+            .iter()
+            .filter_map(|e| e["msg"].as_str())
+            .collect::<Vec<_>>()
+            .join("; "),
+        _ => detail["message"].as_str().map(|m| m.to_string()).unwrap_or(body),
+    };
+
+    // Quota errors arrive as 401 with a distinguishing status, so check it first
+    if detail_status == "quota_exceeded" {
+        return ElevenLabsError::QuotaExceeded { message };
+    }
+    match status.as_u16() {
+        401 => ElevenLabsError::Unauthorized { message },
+        429 => ElevenLabsError::RateLimited { message, retry_after_secs },
+        400 | 422 => ElevenLabsError::InvalidRequest { message },
+        code => ElevenLabsError::Api { status: code, message },
+    }
+}
+
+fn network_error(e: reqwest::Error) -> ElevenLabsError {
+    ElevenLabsError::Network { message: e.to_string() }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElevenLabsVoice {
+    pub voice_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub labels: std::collections::HashMap<String, String>,
+    #[serde(default)]
+    pub preview_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VoiceList {
+    voices: Vec<ElevenLabsVoice>,
+}
+
+/// Character usage from `GET /v1/user/subscription`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ElevenLabsUsage {
+    pub tier: String,
+    pub character_count: u64,
+    pub character_limit: u64,
+    #[serde(default)]
+    pub next_character_count_reset_unix: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SpeechRequest {
+    pub text: String,
+    #[serde(default)]
+    pub voice_id: Option<String>,
+    #[serde(default)]
+    pub model_id: Option<String>,
+    #[serde(default)]
+    pub stability: Option<f32>,
+    #[serde(default)]
+    pub similarity_boost: Option<f32>,
+}
+
+#[derive(Clone)]
+pub struct ElevenLabsClient {
+    http: reqwest::Client,
+    base_url: String,
+    settings: Arc<ElevenLabsSettingsStore>,
+}
+
+impl ElevenLabsClient {
+    pub fn new(settings: Arc<ElevenLabsSettingsStore>) -> Self {
+        Self::with_base_url(get_elevenlabs_base_url(), settings)
+    }
+
+    pub fn with_base_url(base_url: String, settings: Arc<ElevenLabsSettingsStore>) -> Self {
+        ElevenLabsClient {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            settings,
+        }
+    }
+
+    pub async fn has_api_key(&self) -> bool {
+        self.settings.get().await.is_some()
+    }
+
+    /// Store a new key, or forget it when `api_key` is blank
+    pub async fn set_api_key(&self, api_key: &str) -> Result<(), String> {
+        let api_key = api_key.trim();
+        if api_key.is_empty() {
+            return self.settings.clear().await;
+        }
+        self.settings.set(ElevenLabsSettings { api_key: api_key.to_string() }).await
+    }
+
+    async fn api_key(&self) -> Result<String, ElevenLabsError> {
+        self.settings
+            .get()
+            .await
+            .map(|s| s.api_key)
+            .ok_or(ElevenLabsError::MissingApiKey)
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, ElevenLabsError> {
+        let response = self
+            .http
+            .get(format!("{}{}", self.base_url, path))
+            .header("xi-api-key", self.api_key().await?)
+            .send()
+            .await
+            .map_err(network_error)?;
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+        response.json().await.map_err(|e| ElevenLabsError::Api {
+            status: 200,
+            message: format!("Invalid response: {}", e),
+        })
+    }
+
+    pub async fn list_voices(&self) -> Result<Vec<ElevenLabsVoice>, ElevenLabsError> {
+        let list: VoiceList = self.get("/v1/voices").await?;
+        Ok(list.voices)
+    }
+
+    pub async fn usage(&self) -> Result<ElevenLabsUsage, ElevenLabsError> {
+        self.get("/v1/user/subscription").await
+    }
+
+    async fn send_speech(&self, request: &SpeechRequest, stream: bool) -> Result<reqwest::Response, ElevenLabsError> {
+        if request.text.trim().is_empty() {
+            return Err(ElevenLabsError::InvalidRequest { message: "Text is empty".to_string() });
+        }
+
+        let voice_id = request.voice_id.as_deref().unwrap_or(DEFAULT_VOICE_ID);
+        let suffix = if stream { "/stream" } else { "" };
+        let body = serde_json::json!({
+            "text": request.text,
+            "model_id": request.model_id.as_deref().unwrap_or(DEFAULT_MODEL_ID),
+            "voice_settings": {
+                "stability": request.stability.unwrap_or(DEFAULT_STABILITY),
+                "similarity_boost": request.similarity_boost.unwrap_or(DEFAULT_SIMILARITY_BOOST),
+            },
+        });
+
+        let response = self
+            .http
+            .post(format!("{}/v1/text-to-speech/{}{}", self.base_url, voice_id, suffix))
+            .header("xi-api-key", self.api_key().await?)
+            .query(&[("output_format", OUTPUT_FORMAT)])
+            .json(&body)
+            .send()
+            .await
+            .map_err(network_error)?;
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+        Ok(response)
+    }
+
+    /// Synthesize the whole clip and return it as MP3 bytes
+    pub async fn text_to_speech(&self, request: &SpeechRequest) -> Result<Vec<u8>, ElevenLabsError> {
+        let response = self.send_speech(request, false).await?;
+        let audio = response.bytes().await.map_err(network_error)?;
+        Ok(audio.to_vec())
+    }
+
+    /// Synthesize via the streaming endpoint, handing MP3 chunks to `on_chunk` as they arrive
+    pub async fn text_to_speech_stream<F>(&self, request: &SpeechRequest, mut on_chunk: F) -> Result<(), ElevenLabsError>
+    where
+        F: FnMut(Vec<u8>),
+    {
+        let mut response = self.send_speech(request, true).await?;
+        while let Some(chunk) = response.chunk().await.map_err(network_error)? {
+            on_chunk(chunk.to_vec());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Path;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use tokio::net::TcpListener;
+
+    async fn speech(Path(voice_id): Path<String>, headers: HeaderMap, Json(body): Json<serde_json::Value>) -> impl IntoResponse {
+        match headers.get("xi-api-key").and_then(|h| h.to_str().ok()) {
+            Some("good-key") => {}
+            Some("empty-key") => {
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    Json(serde_json::json!({"detail": {"status": "quota_exceeded", "message": "This request exceeds your quota."}})),
+                )
+                    .into_response()
+            }
+            Some("busy-key") => {
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [("retry-after", "3")],
+                    Json(serde_json::json!({"detail": {"status": "too_many_concurrent_requests", "message": "Too many requests"}})),
+                )
+                    .into_response()
+            }
+            _ => {
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    Json(serde_json::json!({"detail": {"status": "invalid_api_key", "message": "Invalid API key"}})),
+                )
+                    .into_response()
+            }
+        }
+        format!("mp3:{}:{}:{}", voice_id, body["model_id"].as_str().unwrap(), body["text"].as_str().unwrap()).into_response()
+    }
+
+    async fn voices() -> impl IntoResponse {
+        Json(serde_json::json!({"voices": [
+            {"voice_id": "v1", "name": "Rachel", "category": "premade", "labels": {"accent": "american"}, "preview_url": null}
+        ]}))
+    }
+
+    async fn subscription() -> impl IntoResponse {
+        Json(serde_json::json!({
+            "tier": "free",
+            "character_count": 1200,
+            "character_limit": 10000,
+            "next_character_count_reset_unix": 1717000000,
+            "status": "free"
+        }))
+    }
+
+    async fn stub() -> String {
+        let app = Router::new()
+            .route("/v1/text-to-speech/:voice_id", post(speech))
+            .route("/v1/text-to-speech/:voice_id/stream", post(speech))
+            .route("/v1/voices", get(voices))
+            .route("/v1/user/subscription", get(subscription));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    async fn client_with_key(url: &str, key: Option<&str>) -> ElevenLabsClient {
+        let client = ElevenLabsClient::with_base_url(url.to_string(), Arc::new(ElevenLabsSettingsStore::load(None)));
+        if let Some(key) = key {
+            client.set_api_key(key).await.unwrap();
+        }
+        client
+    }
+
+    fn request(text: &str) -> SpeechRequest {
+        SpeechRequest { text: text.to_string(), voice_id: Some("v1".to_string()), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn synthesizes_and_lists() {
+        let url = stub().await;
+        let client = client_with_key(&url, Some("good-key")).await;
+
+        let audio = client.text_to_speech(&request("привет")).await.unwrap();
+        assert_eq!(String::from_utf8(audio).unwrap(), "mp3:v1:eleven_multilingual_v2:привет");
+
+        let mut streamed = Vec::new();
+        client
+            .text_to_speech_stream(&request("hi"), |chunk| streamed.extend(chunk))
+            .await
+            .unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), "mp3:v1:eleven_multilingual_v2:hi");
+
+        let voices = client.list_voices().await.unwrap();
+        assert_eq!(voices[0].name, "Rachel");
+        assert_eq!(voices[0].labels["accent"], "american");
+
+        let usage = client.usage().await.unwrap();
+        assert_eq!(usage.character_count, 1200);
+        assert_eq!(usage.character_limit, 10000);
+    }
+
+    #[tokio::test]
+    async fn maps_errors_to_kinds() {
+        let url = stub().await;
+
+        let missing = client_with_key(&url, None).await;
+        assert_eq!(missing.text_to_speech(&request("hi")).await, Err(ElevenLabsError::MissingApiKey));
+
+        let bad = client_with_key(&url, Some("bad-key")).await;
+        assert!(matches!(
+            bad.text_to_speech(&request("hi")).await,
+            Err(ElevenLabsError::Unauthorized { message }) if message == "Invalid API key"
+        ));
+
+        let empty = client_with_key(&url, Some("empty-key")).await;
+        assert!(matches!(empty.text_to_speech(&request("hi")).await, Err(ElevenLabsError::QuotaExceeded { .. })));
+
+        let busy = client_with_key(&url, Some("busy-key")).await;
+        assert!(matches!(
+            busy.text_to_speech(&request("hi")).await,
+            Err(ElevenLabsError::RateLimited { retry_after_secs: Some(3), .. })
+        ));
+
+        let good = client_with_key(&url, Some("good-key")).await;
+        assert!(matches!(good.text_to_speech(&request("  ")).await, Err(ElevenLabsError::InvalidRequest { .. })));
+
+        let error = serde_json::to_value(ElevenLabsError::QuotaExceeded { message: "over".to_string() }).unwrap();
+        assert_eq!(error, serde_json::json!({"kind": "quota_exceeded", "message": "over"}));
+    }
+}
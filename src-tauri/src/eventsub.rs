@@ -0,0 +1,390 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+
+use crate::alerts::{AlertPayload, TwitchEventSubPayload, TwitchSubscription, process_twitch_event};
+
+/// Twitch recommends rejecting notifications older than 10 minutes
+pub const MAX_MESSAGE_AGE_SECS: i64 = 600;
+/// Tolerate small clock differences for timestamps slightly in the future
+const MAX_CLOCK_SKEW_SECS: i64 = 60;
+const MESSAGE_ID_CACHE_CAPACITY: usize = 2048;
+
+/// Check a `Twitch-Eventsub-Message-Timestamp` value against the replay window
+pub fn is_message_fresh(timestamp: &str, now: DateTime<Utc>) -> bool {
+    let sent_at = match DateTime::parse_from_rfc3339(timestamp.trim()) {
+        Ok(ts) => ts.with_timezone(&Utc),
+        Err(_) => return false,
+    };
+
+    let age = now.signed_duration_since(sent_at).num_seconds();
+    (-MAX_CLOCK_SKEW_SECS..=MAX_MESSAGE_AGE_SECS).contains(&age)
+}
+
+/// Remembers recently delivered EventSub message ids so retries are only processed once.
+///
+/// Entries expire after the replay window since anything older is rejected by
+/// [`is_message_fresh`] anyway, and the cache never grows past its capacity.
+pub struct MessageIdCache {
+    seen: HashMap<String, u64>,
+    order: VecDeque<(String, u64)>,
+    capacity: usize,
+    ttl_secs: u64,
+}
+
+impl MessageIdCache {
+    pub fn new() -> Self {
+        Self::with_limits(MESSAGE_ID_CACHE_CAPACITY, MAX_MESSAGE_AGE_SECS as u64)
+    }
+
+    pub fn with_limits(capacity: usize, ttl_secs: u64) -> Self {
+        MessageIdCache {
+            seen: HashMap::new(),
+            order: VecDeque::new(),
+            capacity: capacity.max(1),
+            ttl_secs,
+        }
+    }
+
+    /// Record a message id, returning `false` if it was already seen inside the window
+    pub fn check_and_record(&mut self, message_id: &str, now: u64) -> bool {
+        self.evict_expired(now);
+
+        if self.seen.contains_key(message_id) {
+            return false;
+        }
+
+        while self.order.len() >= self.capacity {
+            self.evict_oldest();
+        }
+
+        self.seen.insert(message_id.to_string(), now);
+        self.order.push_back((message_id.to_string(), now));
+        true
+    }
+
+    fn evict_expired(&mut self, now: u64) {
+        let cutoff = now.saturating_sub(self.ttl_secs);
+        while let Some((_, seen_at)) = self.order.front() {
+            if *seen_at > cutoff {
+                break;
+            }
+            self.evict_oldest();
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some((id, _)) = self.order.pop_front() {
+            self.seen.remove(&id);
+        }
+    }
+}
+
+impl Default for MessageIdCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Why Twitch stopped delivering a subscription
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevocationReason {
+    /// The user revoked the authorization token the subscription relied on
+    AuthorizationRevoked,
+    /// The user in the condition was deleted or banned
+    UserRemoved,
+    /// The callback failed to respond in time too often
+    NotificationFailuresExceeded,
+    /// The subscribed type and version is no longer supported
+    VersionRemoved,
+    Other(String),
+}
+
+impl RevocationReason {
+    pub fn from_status(status: &str) -> Self {
+        match status {
+            "authorization_revoked" => RevocationReason::AuthorizationRevoked,
+            "user_removed" => RevocationReason::UserRemoved,
+            "notification_failures_exceeded" => RevocationReason::NotificationFailuresExceeded,
+            "version_removed" => RevocationReason::VersionRemoved,
+            other => RevocationReason::Other(other.to_string()),
+        }
+    }
+}
+
+/// A `revocation` message sent by Twitch when a subscription is cancelled on its side
+#[derive(Debug, Clone, Serialize)]
+pub struct EventSubRevocation {
+    pub subscription_id: String,
+    pub subscription_type: String,
+    pub version: String,
+    pub reason: RevocationReason,
+    pub condition: serde_json::Value,
+    pub revoked_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RevocationBody {
+    subscription: TwitchSubscription,
+}
+
+pub fn parse_revocation(body: &str, revoked_at: DateTime<Utc>) -> Option<EventSubRevocation> {
+    let parsed: RevocationBody = serde_json::from_str(body).ok()?;
+    Some(revocation_from_subscription(parsed.subscription, revoked_at))
+}
+
+pub fn revocation_from_subscription(subscription: TwitchSubscription, revoked_at: DateTime<Utc>) -> EventSubRevocation {
+    EventSubRevocation {
+        reason: RevocationReason::from_status(&subscription.status),
+        subscription_id: subscription.id,
+        subscription_type: subscription.r#type,
+        version: subscription.version,
+        condition: subscription.condition,
+        revoked_at,
+    }
+}
+
+/// Last known state of a single EventSub subscription
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriptionHealth {
+    pub subscription_id: String,
+    pub subscription_type: String,
+    pub status: String,
+    pub revocation_reason: Option<RevocationReason>,
+    pub last_notification_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Per-subscription status fed by verification, notification and revocation messages
+#[derive(Default)]
+pub struct SubscriptionHealthRegistry {
+    entries: HashMap<String, SubscriptionHealth>,
+}
+
+impl SubscriptionHealthRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark a subscription as enabled, e.g. after answering its verification challenge
+    pub fn record_enabled(&mut self, subscription: &TwitchSubscription, now: DateTime<Utc>) {
+        let entry = self.entry(subscription, now);
+        entry.status = "enabled".to_string();
+        entry.revocation_reason = None;
+        entry.updated_at = now;
+    }
+
+    pub fn record_notification(&mut self, subscription: &TwitchSubscription, now: DateTime<Utc>) {
+        self.record_enabled(subscription, now);
+        if let Some(entry) = self.entries.get_mut(&subscription.id) {
+            entry.last_notification_at = Some(now);
+        }
+    }
+
+    pub fn record_revocation(&mut self, revocation: &EventSubRevocation) {
+        let entry = self
+            .entries
+            .entry(revocation.subscription_id.clone())
+            .or_insert_with(|| SubscriptionHealth {
+                subscription_id: revocation.subscription_id.clone(),
+                subscription_type: revocation.subscription_type.clone(),
+                status: String::new(),
+                revocation_reason: None,
+                last_notification_at: None,
+                updated_at: revocation.revoked_at,
+            });
+        entry.status = "revoked".to_string();
+        entry.revocation_reason = Some(revocation.reason.clone());
+        entry.updated_at = revocation.revoked_at;
+    }
+
+    /// All known subscriptions ordered by type for stable display
+    pub fn snapshot(&self) -> Vec<SubscriptionHealth> {
+        let mut entries: Vec<SubscriptionHealth> = self.entries.values().cloned().collect();
+        entries.sort_by(|a, b| {
+            a.subscription_type
+                .cmp(&b.subscription_type)
+                .then_with(|| a.subscription_id.cmp(&b.subscription_id))
+        });
+        entries
+    }
+
+    fn entry(&mut self, subscription: &TwitchSubscription, now: DateTime<Utc>) -> &mut SubscriptionHealth {
+        self.entries
+            .entry(subscription.id.clone())
+            .or_insert_with(|| SubscriptionHealth {
+                subscription_id: subscription.id.clone(),
+                subscription_type: subscription.r#type.clone(),
+                status: subscription.status.clone(),
+                revocation_reason: None,
+                last_notification_at: None,
+                updated_at: now,
+            })
+    }
+}
+
+/// Where EventSub messages end up regardless of the transport that delivered them
+#[derive(Clone)]
+pub struct EventSubSinks {
+    pub alert_sender: broadcast::Sender<AlertPayload>,
+    pub revocation_sender: broadcast::Sender<EventSubRevocation>,
+    pub health: Arc<RwLock<SubscriptionHealthRegistry>>,
+}
+
+impl EventSubSinks {
+    pub async fn deliver_notification(&self, payload: TwitchEventSubPayload) {
+        self.health
+            .write()
+            .await
+            .record_notification(&payload.subscription, Utc::now());
+        if let Some(alert) = process_twitch_event(payload) {
+            log::info!("Twitch alert processed: {} - {}", alert.alert_type, alert.user_name);
+            let _ = self.alert_sender.send(alert);
+        }
+    }
+
+    pub async fn deliver_revocation(&self, revocation: EventSubRevocation) {
+        log::warn!(
+            "Twitch EventSub subscription revoked: type={}, id={}, reason={:?}",
+            revocation.subscription_type, revocation.subscription_id, revocation.reason
+        );
+        self.health.write().await.record_revocation(&revocation);
+        let _ = self.revocation_sender.send(revocation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn fresh_timestamp_is_accepted() {
+        let now = Utc::now();
+        let ts = (now - Duration::seconds(30)).to_rfc3339();
+        assert!(is_message_fresh(&ts, now));
+    }
+
+    #[test]
+    fn stale_timestamp_is_rejected() {
+        let now = Utc::now();
+        let ts = (now - Duration::minutes(11)).to_rfc3339();
+        assert!(!is_message_fresh(&ts, now));
+    }
+
+    #[test]
+    fn future_and_malformed_timestamps_are_rejected() {
+        let now = Utc::now();
+        let ts = (now + Duration::minutes(5)).to_rfc3339();
+        assert!(!is_message_fresh(&ts, now));
+        assert!(!is_message_fresh("yesterday", now));
+        assert!(!is_message_fresh("", now));
+    }
+
+    #[test]
+    fn twitch_nanosecond_timestamp_parses() {
+        let now = DateTime::parse_from_rfc3339("2023-07-19T14:57:00Z").unwrap().with_timezone(&Utc);
+        assert!(is_message_fresh("2023-07-19T14:56:51.634234626Z", now));
+    }
+
+    #[test]
+    fn duplicate_ids_are_reported() {
+        let mut cache = MessageIdCache::new();
+        assert!(cache.check_and_record("a", 100));
+        assert!(!cache.check_and_record("a", 101));
+        assert!(cache.check_and_record("b", 102));
+    }
+
+    #[test]
+    fn ids_expire_after_ttl() {
+        let mut cache = MessageIdCache::with_limits(10, 60);
+        assert!(cache.check_and_record("a", 100));
+        assert!(!cache.check_and_record("a", 159));
+        assert!(cache.check_and_record("a", 161));
+    }
+
+    #[test]
+    fn cache_is_bounded() {
+        let mut cache = MessageIdCache::with_limits(3, 600);
+        for (i, id) in ["a", "b", "c", "d"].iter().enumerate() {
+            assert!(cache.check_and_record(id, 100 + i as u64));
+        }
+        assert_eq!(cache.seen.len(), 3);
+        // "a" was pushed out by capacity and is treated as new again
+        assert!(cache.check_and_record("a", 105));
+        assert!(!cache.check_and_record("d", 105));
+    }
+
+    const REVOCATION_BODY: &str = r#"{
+        "subscription": {
+            "id": "f1c2a387-161a-49f9-a165-0f21d7a4e1c4",
+            "status": "authorization_revoked",
+            "type": "channel.follow",
+            "cost": 1,
+            "version": "2",
+            "condition": {
+                "broadcaster_user_id": "12826"
+            },
+            "transport": {
+                "method": "webhook",
+                "callback": "https://example.com/webhooks/callback"
+            },
+            "created_at": "2019-11-16T10:11:12.634234626Z"
+        }
+    }"#;
+
+    fn subscription(id: &str, r#type: &str) -> TwitchSubscription {
+        TwitchSubscription {
+            id: id.to_string(),
+            status: "enabled".to_string(),
+            r#type: r#type.to_string(),
+            version: "1".to_string(),
+            condition: serde_json::json!({}),
+        }
+    }
+
+    #[test]
+    fn revocation_is_parsed() {
+        let revocation = parse_revocation(REVOCATION_BODY, Utc::now()).unwrap();
+        assert_eq!(revocation.subscription_id, "f1c2a387-161a-49f9-a165-0f21d7a4e1c4");
+        assert_eq!(revocation.subscription_type, "channel.follow");
+        assert_eq!(revocation.reason, RevocationReason::AuthorizationRevoked);
+        assert_eq!(revocation.condition["broadcaster_user_id"], "12826");
+    }
+
+    #[test]
+    fn revocation_reasons_map_from_status() {
+        assert_eq!(RevocationReason::from_status("user_removed"), RevocationReason::UserRemoved);
+        assert_eq!(
+            RevocationReason::from_status("notification_failures_exceeded"),
+            RevocationReason::NotificationFailuresExceeded
+        );
+        assert_eq!(RevocationReason::from_status("version_removed"), RevocationReason::VersionRemoved);
+        assert_eq!(
+            RevocationReason::from_status("moderator_removed"),
+            RevocationReason::Other("moderator_removed".to_string())
+        );
+    }
+
+    #[test]
+    fn health_tracks_notifications_and_revocations() {
+        let mut registry = SubscriptionHealthRegistry::new();
+        let now = Utc::now();
+        registry.record_notification(&subscription("f1c2a387-161a-49f9-a165-0f21d7a4e1c4", "channel.follow"), now);
+        registry.record_enabled(&subscription("b", "channel.cheer"), now);
+
+        let revocation = parse_revocation(REVOCATION_BODY, now).unwrap();
+        registry.record_revocation(&revocation);
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].subscription_type, "channel.cheer");
+        assert_eq!(snapshot[0].status, "enabled");
+        assert_eq!(snapshot[1].status, "revoked");
+        assert_eq!(snapshot[1].revocation_reason, Some(RevocationReason::AuthorizationRevoked));
+        assert_eq!(snapshot[1].last_notification_at, Some(now));
+    }
+}
@@ -0,0 +1,514 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use crate::alerts::TwitchSubscription;
+use crate::eventsub::SubscriptionHealthRegistry;
+use crate::eventsub_ws::EventSubWebSocket;
+use crate::twitch_api::{HelixClient, HelixSubscription, TwitchCredentialStore, TwitchCredentials};
+
+/// Subscription types turned into alerts by `process_twitch_event`, with their EventSub version
+pub const SUPPORTED_SUBSCRIPTION_TYPES: &[(&str, &str)] = &[
+    ("channel.follow", "2"),
+    ("channel.subscribe", "1"),
+    ("channel.subscription.gift", "1"),
+    ("channel.subscription.message", "1"),
+    ("channel.cheer", "1"),
+    ("channel.raid", "1"),
+    ("channel.channel_points_custom_reward_redemption.add", "1"),
+];
+
+/// How long to wait for the WebSocket welcome before giving up on a create request
+const SESSION_WAIT: Duration = Duration::from_secs(15);
+
+fn version_for(subscription_type: &str) -> Option<&'static str> {
+    SUPPORTED_SUBSCRIPTION_TYPES
+        .iter()
+        .find(|(t, _)| *t == subscription_type)
+        .map(|(_, v)| *v)
+}
+
+/// Build the condition object Twitch expects for each supported type
+fn condition_for(subscription_type: &str, broadcaster_id: &str) -> serde_json::Value {
+    match subscription_type {
+        "channel.follow" => serde_json::json!({
+            "broadcaster_user_id": broadcaster_id,
+            "moderator_user_id": broadcaster_id,
+        }),
+        "channel.raid" => serde_json::json!({
+            "to_broadcaster_user_id": broadcaster_id,
+        }),
+        _ => serde_json::json!({
+            "broadcaster_user_id": broadcaster_id,
+        }),
+    }
+}
+
+/// Outcome of the last attempt to subscribe to one type
+#[derive(Debug, Clone, Serialize)]
+pub struct EventSubTypeStatus {
+    pub subscription_type: String,
+    pub version: String,
+    /// "enabled", "pending", "failed" or "not_subscribed"
+    pub status: String,
+    pub subscription_id: Option<String>,
+    pub error: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl EventSubTypeStatus {
+    fn not_subscribed(subscription_type: &str, version: &str) -> Self {
+        EventSubTypeStatus {
+            subscription_type: subscription_type.to_string(),
+            version: version.to_string(),
+            status: "not_subscribed".to_string(),
+            subscription_id: None,
+            error: None,
+            updated_at: None,
+        }
+    }
+}
+
+/// Creates, lists and deletes EventSub subscriptions bound to the WebSocket session
+pub struct EventSubManager {
+    helix: HelixClient,
+    credentials: Arc<TwitchCredentialStore>,
+    websocket: Arc<EventSubWebSocket>,
+    health: Arc<RwLock<SubscriptionHealthRegistry>>,
+    type_status: RwLock<HashMap<String, EventSubTypeStatus>>,
+}
+
+impl EventSubManager {
+    pub fn new(
+        helix: HelixClient,
+        credentials: Arc<TwitchCredentialStore>,
+        websocket: Arc<EventSubWebSocket>,
+        health: Arc<RwLock<SubscriptionHealthRegistry>>,
+    ) -> Self {
+        EventSubManager {
+            helix,
+            credentials,
+            websocket,
+            health,
+            type_status: RwLock::new(HashMap::new()),
+        }
+    }
+
+    async fn require_credentials(&self) -> Result<TwitchCredentials, String> {
+        self.credentials
+            .get()
+            .await
+            .ok_or_else(|| "Not connected to Twitch".to_string())
+    }
+
+    /// Start the WebSocket if needed and wait until it has a session id
+    async fn require_session(&self) -> Result<String, String> {
+        if let Some(id) = self.websocket.session_id() {
+            return Ok(id);
+        }
+
+        let mut session = self.websocket.subscribe_session();
+        self.websocket.start().await;
+        let waited = tokio::time::timeout(SESSION_WAIT, async {
+            loop {
+                if let Some(current) = session.borrow_and_update().clone() {
+                    return Some(current.id);
+                }
+                if session.changed().await.is_err() {
+                    return None;
+                }
+            }
+        })
+        .await;
+
+        match waited {
+            Ok(Some(id)) => Ok(id),
+            _ => Err("EventSub WebSocket session is not available".to_string()),
+        }
+    }
+
+    /// Validate and store a user token, then (re)subscribe everything with it
+    pub async fn connect(&self, access_token: &str) -> Result<TwitchCredentials, String> {
+        let credentials = self.helix.validate_token(access_token).await?;
+        self.credentials.set(credentials.clone()).await?;
+        log::info!("Twitch credentials stored for {}", credentials.login);
+        // An already welcomed session will not trigger the resubscriber again
+        match self.websocket.session_id() {
+            Some(session_id) => {
+                self.subscribe_all(&session_id).await?;
+            }
+            None => self.websocket.start().await,
+        }
+        Ok(credentials)
+    }
+
+    pub async fn disconnect(&self) -> Result<(), String> {
+        self.websocket.stop().await;
+        self.type_status.write().await.clear();
+        self.credentials.clear().await
+    }
+
+    pub async fn create(&self, subscription_type: &str) -> Result<HelixSubscription, String> {
+        let version = version_for(subscription_type)
+            .ok_or_else(|| format!("Unsupported subscription type: {}", subscription_type))?;
+        let credentials = self.require_credentials().await?;
+        let session_id = self.require_session().await?;
+        self.create_with_session(&credentials, &session_id, subscription_type, version).await
+    }
+
+    async fn create_with_session(
+        &self,
+        credentials: &TwitchCredentials,
+        session_id: &str,
+        subscription_type: &str,
+        version: &str,
+    ) -> Result<HelixSubscription, String> {
+        let result = self
+            .helix
+            .create_subscription(
+                credentials,
+                subscription_type,
+                version,
+                condition_for(subscription_type, &credentials.user_id),
+                serde_json::json!({ "method": "websocket", "session_id": session_id }),
+            )
+            .await;
+
+        match &result {
+            Ok(created) => self.record_subscribed(version, created).await,
+            Err(e) => {
+                log::warn!("{}", e);
+                let status = EventSubTypeStatus {
+                    subscription_type: subscription_type.to_string(),
+                    version: version.to_string(),
+                    status: "failed".to_string(),
+                    subscription_id: None,
+                    error: Some(e.clone()),
+                    updated_at: Some(Utc::now()),
+                };
+                self.type_status
+                    .write()
+                    .await
+                    .insert(subscription_type.to_string(), status);
+            }
+        }
+
+        result
+    }
+
+    /// Mark a subscription Twitch has accepted, whether just created or found already in place
+    async fn record_subscribed(&self, version: &str, subscription: &HelixSubscription) {
+        let now = Utc::now();
+        self.health.write().await.record_enabled(
+            &TwitchSubscription {
+                id: subscription.id.clone(),
+                status: subscription.status.clone(),
+                r#type: subscription.r#type.clone(),
+                version: subscription.version.clone(),
+                condition: subscription.condition.clone(),
+            },
+            now,
+        );
+        let status = EventSubTypeStatus {
+            subscription_type: subscription.r#type.clone(),
+            version: version.to_string(),
+            status: subscription.status.clone(),
+            subscription_id: Some(subscription.id.clone()),
+            error: None,
+            updated_at: Some(now),
+        };
+        self.type_status
+            .write()
+            .await
+            .insert(subscription.r#type.clone(), status);
+    }
+
+    pub async fn list(&self) -> Result<Vec<HelixSubscription>, String> {
+        let credentials = self.require_credentials().await?;
+        self.helix.list_subscriptions(&credentials).await
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<(), String> {
+        let credentials = self.require_credentials().await?;
+        self.helix.delete_subscription(&credentials, id).await?;
+
+        let mut status = self.type_status.write().await;
+        if let Some(entry) = status.values_mut().find(|s| s.subscription_id.as_deref() == Some(id)) {
+            entry.status = "not_subscribed".to_string();
+            entry.subscription_id = None;
+            entry.error = None;
+            entry.updated_at = Some(Utc::now());
+        }
+        Ok(())
+    }
+
+    /// Subscribe every supported type to the given session, continuing past individual failures.
+    ///
+    /// Types already enabled on this session are kept as they are; posting them again would
+    /// only get a 409 from Twitch.
+    pub async fn subscribe_all(&self, session_id: &str) -> Result<Vec<EventSubTypeStatus>, String> {
+        let credentials = self.require_credentials().await?;
+        let existing = match self.helix.list_subscriptions(&credentials).await {
+            Ok(existing) => existing,
+            Err(e) => {
+                log::warn!("Could not list EventSub subscriptions, creating all of them: {}", e);
+                Vec::new()
+            }
+        };
+
+        for (subscription_type, version) in SUPPORTED_SUBSCRIPTION_TYPES {
+            let enabled = existing.iter().find(|s| {
+                s.r#type == *subscription_type
+                    && s.version == *version
+                    && s.status == "enabled"
+                    && s.transport["session_id"] == session_id
+            });
+            match enabled {
+                Some(subscription) => self.record_subscribed(version, subscription).await,
+                None => {
+                    let _ = self
+                        .create_with_session(&credentials, session_id, subscription_type, version)
+                        .await;
+                }
+            }
+        }
+        Ok(self.type_status().await)
+    }
+
+    /// Status of every supported type, including ones never attempted
+    pub async fn type_status(&self) -> Vec<EventSubTypeStatus> {
+        let status = self.type_status.read().await;
+        SUPPORTED_SUBSCRIPTION_TYPES
+            .iter()
+            .map(|(t, v)| {
+                status
+                    .get(*t)
+                    .cloned()
+                    .unwrap_or_else(|| EventSubTypeStatus::not_subscribed(t, v))
+            })
+            .collect()
+    }
+
+    /// Resubscribe on every new WebSocket session, since subscriptions die with their session.
+    ///
+    /// Also starts the WebSocket right away when credentials were persisted by a previous run.
+    pub async fn run_resubscriber(self: Arc<Self>) {
+        if self.credentials.get().await.is_some() {
+            self.websocket.start().await;
+        }
+
+        let mut session = self.websocket.subscribe_session();
+        loop {
+            if session.changed().await.is_err() {
+                return;
+            }
+            let current = session.borrow_and_update().clone();
+            // A session_reconnect carries its subscriptions over to the new socket
+            let Some(current) = current.filter(|s| !s.resumed) else {
+                continue;
+            };
+
+            if self.credentials.get().await.is_none() {
+                continue;
+            }
+            log::info!("Subscribing EventSub types for session {}", current.id);
+            if let Err(e) = self.subscribe_all(&current.id).await {
+                log::warn!("EventSub resubscribe failed: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventsub::EventSubSinks;
+    use axum::extract::{Query, State};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use tokio::net::TcpListener;
+    use tokio::sync::broadcast;
+
+    #[derive(Default)]
+    struct FakeHelix {
+        subscriptions: Vec<serde_json::Value>,
+    }
+
+    type Shared = Arc<RwLock<FakeHelix>>;
+
+    fn authorized(headers: &HeaderMap) -> bool {
+        headers.get("Authorization").and_then(|h| h.to_str().ok()) == Some("Bearer token-1")
+            && headers.get("Client-Id").and_then(|h| h.to_str().ok()) == Some("client-1")
+    }
+
+    async fn validate(headers: HeaderMap) -> impl IntoResponse {
+        if headers.get("Authorization").and_then(|h| h.to_str().ok()) != Some("OAuth token-1") {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        Json(serde_json::json!({
+            "client_id": "client-1",
+            "login": "streamer",
+            "scopes": ["bits:read"],
+            "user_id": "1337",
+            "expires_in": 5000
+        }))
+        .into_response()
+    }
+
+    async fn create(State(state): State<Shared>, headers: HeaderMap, Json(body): Json<serde_json::Value>) -> impl IntoResponse {
+        if !authorized(&headers) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        if body["type"] == "channel.cheer" {
+            return (
+                StatusCode::FORBIDDEN,
+                Json(serde_json::json!({"error": "Forbidden", "status": 403, "message": "missing scope"})),
+            )
+                .into_response();
+        }
+        let mut state = state.write().await;
+        if state.subscriptions.iter().any(|s| s["type"] == body["type"] && s["transport"] == body["transport"]) {
+            return (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({"error": "Conflict", "status": 409, "message": "subscription already exists"})),
+            )
+                .into_response();
+        }
+        let subscription = serde_json::json!({
+            "id": format!("sub-{}", state.subscriptions.len() + 1),
+            "status": "enabled",
+            "type": body["type"],
+            "version": body["version"],
+            "condition": body["condition"],
+            "transport": body["transport"],
+            "created_at": "2024-01-01T00:00:00Z",
+            "cost": 0
+        });
+        state.subscriptions.push(subscription.clone());
+        (StatusCode::ACCEPTED, Json(serde_json::json!({"data": [subscription], "total": 1}))).into_response()
+    }
+
+    async fn list(State(state): State<Shared>, headers: HeaderMap) -> impl IntoResponse {
+        if !authorized(&headers) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        let state = state.read().await;
+        Json(serde_json::json!({"data": state.subscriptions, "pagination": {}})).into_response()
+    }
+
+    async fn delete(
+        State(state): State<Shared>,
+        headers: HeaderMap,
+        Query(params): Query<HashMap<String, String>>,
+    ) -> impl IntoResponse {
+        if !authorized(&headers) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        let mut state = state.write().await;
+        let id = params.get("id").cloned().unwrap_or_default();
+        let before = state.subscriptions.len();
+        state.subscriptions.retain(|s| s["id"] != id.as_str());
+        if state.subscriptions.len() == before {
+            StatusCode::NOT_FOUND.into_response()
+        } else {
+            StatusCode::NO_CONTENT.into_response()
+        }
+    }
+
+    async fn fake_helix() -> (String, Shared) {
+        let shared: Shared = Arc::new(RwLock::new(FakeHelix::default()));
+        let app = Router::new()
+            .route("/oauth2/validate", get(validate))
+            .route("/helix/eventsub/subscriptions", get(list).post(create).delete(delete))
+            .with_state(shared.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (base, shared)
+    }
+
+    fn manager(base: &str) -> EventSubManager {
+        let (alert_sender, _) = broadcast::channel(4);
+        let (revocation_sender, _) = broadcast::channel(4);
+        let health = Arc::new(RwLock::new(SubscriptionHealthRegistry::new()));
+        let sinks = EventSubSinks {
+            alert_sender,
+            revocation_sender,
+            health: health.clone(),
+        };
+        EventSubManager::new(
+            HelixClient::with_base_urls(format!("{}/helix", base), base.to_string()),
+            Arc::new(TwitchCredentialStore::load(None)),
+            Arc::new(EventSubWebSocket::with_url("ws://127.0.0.1:9".to_string(), sinks)),
+            health,
+        )
+    }
+
+    #[test]
+    fn conditions_match_subscription_type() {
+        assert_eq!(condition_for("channel.raid", "1")["to_broadcaster_user_id"], "1");
+        assert_eq!(condition_for("channel.follow", "1")["moderator_user_id"], "1");
+        assert_eq!(condition_for("channel.cheer", "1")["broadcaster_user_id"], "1");
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_token() {
+        let (base, _) = fake_helix().await;
+        let manager = manager(&base);
+        assert!(manager.helix.validate_token("wrong").await.is_err());
+        assert!(manager.credentials.get().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn subscribe_all_reports_per_type_status() {
+        let (base, fake) = fake_helix().await;
+        let manager = manager(&base);
+        let credentials = manager.helix.validate_token("token-1").await.unwrap();
+        assert_eq!(credentials.user_id, "1337");
+        manager.credentials.set(credentials).await.unwrap();
+
+        let status = manager.subscribe_all("session-1").await.unwrap();
+        assert_eq!(status.len(), SUPPORTED_SUBSCRIPTION_TYPES.len());
+        let cheer = status.iter().find(|s| s.subscription_type == "channel.cheer").unwrap();
+        assert_eq!(cheer.status, "failed");
+        assert!(cheer.error.as_deref().unwrap().contains("missing scope"));
+        let follow = status.iter().find(|s| s.subscription_type == "channel.follow").unwrap();
+        assert_eq!(follow.status, "enabled");
+        assert_eq!(follow.version, "2");
+
+        let created = fake.read().await.subscriptions.clone();
+        assert_eq!(created.len(), SUPPORTED_SUBSCRIPTION_TYPES.len() - 1);
+        assert_eq!(created[0]["transport"]["session_id"], "session-1");
+
+        let listed = manager.list().await.unwrap();
+        assert_eq!(listed.len(), created.len());
+
+        // A second pass on the same session, as on reconnect, creates nothing and reports no conflicts
+        let status = manager.subscribe_all("session-1").await.unwrap();
+        assert_eq!(fake.read().await.subscriptions.len(), created.len());
+        let failed: Vec<&str> = status.iter().filter(|s| s.status == "failed").map(|s| s.subscription_type.as_str()).collect();
+        assert_eq!(failed, ["channel.cheer"]);
+
+        let follow_id = follow.subscription_id.clone().unwrap();
+        manager.delete(&follow_id).await.unwrap();
+        assert_eq!(manager.list().await.unwrap().len(), created.len() - 1);
+        let status = manager.type_status().await;
+        assert_eq!(status[0].status, "not_subscribed");
+        assert!(manager.delete(&follow_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn create_rejects_unsupported_type() {
+        let (base, _) = fake_helix().await;
+        let manager = manager(&base);
+        let err = manager.create("channel.update").await.unwrap_err();
+        assert!(err.contains("Unsupported"));
+    }
+}
@@ -0,0 +1,608 @@
+use futures_util::StreamExt;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use crate::alerts::{TwitchEventSubPayload, TwitchSubscription};
+use crate::eventsub::{EventSubSinks, MessageIdCache, is_message_fresh, revocation_from_subscription};
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+const DEFAULT_EVENTSUB_WS_URL: &str = "wss://eventsub.wss.twitch.tv/ws";
+/// Twitch sends the welcome message right after connecting; give it some slack
+const WELCOME_TIMEOUT: Duration = Duration::from_secs(15);
+/// Extra time on top of `keepalive_timeout_seconds` before the session is considered dead
+const KEEPALIVE_GRACE: Duration = Duration::from_secs(5);
+const DEFAULT_KEEPALIVE_SECS: u64 = 10;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(120);
+/// Close code Twitch uses when no subscription was created within 10 seconds of the welcome
+const CLOSE_CONNECTION_UNUSED: u16 = 4003;
+
+fn get_twitch_eventsub_ws_url() -> String {
+    std::env::var("TWITCH_EVENTSUB_WS_URL")
+        .unwrap_or_else(|_| DEFAULT_EVENTSUB_WS_URL.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+struct WsMessage {
+    metadata: WsMetadata,
+    #[serde(default)]
+    payload: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct WsMetadata {
+    message_id: String,
+    message_type: String,
+    message_timestamp: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct WsSession {
+    id: String,
+    keepalive_timeout_seconds: Option<u64>,
+    reconnect_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionPayload {
+    session: WsSession,
+}
+
+#[derive(Debug, Deserialize)]
+struct RevocationPayload {
+    subscription: TwitchSubscription,
+}
+
+/// The session currently bound to the socket
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventSubSession {
+    pub id: String,
+    /// True when the session was carried over by `session_reconnect` and keeps its subscriptions
+    pub resumed: bool,
+}
+
+enum SessionEnd {
+    /// Twitch asked us to move to another edge; the new socket is already welcomed
+    Reconnected(Box<WsStream>, WsSession),
+    /// The connection dropped or went silent past the keepalive window
+    Lost { welcomed: bool },
+    /// Twitch closed the socket because no subscription used it
+    Unused,
+}
+
+/// EventSub over WebSocket, for machines where Twitch cannot reach the local webhook.
+///
+/// Notifications are fed into the same [`EventSubSinks`] as the `/twitch-alerts` webhook.
+/// The current session id is published so subscriptions can be created against it.
+/// Every dropped or closed session is reconnected; only [`EventSubWebSocket::stop`] ends the loop.
+pub struct EventSubWebSocket {
+    url: String,
+    sinks: EventSubSinks,
+    session_tx: watch::Sender<Option<EventSubSession>>,
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl EventSubWebSocket {
+    pub fn new(sinks: EventSubSinks) -> Self {
+        Self::with_url(get_twitch_eventsub_ws_url(), sinks)
+    }
+
+    pub fn with_url(url: String, sinks: EventSubSinks) -> Self {
+        let (session_tx, _) = watch::channel(None);
+        EventSubWebSocket {
+            url,
+            sinks,
+            session_tx,
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Start the connection loop unless it is already running
+    pub async fn start(&self) {
+        let mut task = self.task.lock().await;
+        if task.as_ref().is_some_and(|t| !t.is_finished()) {
+            return;
+        }
+
+        log::info!("Starting Twitch EventSub WebSocket client: {}", self.url);
+        *task = Some(tokio::spawn(run_connection_loop(
+            self.url.clone(),
+            self.sinks.clone(),
+            self.session_tx.clone(),
+        )));
+    }
+
+    pub async fn stop(&self) {
+        if let Some(task) = self.task.lock().await.take() {
+            task.abort();
+            log::info!("Twitch EventSub WebSocket client stopped");
+        }
+        self.session_tx.send_replace(None);
+    }
+
+    pub fn session_id(&self) -> Option<String> {
+        self.session_tx.borrow().as_ref().map(|s| s.id.clone())
+    }
+
+    /// Receiver that changes whenever a new session is welcomed or the current one is lost
+    pub fn subscribe_session(&self) -> watch::Receiver<Option<EventSubSession>> {
+        self.session_tx.subscribe()
+    }
+}
+
+async fn run_connection_loop(
+    url: String,
+    sinks: EventSubSinks,
+    session_tx: watch::Sender<Option<EventSubSession>>,
+) {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut seen = MessageIdCache::new();
+    let mut resumed: Option<(WsStream, WsSession)> = None;
+
+    loop {
+        let (stream, session) = match resumed.take() {
+            Some((stream, session)) => (stream, Some(session)),
+            None => match connect_async(url.as_str()).await {
+                Ok((stream, _)) => (stream, None),
+                Err(e) => {
+                    log::warn!("EventSub WebSocket connect failed: {}, retrying in {:?}", e, backoff);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    continue;
+                }
+            },
+        };
+
+        match run_session(stream, session, &sinks, &session_tx, &mut seen).await {
+            SessionEnd::Reconnected(stream, session) => {
+                log::info!("EventSub WebSocket moved to new session {}", session.id);
+                resumed = Some((*stream, session));
+                backoff = INITIAL_BACKOFF;
+            }
+            SessionEnd::Lost { welcomed } => {
+                session_tx.send_replace(None);
+                if welcomed {
+                    backoff = INITIAL_BACKOFF;
+                }
+                log::warn!("EventSub WebSocket session lost, reconnecting in {:?}", backoff);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            SessionEnd::Unused => {
+                // Keep backing off: the subscriptions may keep failing until the user fixes them
+                session_tx.send_replace(None);
+                log::warn!(
+                    "EventSub WebSocket closed by Twitch: no subscriptions were created for the session, reconnecting in {:?}",
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+fn keepalive_window(session: Option<&WsSession>) -> Duration {
+    match session {
+        Some(session) => {
+            Duration::from_secs(session.keepalive_timeout_seconds.unwrap_or(DEFAULT_KEEPALIVE_SECS)) + KEEPALIVE_GRACE
+        }
+        None => WELCOME_TIMEOUT,
+    }
+}
+
+async fn run_session(
+    mut stream: WsStream,
+    mut session: Option<WsSession>,
+    sinks: &EventSubSinks,
+    session_tx: &watch::Sender<Option<EventSubSession>>,
+    seen: &mut MessageIdCache,
+) -> SessionEnd {
+    if let Some(ref resumed) = session {
+        session_tx.send_replace(Some(EventSubSession {
+            id: resumed.id.clone(),
+            resumed: true,
+        }));
+    }
+
+    loop {
+        let frame = match tokio::time::timeout(keepalive_window(session.as_ref()), stream.next()).await {
+            Ok(Some(Ok(frame))) => frame,
+            Ok(Some(Err(e))) => {
+                log::warn!("EventSub WebSocket read error: {}", e);
+                return SessionEnd::Lost { welcomed: session.is_some() };
+            }
+            Ok(None) => return SessionEnd::Lost { welcomed: session.is_some() },
+            Err(_) => {
+                log::warn!("EventSub WebSocket keepalive timed out");
+                let _ = stream.close(None).await;
+                return SessionEnd::Lost { welcomed: session.is_some() };
+            }
+        };
+
+        let text = match frame {
+            Message::Text(text) => text,
+            Message::Close(frame) => {
+                let code = frame.as_ref().map(|f| u16::from(f.code)).unwrap_or_default();
+                log::info!("EventSub WebSocket closed by server with code {}", code);
+                if code == CLOSE_CONNECTION_UNUSED {
+                    return SessionEnd::Unused;
+                }
+                return SessionEnd::Lost { welcomed: session.is_some() };
+            }
+            // Pings are answered by tungstenite itself
+            _ => continue,
+        };
+
+        let message: WsMessage = match serde_json::from_str(text.as_str()) {
+            Ok(message) => message,
+            Err(e) => {
+                log::warn!("Ignoring malformed EventSub WebSocket message: {}", e);
+                continue;
+            }
+        };
+
+        match message.metadata.message_type.as_str() {
+            "session_welcome" => match serde_json::from_value::<SessionPayload>(message.payload) {
+                Ok(payload) => {
+                    log::info!("EventSub WebSocket session welcomed: {}", payload.session.id);
+                    session_tx.send_replace(Some(EventSubSession {
+                        id: payload.session.id.clone(),
+                        resumed: false,
+                    }));
+                    session = Some(payload.session);
+                }
+                Err(e) => log::warn!("Invalid session_welcome payload: {}", e),
+            },
+            "session_keepalive" => {}
+            "session_reconnect" => {
+                let reconnect_url = serde_json::from_value::<SessionPayload>(message.payload)
+                    .ok()
+                    .and_then(|p| p.session.reconnect_url);
+                let Some(reconnect_url) = reconnect_url else {
+                    log::warn!("session_reconnect without reconnect_url");
+                    return SessionEnd::Lost { welcomed: session.is_some() };
+                };
+                // Keep reading this socket until the new one is welcomed so no events are lost
+                let connecting = connect_reconnect_url(&reconnect_url);
+                tokio::pin!(connecting);
+                let mut old_open = true;
+                loop {
+                    tokio::select! {
+                        connected = &mut connecting => match connected {
+                            Some((new_stream, new_session)) => {
+                                let _ = stream.close(None).await;
+                                return SessionEnd::Reconnected(Box::new(new_stream), new_session);
+                            }
+                            None => return SessionEnd::Lost { welcomed: session.is_some() },
+                        },
+                        frame = stream.next(), if old_open => match frame {
+                            Some(Ok(Message::Text(text))) => {
+                                if let Ok(message) = serde_json::from_str::<WsMessage>(text.as_str()) {
+                                    deliver_event(message, sinks, seen).await;
+                                }
+                            }
+                            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => old_open = false,
+                            Some(Ok(_)) => {}
+                        },
+                    }
+                }
+            }
+            "notification" | "revocation" => deliver_event(message, sinks, seen).await,
+            other => log::debug!("Ignoring EventSub WebSocket message type {}", other),
+        }
+    }
+}
+
+/// Hand a notification or revocation to the sinks; other message types are ignored
+async fn deliver_event(message: WsMessage, sinks: &EventSubSinks, seen: &mut MessageIdCache) {
+    match message.metadata.message_type.as_str() {
+        "notification" => {
+            if !accept_message(&message.metadata, seen) {
+                return;
+            }
+            match serde_json::from_value::<TwitchEventSubPayload>(message.payload) {
+                Ok(payload) => sinks.deliver_notification(payload).await,
+                Err(e) => log::warn!("Invalid EventSub notification payload: {}", e),
+            }
+        }
+        "revocation" => {
+            if !accept_message(&message.metadata, seen) {
+                return;
+            }
+            match serde_json::from_value::<RevocationPayload>(message.payload) {
+                Ok(payload) => {
+                    let revocation = revocation_from_subscription(payload.subscription, chrono::Utc::now());
+                    sinks.deliver_revocation(revocation).await;
+                }
+                Err(e) => log::warn!("Invalid EventSub revocation payload: {}", e),
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replay and duplicate protection shared with the webhook transport
+fn accept_message(metadata: &WsMetadata, seen: &mut MessageIdCache) -> bool {
+    if !is_message_fresh(&metadata.message_timestamp, chrono::Utc::now()) {
+        log::warn!("Stale EventSub WebSocket message {} ignored", metadata.message_id);
+        return false;
+    }
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    if !seen.check_and_record(&metadata.message_id, now) {
+        log::info!("Duplicate EventSub WebSocket message {} ignored", metadata.message_id);
+        return false;
+    }
+    true
+}
+
+async fn connect_reconnect_url(url: &str) -> Option<(WsStream, WsSession)> {
+    let (mut stream, _) = match connect_async(url).await {
+        Ok(connected) => connected,
+        Err(e) => {
+            log::warn!("EventSub WebSocket reconnect failed: {}", e);
+            return None;
+        }
+    };
+
+    let deadline = tokio::time::Instant::now() + WELCOME_TIMEOUT;
+    loop {
+        let frame = match tokio::time::timeout_at(deadline, stream.next()).await {
+            Ok(Some(Ok(frame))) => frame,
+            _ => {
+                log::warn!("No welcome received on EventSub reconnect URL");
+                return None;
+            }
+        };
+        let Message::Text(text) = frame else {
+            continue;
+        };
+        let Ok(message) = serde_json::from_str::<WsMessage>(text.as_str()) else {
+            continue;
+        };
+        if message.metadata.message_type == "session_welcome" {
+            let payload = serde_json::from_value::<SessionPayload>(message.payload).ok()?;
+            return Some((stream, payload.session));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alerts::AlertPayload;
+    use crate::eventsub::SubscriptionHealthRegistry;
+    use futures_util::SinkExt;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+    use tokio::sync::{broadcast, RwLock};
+    use tokio_tungstenite::accept_async;
+
+    fn sinks() -> (EventSubSinks, broadcast::Receiver<AlertPayload>) {
+        let (alert_sender, alert_receiver) = broadcast::channel(16);
+        let (revocation_sender, _) = broadcast::channel(16);
+        let sinks = EventSubSinks {
+            alert_sender,
+            revocation_sender,
+            health: Arc::new(RwLock::new(SubscriptionHealthRegistry::new())),
+        };
+        (sinks, alert_receiver)
+    }
+
+    fn welcome(session_id: &str) -> String {
+        serde_json::json!({
+            "metadata": {
+                "message_id": format!("welcome-{}", session_id),
+                "message_type": "session_welcome",
+                "message_timestamp": chrono::Utc::now().to_rfc3339()
+            },
+            "payload": {
+                "session": {
+                    "id": session_id,
+                    "status": "connected",
+                    "connected_at": chrono::Utc::now().to_rfc3339(),
+                    "keepalive_timeout_seconds": 10,
+                    "reconnect_url": null
+                }
+            }
+        })
+        .to_string()
+    }
+
+    fn reconnect(url: &str) -> String {
+        serde_json::json!({
+            "metadata": {
+                "message_id": "reconnect-1",
+                "message_type": "session_reconnect",
+                "message_timestamp": chrono::Utc::now().to_rfc3339()
+            },
+            "payload": {
+                "session": {
+                    "id": "session-1",
+                    "status": "reconnecting",
+                    "keepalive_timeout_seconds": null,
+                    "reconnect_url": url,
+                    "connected_at": chrono::Utc::now().to_rfc3339()
+                }
+            }
+        })
+        .to_string()
+    }
+
+    fn follow_notification(message_id: &str, user_name: &str) -> String {
+        serde_json::json!({
+            "metadata": {
+                "message_id": message_id,
+                "message_type": "notification",
+                "message_timestamp": chrono::Utc::now().to_rfc3339(),
+                "subscription_type": "channel.follow",
+                "subscription_version": "2"
+            },
+            "payload": {
+                "subscription": {
+                    "id": "sub-1",
+                    "status": "enabled",
+                    "type": "channel.follow",
+                    "version": "2",
+                    "cost": 0,
+                    "condition": { "broadcaster_user_id": "1337", "moderator_user_id": "1337" },
+                    "transport": { "method": "websocket", "session_id": "session-1" },
+                    "created_at": "2023-07-19T14:56:51.634234626Z"
+                },
+                "event": {
+                    "user_id": "1234",
+                    "user_login": "cool_user",
+                    "user_name": user_name,
+                    "broadcaster_user_id": "1337",
+                    "broadcaster_user_login": "cooler_user",
+                    "broadcaster_user_name": "Cooler_User",
+                    "followed_at": "2023-07-19T14:56:51.634234626Z"
+                }
+            }
+        })
+        .to_string()
+    }
+
+    /// Accept one connection and send the scripted frames, then hold it open
+    async fn mock_server(frames: Vec<String>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(tcp).await.unwrap();
+            for frame in frames {
+                ws.send(Message::text(frame)).await.unwrap();
+            }
+            while let Some(Ok(_)) = ws.next().await {}
+        });
+        url
+    }
+
+    async fn next_alert(receiver: &mut broadcast::Receiver<AlertPayload>) -> AlertPayload {
+        tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .expect("alert within timeout")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn notifications_reach_alert_sender_once() {
+        let url = mock_server(vec![
+            welcome("session-1"),
+            follow_notification("msg-1", "First"),
+            follow_notification("msg-1", "First"),
+            follow_notification("msg-2", "Second"),
+        ])
+        .await;
+        let (sinks, mut alerts) = sinks();
+        let client = EventSubWebSocket::with_url(url, sinks.clone());
+        client.start().await;
+
+        assert_eq!(next_alert(&mut alerts).await.user_name, "First");
+        assert_eq!(next_alert(&mut alerts).await.user_name, "Second");
+        assert_eq!(client.session_id().as_deref(), Some("session-1"));
+        assert_eq!(sinks.health.read().await.snapshot()[0].status, "enabled");
+
+        client.stop().await;
+        assert_eq!(client.session_id(), None);
+    }
+
+    #[tokio::test]
+    async fn unused_session_close_reconnects() {
+        use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+        use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(tcp).await.unwrap();
+            ws.send(Message::text(welcome("session-1"))).await.unwrap();
+            let close = CloseFrame { code: CloseCode::from(CLOSE_CONNECTION_UNUSED), reason: "connection unused".into() };
+            ws.send(Message::Close(Some(close))).await.unwrap();
+            drop(ws);
+
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(tcp).await.unwrap();
+            ws.send(Message::text(welcome("session-2"))).await.unwrap();
+            while let Some(Ok(_)) = ws.next().await {}
+        });
+
+        let (sinks, _alerts) = sinks();
+        let client = EventSubWebSocket::with_url(url, sinks);
+        let mut session = client.subscribe_session();
+        client.start().await;
+
+        let reconnected = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                session.changed().await.unwrap();
+                if session.borrow_and_update().as_ref().is_some_and(|s| s.id == "session-2") {
+                    break;
+                }
+            }
+        })
+        .await;
+        assert!(reconnected.is_ok(), "the client should reconnect after close code 4003");
+        client.stop().await;
+    }
+
+    #[tokio::test]
+    async fn session_reconnect_moves_to_new_url() {
+        let second = mock_server(vec![welcome("session-2"), follow_notification("msg-3", "AfterMove")]).await;
+        let first = mock_server(vec![welcome("session-1"), reconnect(&second)]).await;
+        let (sinks, mut alerts) = sinks();
+        let client = EventSubWebSocket::with_url(first, sinks);
+        let mut session = client.subscribe_session();
+        client.start().await;
+
+        assert_eq!(next_alert(&mut alerts).await.user_name, "AfterMove");
+        let current = session.borrow_and_update().clone().unwrap();
+        assert_eq!(current.id, "session-2");
+        assert!(current.resumed);
+        client.stop().await;
+    }
+
+    #[tokio::test]
+    async fn old_socket_is_read_until_the_new_one_is_welcomed() {
+        // The new edge welcomes late, so the old one still delivers in between
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let second = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = accept_async(tcp).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            ws.send(Message::text(welcome("session-2"))).await.unwrap();
+            ws.send(Message::text(follow_notification("msg-3", "AfterMove"))).await.unwrap();
+            // Twitch may repeat an event on the new edge; it is still delivered once
+            ws.send(Message::text(follow_notification("msg-2", "DuringMove"))).await.unwrap();
+            ws.send(Message::text(follow_notification("msg-4", "Last"))).await.unwrap();
+            while let Some(Ok(_)) = ws.next().await {}
+        });
+        let first = mock_server(vec![
+            welcome("session-1"),
+            reconnect(&second),
+            follow_notification("msg-2", "DuringMove"),
+        ])
+        .await;
+        let (sinks, mut alerts) = sinks();
+        let client = EventSubWebSocket::with_url(first, sinks);
+        client.start().await;
+
+        assert_eq!(next_alert(&mut alerts).await.user_name, "DuringMove");
+        assert_eq!(next_alert(&mut alerts).await.user_name, "AfterMove");
+        assert_eq!(next_alert(&mut alerts).await.user_name, "Last");
+        assert_eq!(client.session_id().as_deref(), Some("session-2"));
+        client.stop().await;
+    }
+}
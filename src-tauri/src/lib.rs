@@ -1,11 +1,58 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
 use tauri::{Emitter, Manager};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, Mutex, RwLock};
 
 mod oauth;
 mod alerts;
+mod audio_cache;
+mod chat;
+mod chat_router;
+mod elevenlabs;
+mod eventsub;
+mod eventsub_subscriptions;
+mod eventsub_ws;
+mod local_tts;
+mod message_filter;
+mod moderation;
+mod normalizer;
+mod permissions;
+mod profanity;
+mod pronunciation;
+mod spam_guard;
+mod ssml;
+mod storage;
+mod tts;
+mod tts_queue;
+mod twitch_api;
+mod twitch_chat;
+mod viewer_voices;
+mod youtube_chat;
+mod youtube_feed;
 
 use oauth::{OAuthCallback, start_oauth_server};
 use alerts::AlertPayload;
+use audio_cache::{AudioCache, AudioCacheStats, DEFAULT_CACHE_MAX_BYTES};
+use chat::{ChatBadge, ChatMessage};
+use chat_router::{route_chat_message, ChatOutcome};
+use elevenlabs::{ElevenLabsClient, ElevenLabsError, ElevenLabsSettingsStore, ElevenLabsUsage, ElevenLabsVoice, SpeechRequest};
+use eventsub::{EventSubRevocation, EventSubSinks, SubscriptionHealth, SubscriptionHealthRegistry};
+use eventsub_subscriptions::{EventSubManager, EventSubTypeStatus};
+use eventsub_ws::EventSubWebSocket;
+use local_tts::{LocalTts, LocalTtsError, LocalVoice, DEFAULT_LOCAL_VOICE};
+use message_filter::{FilterConfig, FilterDecision, MessageFilter};
+use moderation::{AuditEntry, AuditStore, Moderation, MuteStore};
+use permissions::{FollowerLookup, PermissionPolicy, PermissionStore, Permissions};
+use profanity::{ProfanityConfig, ProfanityFilter};
+use pronunciation::{language_for_voice, DictionaryFormat, PronunciationDictionary, PronunciationRule};
+use spam_guard::{SpamGuard, SpamGuardConfig, SpamGuardStore};
+use twitch_api::{HelixClient, HelixSubscription, TwitchCredentialStore};
+use tts::{ProviderHealth, ProviderVoice, SynthesisRequest, TtsChainError, TtsChainStore, TtsProvider, TtsRegistry};
+use tts_queue::{DropPolicy, FinishReason, NewQueueItem, QueueEvent, QueuePriority, QueueSnapshot, TtsQueue};
+use twitch_chat::TwitchChatClient;
+use viewer_voices::{ViewerVoiceConfig, ViewerVoiceStore, ViewerVoices, VoiceChoice};
+use youtube_chat::{YouTubeChatPoller, YouTubeCredentialStore, YouTubeCredentials};
 
 
 
@@ -14,6 +61,26 @@ pub struct AppState {
     pub oauth_sender: broadcast::Sender<OAuthCallback>,
     #[allow(dead_code)]
     pub alert_sender: broadcast::Sender<AlertPayload>,
+    #[allow(dead_code)]
+    pub revocation_sender: broadcast::Sender<EventSubRevocation>,
+    pub eventsub_health: Arc<RwLock<SubscriptionHealthRegistry>>,
+    pub eventsub_ws: Arc<EventSubWebSocket>,
+    pub eventsub_manager: Arc<EventSubManager>,
+    pub twitch_chat: Arc<TwitchChatClient>,
+    pub youtube_credentials: Arc<YouTubeCredentialStore>,
+    pub youtube_chat: Arc<YouTubeChatPoller>,
+    pub tts_queue: Arc<Mutex<TtsQueue>>,
+    pub elevenlabs: ElevenLabsClient,
+    pub local_tts: LocalTts,
+    pub tts: Arc<TtsRegistry>,
+    pub audio_cache: Option<Arc<AudioCache>>,
+    pub pronunciation: PronunciationDictionary,
+    pub viewer_voices: Arc<ViewerVoices>,
+    pub message_filter: Arc<MessageFilter>,
+    pub permissions: Arc<Permissions>,
+    pub spam_guard: Arc<SpamGuard>,
+    pub profanity: Arc<ProfanityFilter>,
+    pub moderation: Arc<Moderation>,
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -23,6 +90,17 @@ pub fn run() {
     
     let (oauth_sender, mut oauth_receiver) = broadcast::channel(32);
     let (alert_sender, mut alert_receiver) = broadcast::channel(32);
+    let (revocation_sender, mut revocation_receiver) = broadcast::channel(32);
+    let (chat_sender, mut chat_receiver) = broadcast::channel::<ChatMessage>(256);
+    let eventsub_health = Arc::new(RwLock::new(SubscriptionHealthRegistry::new()));
+    let eventsub_sinks = EventSubSinks {
+        alert_sender: alert_sender.clone(),
+        revocation_sender: revocation_sender.clone(),
+        health: eventsub_health.clone(),
+    };
+    let eventsub_ws = Arc::new(EventSubWebSocket::new(eventsub_sinks.clone()));
+    let (queue_sender, mut queue_receiver) = broadcast::channel::<QueueEvent>(64);
+    let tts_queue = Arc::new(Mutex::new(TtsQueue::new(queue_sender)));
     
 tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
@@ -35,13 +113,94 @@ tauri::Builder::default()
                 )?;
             }
             
+            let data_dir = app.path().app_data_dir()
+                .map_err(|e| log::warn!("App data directory unavailable, settings will not persist: {}", e))
+                .ok();
+            
+            let twitch_credentials = Arc::new(TwitchCredentialStore::load(
+                data_dir.as_deref().map(|dir| storage::data_file(dir, "twitch_credentials.json")),
+            ));
+            let twitch_chat = Arc::new(TwitchChatClient::new(chat_sender.clone(), twitch_credentials.clone()));
+            let permissions = Arc::new(Permissions::new(
+                Arc::new(PermissionStore::load(
+                    data_dir.as_deref().map(|dir| storage::data_file(dir, "permissions.json")),
+                )),
+                FollowerLookup::new(HelixClient::new(), twitch_credentials.clone()),
+            ));
+            let youtube_credentials = Arc::new(YouTubeCredentialStore::load(
+                data_dir.as_deref().map(|dir| storage::data_file(dir, "youtube_credentials.json")),
+            ));
+            let youtube_chat = Arc::new(YouTubeChatPoller::new(
+                chat_sender,
+                alert_sender.clone(),
+                youtube_credentials.clone(),
+            ));
+            let youtube_credentials_oauth = youtube_credentials.clone();
+            let elevenlabs = ElevenLabsClient::new(Arc::new(ElevenLabsSettingsStore::load(
+                data_dir.as_deref().map(|dir| storage::data_file(dir, "elevenlabs.json")),
+            )));
+            let local_tts = LocalTts::new(data_dir.as_deref().map(|dir| dir.join("piper-voices")));
+            let tts_providers: Vec<Arc<dyn TtsProvider>> = vec![
+                Arc::new(elevenlabs.clone()),
+                Arc::new(local_tts.clone()),
+            ];
+            let audio_cache = app.path().app_cache_dir()
+                .map_err(|e| log::warn!("App cache directory unavailable, synthesized audio will not be cached: {}", e))
+                .ok()
+                .map(|dir| Arc::new(AudioCache::open(dir.join("audio"), DEFAULT_CACHE_MAX_BYTES)));
+            let mut tts = TtsRegistry::new(
+                tts_providers,
+                TtsChainStore::load(data_dir.as_deref().map(|dir| storage::data_file(dir, "tts_chain.json"))),
+            );
+            if let Some(ref cache) = audio_cache {
+                tts = tts.with_cache(cache.clone());
+            }
+            let tts = Arc::new(tts);
+            let viewer_voices = Arc::new(ViewerVoices::new(Arc::new(ViewerVoiceStore::load(
+                data_dir.as_deref().map(|dir| storage::data_file(dir, "viewer_voices.json")),
+            ))));
+            let message_filter = Arc::new(MessageFilter::load(
+                data_dir.as_deref().map(|dir| storage::data_file(dir, "message_filter.json")),
+            ));
+            let spam_guard = Arc::new(SpamGuard::new(Arc::new(SpamGuardStore::load(
+                data_dir.as_deref().map(|dir| storage::data_file(dir, "spam_guard.json")),
+            ))));
+            let profanity = Arc::new(ProfanityFilter::load(
+                data_dir.as_deref().map(|dir| storage::data_file(dir, "profanity.json")),
+            ));
+            let profanity_alerts = profanity.clone();
+            let moderation = Arc::new(Moderation::new(
+                tts_queue.clone(),
+                Arc::new(MuteStore::load(data_dir.as_deref().map(|dir| storage::data_file(dir, "tts_mutes.json")))),
+                Arc::new(AuditStore::load(data_dir.as_deref().map(|dir| storage::data_file(dir, "moderation_audit.json")))),
+            ));
+            let pronunciation = PronunciationDictionary::load(
+                data_dir.as_deref().map(|dir| storage::data_file(dir, "pronunciation.json")),
+            );
+            let eventsub_manager = Arc::new(EventSubManager::new(
+                HelixClient::new(),
+                twitch_credentials,
+                eventsub_ws.clone(),
+                eventsub_health.clone(),
+            ));
+            tauri::async_runtime::spawn(eventsub_manager.clone().run_resubscriber());
+            let eventsub_manager_oauth = eventsub_manager.clone();
+            
             let oauth_sender_clone = oauth_sender.clone();
             let alert_sender_clone = alert_sender.clone();
             let app_handle_oauth = app.handle().clone();
             let app_handle_alerts = app.handle().clone();
+            let app_handle_revocations = app.handle().clone();
+            let app_handle_chat = app.handle().clone();
+            let app_handle_queue = app.handle().clone();
+            let tts_queue_alerts = tts_queue.clone();
             
             tauri::async_runtime::spawn(async move {
-                if let Err(e) = start_oauth_server(oauth_sender_clone, alert_sender_clone).await {
+                if let Err(e) = start_oauth_server(
+                    oauth_sender_clone,
+                    alert_sender_clone,
+                    eventsub_sinks,
+                ).await {
                     log::error!("OAuth server error: {}", e);
                 }
             });
@@ -49,6 +208,25 @@ tauri::Builder::default()
             app.manage(AppState {
                 oauth_sender,
                 alert_sender,
+                revocation_sender,
+                eventsub_health,
+                eventsub_ws,
+                eventsub_manager,
+                twitch_chat,
+                youtube_credentials,
+                youtube_chat,
+                tts_queue,
+                elevenlabs,
+                local_tts,
+                tts,
+                audio_cache,
+                pronunciation,
+                viewer_voices,
+                message_filter,
+                permissions,
+                spam_guard,
+                profanity,
+                moderation,
             });
             
             tauri::async_runtime::spawn(async move {
@@ -57,6 +235,31 @@ tauri::Builder::default()
                         Ok(callback) => {
                             log::info!("Received OAuth callback, emitting to frontend: service={}", callback.service);
                             
+                            if callback.service == "twitch" && callback.error.is_none() && !callback.token.is_empty() {
+                                let manager = eventsub_manager_oauth.clone();
+                                let token = callback.token.clone();
+                                tauri::async_runtime::spawn(async move {
+                                    if let Err(e) = manager.connect(&token).await {
+                                        log::warn!("Failed to set up Twitch EventSub: {}", e);
+                                    }
+                                });
+                            }
+                            
+                            if callback.service == "youtube" && callback.error.is_none() && !callback.token.is_empty() {
+                                // Google omits the refresh token on repeat consents, keep the one we have
+                                let refresh_token = match callback.refresh_token.clone() {
+                                    Some(token) => Some(token),
+                                    None => youtube_credentials_oauth.get().await.and_then(|c| c.refresh_token),
+                                };
+                                let credentials = YouTubeCredentials {
+                                    access_token: callback.token.clone(),
+                                    refresh_token,
+                                };
+                                if let Err(e) = youtube_credentials_oauth.set(credentials).await {
+                                    log::warn!("Failed to store YouTube credentials: {}", e);
+                                }
+                            }
+                            
                             let payload = serde_json::json!({
                                 "type": format!("{}-oauth-callback", callback.service),
                                 "token": callback.token,
@@ -81,9 +284,34 @@ tauri::Builder::default()
             tauri::async_runtime::spawn(async move {
                 loop {
                     match alert_receiver.recv().await {
-                        Ok(alert) => {
+                        Ok(mut alert) => {
+                            alert.spoken_amount = alert
+                                .amount
+                                .as_deref()
+                                .and_then(|amount| normalizer::speak_amount(amount, alert.currency.as_deref()));
+                            if let Some(user_message) = alert.user_message.take() {
+                                alert.user_message = profanity_alerts.apply(&user_message).await;
+                            }
+                            let Some(user_name) = profanity_alerts.apply_name(&alert.user_name).await else {
+                                log::info!("Dropped {} alert: the sender's name is filtered", alert.alert_type);
+                                continue;
+                            };
+                            if user_name != alert.user_name {
+                                alert.set_user_name(user_name);
+                            }
                             log::info!("Received alert, emitting to frontend: platform={}, type={}", alert.platform, alert.alert_type);
                             
+                            let mut item = NewQueueItem {
+                                platform: alert.platform.clone(),
+                                user_name: alert.user_name.clone(),
+                                text: alert.message.clone(),
+                                priority: QueuePriority::Alert,
+                                speech: Some(ssml::alert_speech(&alert)),
+                                voice: None,
+                            };
+                            prepare_queue_item(&app_handle_alerts.state::<AppState>(), &mut item).await;
+                            tts_queue_alerts.lock().await.enqueue(item);
+                            
                             app_handle_alerts.emit("integration-alert", alert)
                                 .map_err(|e| log::error!("Failed to emit alert: {}", e))
                                 .ok();
@@ -95,11 +323,176 @@ tauri::Builder::default()
                     }
                 }
             });
+
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    match revocation_receiver.recv().await {
+                        Ok(revocation) => {
+                            log::info!("EventSub subscription revoked, emitting to frontend: type={}", revocation.subscription_type);
+                            
+                            app_handle_revocations.emit("eventsub-revoked", revocation)
+                                .map_err(|e| log::error!("Failed to emit revocation: {}", e))
+                                .ok();
+                        }
+                        Err(e) => {
+                            log::error!("Revocation receiver error: {}", e);
+                            break;
+                        }
+                    }
+                }
+            });
+
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    match chat_receiver.recv().await {
+                        Ok(mut message) => {
+                            let state = app_handle_chat.state::<AppState>();
+                            match route_chat_message(&state, &mut message).await {
+                                ChatOutcome::NotPermitted => {
+                                    log::debug!("{} lacks the role for {:?}", message.user_name, message.text);
+                                }
+                                ChatOutcome::Moderation(Ok(entry)) => {
+                                    log::info!("{} used a moderation command: {:?}", entry.moderator, entry.action);
+                                    app_handle_chat.emit("moderation-action", entry)
+                                        .map_err(|e| log::error!("Failed to emit moderation action: {}", e))
+                                        .ok();
+                                }
+                                ChatOutcome::Moderation(Err(e)) => {
+                                    log::warn!("Moderation command from {} failed: {}", message.user_name, e);
+                                }
+                                ChatOutcome::Voice(Ok(outcome)) => {
+                                    app_handle_chat.emit("voice-command", serde_json::json!({
+                                        "platform": message.platform,
+                                        "user_name": message.user_name,
+                                        "outcome": outcome,
+                                    }))
+                                    .map_err(|e| log::error!("Failed to emit voice command: {}", e))
+                                    .ok();
+                                }
+                                ChatOutcome::Voice(Err(e)) => {
+                                    log::warn!("Failed to handle voice command from {}: {}", message.user_name, e);
+                                }
+                                ChatOutcome::Filtered { rule } => {
+                                    log::debug!("Not reading message from {} (rule {:?})", message.user_name, rule);
+                                }
+                                ChatOutcome::Suppressed(reason) => {
+                                    app_handle_chat.emit("message-suppressed", serde_json::json!({
+                                        "platform": message.platform,
+                                        "user_name": message.user_name,
+                                        "message_id": message.id,
+                                        "text": message.text,
+                                        "reason": reason,
+                                    }))
+                                    .map_err(|e| log::error!("Failed to emit suppressed message: {}", e))
+                                    .ok();
+                                }
+                                ChatOutcome::Queued => {}
+                            }
+
+                            app_handle_chat.emit("chat-message", message)
+                                .map_err(|e| log::error!("Failed to emit chat message: {}", e))
+                                .ok();
+                        }
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            log::warn!("Chat receiver lagged, skipped {} messages", skipped);
+                        }
+                        Err(e) => {
+                            log::error!("Chat receiver error: {}", e);
+                            break;
+                        }
+                    }
+                }
+            });
+
+            tauri::async_runtime::spawn(async move {
+                loop {
+                    let emitted = match queue_receiver.recv().await {
+                        Ok(QueueEvent::Updated(snapshot)) => app_handle_queue.emit("queue-updated", snapshot),
+                        Ok(QueueEvent::ItemStarted(item)) => app_handle_queue.emit("item-started", item),
+                        Ok(QueueEvent::ItemFinished { item, reason }) => app_handle_queue.emit(
+                            "item-finished",
+                            serde_json::json!({ "item": item, "reason": reason }),
+                        ),
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            log::warn!("Queue event receiver lagged, skipped {} events", skipped);
+                            continue;
+                        }
+                        Err(e) => {
+                            log::error!("Queue event receiver error: {}", e);
+                            break;
+                        }
+                    };
+                    emitted
+                        .map_err(|e| log::error!("Failed to emit queue event: {}", e))
+                        .ok();
+                }
+            });
             
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
-            open_oauth_url
+            open_oauth_url,
+            get_eventsub_status,
+            start_eventsub_websocket,
+            stop_eventsub_websocket,
+            connect_twitch_eventsub,
+            disconnect_twitch_eventsub,
+            create_eventsub_subscription,
+            list_eventsub_subscriptions,
+            delete_eventsub_subscription,
+            get_eventsub_type_status,
+            connect_twitch_chat,
+            disconnect_twitch_chat,
+            set_youtube_credentials,
+            connect_youtube_chat,
+            disconnect_youtube_chat,
+            get_tts_queue,
+            enqueue_tts_message,
+            finish_tts_item,
+            skip_tts_item,
+            pause_tts_queue,
+            resume_tts_queue,
+            clear_tts_queue,
+            set_tts_queue_limits,
+            set_elevenlabs_api_key,
+            has_elevenlabs_api_key,
+            list_elevenlabs_voices,
+            get_elevenlabs_usage,
+            elevenlabs_text_to_speech,
+            elevenlabs_text_to_speech_stream,
+            list_local_voices,
+            local_text_to_speech,
+            synthesize_speech,
+            list_tts_voices,
+            get_tts_provider_health,
+            get_tts_fallback_chain,
+            set_tts_fallback_chain,
+            get_audio_cache_stats,
+            purge_audio_cache,
+            list_pronunciation_rules,
+            add_pronunciation_rule,
+            update_pronunciation_rule,
+            remove_pronunciation_rule,
+            import_pronunciation_dictionary,
+            export_pronunciation_dictionary,
+            preview_speech_text,
+            get_viewer_voice_config,
+            set_viewer_voice_pool,
+            set_viewer_voice_choices,
+            set_viewer_voice_override,
+            get_viewer_voice,
+            get_message_filter,
+            set_message_filter,
+            test_message_filter,
+            get_permission_policy,
+            set_permission_policy,
+            get_spam_guard_config,
+            set_spam_guard_config,
+            get_profanity_config,
+            set_profanity_config,
+            list_tts_mutes,
+            unmute_tts_user,
+            get_moderation_audit
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -116,3 +509,563 @@ async fn open_oauth_url(
     
     Ok(())
 }
+
+#[tauri::command]
+async fn get_eventsub_status(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<SubscriptionHealth>, String> {
+    Ok(state.eventsub_health.read().await.snapshot())
+}
+
+#[tauri::command]
+async fn start_eventsub_websocket(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.eventsub_ws.start().await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn stop_eventsub_websocket(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.eventsub_ws.stop().await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn connect_twitch_eventsub(
+    access_token: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.eventsub_manager.connect(&access_token).await.map(|_| ())
+}
+
+#[tauri::command]
+async fn disconnect_twitch_eventsub(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.eventsub_manager.disconnect().await
+}
+
+#[tauri::command]
+async fn create_eventsub_subscription(
+    subscription_type: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<HelixSubscription, String> {
+    state.eventsub_manager.create(&subscription_type).await
+}
+
+#[tauri::command]
+async fn list_eventsub_subscriptions(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<HelixSubscription>, String> {
+    state.eventsub_manager.list().await
+}
+
+#[tauri::command]
+async fn delete_eventsub_subscription(
+    id: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.eventsub_manager.delete(&id).await
+}
+
+#[tauri::command]
+async fn get_eventsub_type_status(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<EventSubTypeStatus>, String> {
+    Ok(state.eventsub_manager.type_status().await)
+}
+
+#[tauri::command]
+async fn connect_twitch_chat(
+    channel: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.twitch_chat.start(&channel).await
+}
+
+#[tauri::command]
+async fn disconnect_twitch_chat(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.twitch_chat.stop().await;
+    Ok(())
+}
+
+/// Hand the backend the frontend's current YouTube tokens, e.g. after a restart
+#[tauri::command]
+async fn set_youtube_credentials(
+    access_token: String,
+    refresh_token: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.youtube_credentials.set(YouTubeCredentials { access_token, refresh_token }).await
+}
+
+#[tauri::command]
+async fn connect_youtube_chat(
+    video_id: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    state.youtube_chat.start(video_id.as_deref()).await
+}
+
+#[tauri::command]
+async fn disconnect_youtube_chat(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.youtube_chat.stop().await;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_tts_queue(
+    state: tauri::State<'_, AppState>,
+) -> Result<QueueSnapshot, String> {
+    Ok(state.tts_queue.lock().await.snapshot())
+}
+
+/// Returns the queued item id, or `None` when the queue was full
+#[tauri::command]
+async fn enqueue_tts_message(
+    mut item: NewQueueItem,
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<u64>, String> {
+    if item.text.trim().is_empty() {
+        return Err("Message is empty".to_string());
+    }
+    prepare_queue_item(&state, &mut item).await;
+    Ok(state.tts_queue.lock().await.enqueue(item))
+}
+
+/// Called by the player when it is done speaking an item
+#[tauri::command]
+async fn finish_tts_item(
+    id: u64,
+    failed: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    let reason = if failed { FinishReason::Failed } else { FinishReason::Completed };
+    state.tts_queue.lock().await.finish(id, reason)
+}
+
+#[tauri::command]
+async fn skip_tts_item(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.tts_queue.lock().await.skip();
+    Ok(())
+}
+
+#[tauri::command]
+async fn pause_tts_queue(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.tts_queue.lock().await.pause();
+    Ok(())
+}
+
+#[tauri::command]
+async fn resume_tts_queue(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.tts_queue.lock().await.resume();
+    Ok(())
+}
+
+#[tauri::command]
+async fn clear_tts_queue(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.tts_queue.lock().await.clear();
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_tts_queue_limits(
+    max_length: usize,
+    drop_policy: DropPolicy,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.tts_queue.lock().await.set_limits(max_length, drop_policy);
+    Ok(())
+}
+
+/// Store the ElevenLabs key in the backend; a blank key removes it
+#[tauri::command]
+async fn set_elevenlabs_api_key(
+    api_key: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.elevenlabs.set_api_key(&api_key).await
+}
+
+#[tauri::command]
+async fn has_elevenlabs_api_key(
+    state: tauri::State<'_, AppState>,
+) -> Result<bool, String> {
+    Ok(state.elevenlabs.has_api_key().await)
+}
+
+#[tauri::command]
+async fn list_elevenlabs_voices(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<ElevenLabsVoice>, ElevenLabsError> {
+    state.elevenlabs.list_voices().await
+}
+
+#[tauri::command]
+async fn get_elevenlabs_usage(
+    state: tauri::State<'_, AppState>,
+) -> Result<ElevenLabsUsage, ElevenLabsError> {
+    state.elevenlabs.usage().await
+}
+
+/// Returns the MP3 as a raw binary response rather than a JSON number array
+#[tauri::command]
+async fn elevenlabs_text_to_speech(
+    mut request: SpeechRequest,
+    state: tauri::State<'_, AppState>,
+) -> Result<tauri::ipc::Response, ElevenLabsError> {
+    request.text = prepare_speech_text(&state, &request.text, None).await;
+    let audio = state.elevenlabs.text_to_speech(&request).await?;
+    Ok(tauri::ipc::Response::new(audio))
+}
+
+#[tauri::command]
+async fn elevenlabs_text_to_speech_stream(
+    mut request: SpeechRequest,
+    on_chunk: tauri::ipc::Channel,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), ElevenLabsError> {
+    request.text = prepare_speech_text(&state, &request.text, None).await;
+    state
+        .elevenlabs
+        .text_to_speech_stream(&request, |chunk| {
+            if let Err(e) = on_chunk.send(chunk.into()) {
+                log::warn!("Failed to forward speech chunk: {}", e);
+            }
+        })
+        .await
+}
+
+#[tauri::command]
+async fn list_local_voices(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<LocalVoice>, LocalTtsError> {
+    state.local_tts.list_voices().await
+}
+
+/// Returns WAV bytes as a raw binary response
+#[tauri::command]
+async fn local_text_to_speech(
+    text: String,
+    voice: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<tauri::ipc::Response, LocalTtsError> {
+    let voice = voice.unwrap_or_else(|| DEFAULT_LOCAL_VOICE.to_string());
+    let text = prepare_speech_text(&state, &text, Some(&voice)).await;
+    let audio = state.local_tts.synthesize(&text, &voice).await?;
+    Ok(tauri::ipc::Response::new(audio))
+}
+
+/// Synthesize through the fallback chain; returns the audio as a raw binary response
+#[tauri::command]
+async fn synthesize_speech(
+    mut request: SynthesisRequest,
+    state: tauri::State<'_, AppState>,
+) -> Result<tauri::ipc::Response, TtsChainError> {
+    // Queued items were prepared when they were queued; the speech already holds the text to read
+    if let Some(ref speech) = request.speech {
+        request.text = speech.plain_text();
+    }
+    let audio = state.tts.synthesize(&request).await?;
+    log::info!("Synthesized {} bytes of {} with {}", audio.data.len(), audio.mime_type, audio.provider);
+    Ok(tauri::ipc::Response::new(audio.data))
+}
+
+#[tauri::command]
+async fn list_tts_voices(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<ProviderVoice>, String> {
+    Ok(state.tts.list_voices().await)
+}
+
+#[tauri::command]
+async fn get_tts_provider_health(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<ProviderHealth>, String> {
+    Ok(state.tts.health().await)
+}
+
+#[tauri::command]
+async fn get_tts_fallback_chain(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<String>, String> {
+    Ok(state.tts.chain().await)
+}
+
+#[tauri::command]
+async fn set_tts_fallback_chain(
+    providers: Vec<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.tts.set_chain(providers).await
+}
+
+/// `None` when there is no cache directory and caching is off
+#[tauri::command]
+async fn get_audio_cache_stats(
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<AudioCacheStats>, String> {
+    match state.audio_cache {
+        Some(ref cache) => Ok(Some(cache.stats().await)),
+        None => Ok(None),
+    }
+}
+
+#[tauri::command]
+async fn purge_audio_cache(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    if let Some(ref cache) = state.audio_cache {
+        cache.purge().await;
+        log::info!("Audio cache purged");
+    }
+    Ok(())
+}
+
+/// Rewrite what an item will read before it is queued, so the browser voice hears the same text as the backend providers.
+/// `text` stays as written for the queue display; an item without `speech` gets one holding the prepared text.
+pub(crate) async fn prepare_queue_item(state: &AppState, item: &mut NewQueueItem) {
+    let voice = item.voice.clone();
+    match item.speech {
+        Some(ref mut speech) => {
+            for text in speech.spoken_text_mut() {
+                *text = prepare_speech_text(state, text, voice.as_deref()).await;
+            }
+        }
+        None => {
+            let text = prepare_speech_text(state, &item.text, voice.as_deref()).await;
+            item.speech = Some(ssml::Speech::new().text(&text));
+        }
+    }
+}
+
+/// The user's pronunciation rules first, so they see the text as typed, then the normalizer
+async fn prepare_speech_text(state: &AppState, text: &str, voice: Option<&str>) -> String {
+    let text = state.pronunciation.apply(text, &language_for_voice(voice)).await;
+    normalizer::normalize(&text)
+}
+
+#[tauri::command]
+async fn list_pronunciation_rules(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<PronunciationRule>, String> {
+    Ok(state.pronunciation.rules().await)
+}
+
+/// Returns the stored rule with its assigned id
+#[tauri::command]
+async fn add_pronunciation_rule(
+    rule: PronunciationRule,
+    state: tauri::State<'_, AppState>,
+) -> Result<PronunciationRule, String> {
+    state.pronunciation.add(rule).await
+}
+
+#[tauri::command]
+async fn update_pronunciation_rule(
+    rule: PronunciationRule,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.pronunciation.update(rule).await
+}
+
+#[tauri::command]
+async fn remove_pronunciation_rule(
+    id: u64,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.pronunciation.remove(id).await
+}
+
+/// Returns how many rules were imported
+#[tauri::command]
+async fn import_pronunciation_dictionary(
+    data: String,
+    format: DictionaryFormat,
+    replace: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<usize, String> {
+    let count = state.pronunciation.import(&data, format, replace).await?;
+    log::info!("Imported {} pronunciation rules", count);
+    Ok(count)
+}
+
+#[tauri::command]
+async fn export_pronunciation_dictionary(
+    format: DictionaryFormat,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    state.pronunciation.export(format).await
+}
+
+/// The text exactly as it would be sent to the TTS engine for `voice`
+#[tauri::command]
+async fn preview_speech_text(
+    text: String,
+    voice: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    Ok(prepare_speech_text(&state, &text, voice.as_deref()).await)
+}
+
+#[tauri::command]
+async fn get_viewer_voice_config(
+    state: tauri::State<'_, AppState>,
+) -> Result<ViewerVoiceConfig, String> {
+    Ok(state.viewer_voices.config().await)
+}
+
+#[tauri::command]
+async fn set_viewer_voice_pool(
+    pool: Vec<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.viewer_voices.set_pool(pool).await
+}
+
+/// The voices viewers may pick with `!voice <name>`
+#[tauri::command]
+async fn set_viewer_voice_choices(
+    choices: Vec<VoiceChoice>,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.viewer_voices.set_choices(choices).await
+}
+
+/// `voice: None` returns the viewer to their automatic voice
+#[tauri::command]
+async fn set_viewer_voice_override(
+    platform: String,
+    user_name: String,
+    voice: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.viewer_voices.set_override(&platform, &user_name, voice).await
+}
+
+#[tauri::command]
+async fn get_viewer_voice(
+    platform: String,
+    user_name: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<String>, String> {
+    Ok(state.viewer_voices.voice_for(&platform, &user_name).await)
+}
+
+#[tauri::command]
+async fn get_message_filter(
+    state: tauri::State<'_, AppState>,
+) -> Result<FilterConfig, String> {
+    Ok(state.message_filter.config().await)
+}
+
+#[tauri::command]
+async fn set_message_filter(
+    config: FilterConfig,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.message_filter.set_config(config).await
+}
+
+/// Run the filter over a made-up message, so rules can be tried before chat hits them
+#[tauri::command]
+async fn test_message_filter(
+    text: String,
+    user_name: Option<String>,
+    badges: Vec<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<FilterDecision, String> {
+    let user_name = user_name.unwrap_or_default();
+    let message = ChatMessage {
+        display_name: user_name.clone(),
+        user_name,
+        text,
+        badges: badges.into_iter().map(|name| ChatBadge { name, version: "1".to_string() }).collect(),
+        ..Default::default()
+    };
+    Ok(state.message_filter.evaluate(&message).await)
+}
+
+#[tauri::command]
+async fn get_permission_policy(
+    state: tauri::State<'_, AppState>,
+) -> Result<PermissionPolicy, String> {
+    Ok(state.permissions.policy().await)
+}
+
+#[tauri::command]
+async fn set_permission_policy(
+    policy: PermissionPolicy,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.permissions.set_policy(policy).await
+}
+
+#[tauri::command]
+async fn get_spam_guard_config(
+    state: tauri::State<'_, AppState>,
+) -> Result<SpamGuardConfig, String> {
+    Ok(state.spam_guard.config().await)
+}
+
+#[tauri::command]
+async fn set_spam_guard_config(
+    config: SpamGuardConfig,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.spam_guard.set_config(config).await
+}
+
+#[tauri::command]
+async fn get_profanity_config(
+    state: tauri::State<'_, AppState>,
+) -> Result<ProfanityConfig, String> {
+    Ok(state.profanity.config().await)
+}
+
+#[tauri::command]
+async fn set_profanity_config(
+    config: ProfanityConfig,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.profanity.set_config(config).await
+}
+
+/// Muted viewers by `platform:user_name`, with when each mute ends
+#[tauri::command]
+async fn list_tts_mutes(
+    state: tauri::State<'_, AppState>,
+) -> Result<HashMap<String, DateTime<Utc>>, String> {
+    Ok(state.moderation.mutes().await)
+}
+
+#[tauri::command]
+async fn unmute_tts_user(
+    platform: String,
+    user_name: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.moderation.unmute(&platform, &user_name).await
+}
+
+#[tauri::command]
+async fn get_moderation_audit(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<AuditEntry>, String> {
+    Ok(state.moderation.audit_log().await)
+}
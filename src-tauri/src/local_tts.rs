@@ -0,0 +1,406 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+const DEFAULT_ESPEAK_PATH: &str = "espeak-ng";
+const DEFAULT_PIPER_PATH: &str = "piper";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Russian out of the box, since espeak-ng ships it everywhere
+pub const DEFAULT_LOCAL_VOICE: &str = "espeak:ru";
+
+fn get_espeak_path() -> String {
+    std::env::var("ESPEAK_NG_PATH")
+        .unwrap_or_else(|_| DEFAULT_ESPEAK_PATH.to_string())
+}
+
+fn get_piper_path() -> String {
+    std::env::var("PIPER_PATH")
+        .unwrap_or_else(|_| DEFAULT_PIPER_PATH.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocalEngine {
+    Espeak,
+    Piper,
+}
+
+/// A voice offered by one of the local engines; `id` is `espeak:<name>` or `piper:<model>`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocalVoice {
+    pub id: String,
+    pub name: String,
+    pub language: String,
+    pub engine: LocalEngine,
+    pub gender: Option<String>,
+}
+
+/// Failures the UI reacts to differently, serialized as `{"kind": "...", ...}`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LocalTtsError {
+    BinaryNotFound { binary: String },
+    VoiceNotFound { voice: String },
+    InvalidRequest { message: String },
+    Timeout { binary: String, secs: u64 },
+    Failed { message: String },
+}
+
+impl fmt::Display for LocalTtsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LocalTtsError::BinaryNotFound { binary } => write!(f, "{} is not installed or not on PATH", binary),
+            LocalTtsError::VoiceNotFound { voice } => write!(f, "Local voice {} not found", voice),
+            LocalTtsError::InvalidRequest { message } => write!(f, "Invalid local TTS request: {}", message),
+            LocalTtsError::Timeout { binary, secs } => write!(f, "{} did not finish within {}s", binary, secs),
+            LocalTtsError::Failed { message } => write!(f, "Local TTS failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for LocalTtsError {}
+
+/// Run `binary` with `input` on stdin and return its stdout, killing it after `timeout`
+async fn run_engine(binary: &str, args: &[&str], input: &str, timeout: Duration) -> Result<Vec<u8>, LocalTtsError> {
+    let mut child = Command::new(binary)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound | std::io::ErrorKind::PermissionDenied => {
+                LocalTtsError::BinaryNotFound { binary: binary.to_string() }
+            }
+            _ => LocalTtsError::Failed { message: format!("Failed to start {}: {}", binary, e) },
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // An engine that exits without reading stdin is reported by its exit status instead
+        let _ = stdin.write_all(input.as_bytes()).await;
+    }
+
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| LocalTtsError::Timeout { binary: binary.to_string(), secs: timeout.as_secs() })?
+        .map_err(|e| LocalTtsError::Failed { message: format!("{} failed: {}", binary, e) })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(LocalTtsError::Failed {
+            message: format!("{} exited with {}: {}", binary, output.status, stderr.trim()),
+        });
+    }
+    Ok(output.stdout)
+}
+
+/// Parse the table printed by `espeak-ng --voices`:
+/// `Pty Language       Age/Gender VoiceName          File                 Other Languages`
+pub fn parse_espeak_voices(output: &str) -> Vec<LocalVoice> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let [_, language, age_gender, name, ..] = columns.as_slice() else {
+                return None;
+            };
+            let gender = match age_gender.rsplit('/').next() {
+                Some("M") => Some("male".to_string()),
+                Some("F") => Some("female".to_string()),
+                _ => None,
+            };
+            Some(LocalVoice {
+                id: format!("espeak:{}", language),
+                name: name.replace('_', " "),
+                language: language.to_string(),
+                engine: LocalEngine::Espeak,
+                gender,
+            })
+        })
+        .collect()
+}
+
+/// espeak voice names go on the command line, so refuse anything that could read as a flag
+fn is_safe_espeak_voice(voice: &str) -> bool {
+    !voice.is_empty()
+        && !voice.starts_with('-')
+        && voice.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+' | '/'))
+}
+
+fn unique_temp_wav() -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    std::env::temp_dir().join(format!(
+        "streamtts-piper-{}-{}.wav",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Drives locally installed espeak-ng and Piper binaries; no network needed
+#[derive(Clone)]
+pub struct LocalTts {
+    espeak_path: String,
+    piper_path: String,
+    piper_voices_dir: Option<PathBuf>,
+    timeout: Duration,
+}
+
+impl LocalTts {
+    /// `PIPER_VOICES_DIR` overrides `default_piper_voices_dir`
+    pub fn new(default_piper_voices_dir: Option<PathBuf>) -> Self {
+        let piper_voices_dir = std::env::var("PIPER_VOICES_DIR")
+            .ok()
+            .map(PathBuf::from)
+            .or(default_piper_voices_dir);
+        Self::with_paths(get_espeak_path(), get_piper_path(), piper_voices_dir, DEFAULT_TIMEOUT)
+    }
+
+    pub fn with_paths(espeak_path: String, piper_path: String, piper_voices_dir: Option<PathBuf>, timeout: Duration) -> Self {
+        LocalTts {
+            espeak_path,
+            piper_path,
+            piper_voices_dir,
+            timeout,
+        }
+    }
+
+    /// Voices from every installed engine; a missing engine just contributes nothing
+    pub async fn list_voices(&self) -> Result<Vec<LocalVoice>, LocalTtsError> {
+        let mut voices = match self.espeak_voices().await {
+            Ok(voices) => voices,
+            Err(LocalTtsError::BinaryNotFound { .. }) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        voices.extend(self.piper_voices());
+        Ok(voices)
+    }
+
+    async fn espeak_voices(&self) -> Result<Vec<LocalVoice>, LocalTtsError> {
+        let output = run_engine(&self.espeak_path, &["--voices"], "", self.timeout).await?;
+        Ok(parse_espeak_voices(&String::from_utf8_lossy(&output)))
+    }
+
+    /// Piper voices are `<name>.onnx` models with a `<name>.onnx.json` config next to them
+    fn piper_voices(&self) -> Vec<LocalVoice> {
+        let Some(dir) = self.piper_voices_dir.as_deref() else {
+            return Vec::new();
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+
+        let mut voices: Vec<LocalVoice> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "onnx"))
+            .filter_map(|model| {
+                let name = model.file_stem()?.to_str()?.to_string();
+                let config: serde_json::Value = std::fs::read_to_string(piper_config_path(&model))
+                    .ok()
+                    .and_then(|c| serde_json::from_str(&c).ok())?;
+                let language = config["language"]["code"]
+                    .as_str()
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| name.split('-').next().unwrap_or_default().to_string());
+                Some(LocalVoice {
+                    id: format!("piper:{}", name),
+                    name: config["dataset"].as_str().unwrap_or(&name).to_string(),
+                    language,
+                    engine: LocalEngine::Piper,
+                    gender: None,
+                })
+            })
+            .collect();
+        voices.sort_by(|a, b| a.id.cmp(&b.id));
+        voices
+    }
+
+    /// Synthesize `text` with `voice` (an id from [`LocalTts::list_voices`]) into WAV bytes
+    pub async fn synthesize(&self, text: &str, voice: &str) -> Result<Vec<u8>, LocalTtsError> {
+        if text.trim().is_empty() {
+            return Err(LocalTtsError::InvalidRequest { message: "Text is empty".to_string() });
+        }
+
+        match voice.split_once(':') {
+            Some(("espeak", name)) => self.synthesize_espeak(text, name, false).await,
+            Some(("piper", name)) => self.synthesize_piper(text, name).await,
+            _ => Err(LocalTtsError::VoiceNotFound { voice: voice.to_string() }),
+        }
+    }
+
+    /// Speak an SSML document; only espeak-ng voices understand markup
+    pub async fn synthesize_ssml(&self, ssml: &str, voice: &str) -> Result<Vec<u8>, LocalTtsError> {
+        match voice.split_once(':') {
+            Some(("espeak", name)) => self.synthesize_espeak(ssml, name, true).await,
+            _ => Err(LocalTtsError::InvalidRequest { message: format!("Voice {} does not support SSML", voice) }),
+        }
+    }
+
+    async fn synthesize_espeak(&self, text: &str, voice: &str, ssml: bool) -> Result<Vec<u8>, LocalTtsError> {
+        if !is_safe_espeak_voice(voice) {
+            return Err(LocalTtsError::VoiceNotFound { voice: format!("espeak:{}", voice) });
+        }
+        let mut args = vec!["-v", voice, "--stdout", "--stdin"];
+        if ssml {
+            args.push("-m");
+        }
+        let wav = run_engine(&self.espeak_path, &args, text, self.timeout).await?;
+        if wav.is_empty() {
+            return Err(LocalTtsError::Failed { message: "espeak-ng produced no audio".to_string() });
+        }
+        Ok(wav)
+    }
+
+    async fn synthesize_piper(&self, text: &str, voice: &str) -> Result<Vec<u8>, LocalTtsError> {
+        // Only models we discovered may be loaded, which also rules out path tricks
+        let model = self
+            .piper_voices_dir
+            .as_deref()
+            .map(|dir| dir.join(format!("{}.onnx", voice)))
+            .filter(|_| self.piper_voices().iter().any(|v| v.id == format!("piper:{}", voice)))
+            .ok_or_else(|| LocalTtsError::VoiceNotFound { voice: format!("piper:{}", voice) })?;
+
+        let output = unique_temp_wav();
+        let model_arg = model.to_string_lossy().to_string();
+        let output_arg = output.to_string_lossy().to_string();
+        let result = run_engine(
+            &self.piper_path,
+            &["--model", &model_arg, "--output_file", &output_arg],
+            text,
+            self.timeout,
+        )
+        .await;
+
+        let wav = result.and_then(|_| {
+            std::fs::read(&output).map_err(|e| LocalTtsError::Failed {
+                message: format!("Piper produced no audio: {}", e),
+            })
+        });
+        let _ = std::fs::remove_file(&output);
+        wav
+    }
+}
+
+fn piper_config_path(model: &Path) -> PathBuf {
+    let mut config = model.as_os_str().to_owned();
+    config.push(".json");
+    PathBuf::from(config)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    const ESPEAK_VOICES: &str = "Pty Language       Age/Gender VoiceName          File                 Other Languages
+ 5  en-gb           --/M      English_(Great_Britain) gmw/en               (en 2)
+ 5  ru              --/M      Russian            zle/ru
+ 5  ru-LV           --/F      Russian_(Latvia)   zle/ru-LV
+";
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("streamtts-local-tts-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn script(dir: &Path, name: &str, body: &str) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn parses_espeak_voice_table() {
+        let voices = parse_espeak_voices(ESPEAK_VOICES);
+        assert_eq!(voices.len(), 3);
+        assert_eq!(voices[1].id, "espeak:ru");
+        assert_eq!(voices[1].name, "Russian");
+        assert_eq!(voices[2].gender.as_deref(), Some("female"));
+        assert_eq!(voices[0].name, "English (Great Britain)");
+    }
+
+    #[tokio::test]
+    async fn drives_fake_engines() {
+        let dir = scratch_dir("engines");
+        let espeak = script(
+            &dir,
+            "espeak-ng",
+            &format!(
+                "if [ \"$1\" = \"--voices\" ]; then printf '{}'; exit 0; fi\ntext=$(cat)\nprintf \"RIFF:$2:$text\"",
+                ESPEAK_VOICES.replace('\n', "\\n")
+            ),
+        );
+        // Piper writes to the file named after --output_file
+        let piper = script(&dir, "piper", "text=$(cat)\nprintf \"RIFF:piper:$text\" > \"$4\"");
+        let models = dir.join("models");
+        std::fs::create_dir_all(&models).unwrap();
+        std::fs::write(models.join("ru_RU-irina-medium.onnx"), b"model").unwrap();
+        std::fs::write(
+            models.join("ru_RU-irina-medium.onnx.json"),
+            r#"{"dataset": "irina", "language": {"code": "ru_RU"}}"#,
+        )
+        .unwrap();
+
+        let tts = LocalTts::with_paths(espeak, piper, Some(models), Duration::from_secs(5));
+        let voices = tts.list_voices().await.unwrap();
+        assert_eq!(voices.len(), 4);
+        assert_eq!(voices[3].id, "piper:ru_RU-irina-medium");
+        assert_eq!(voices[3].language, "ru_RU");
+
+        let wav = tts.synthesize("привет", "espeak:ru").await.unwrap();
+        assert_eq!(String::from_utf8(wav).unwrap(), "RIFF:ru:привет");
+        let wav = tts.synthesize_ssml("<speak>привет</speak>", "espeak:ru").await.unwrap();
+        assert_eq!(String::from_utf8(wav).unwrap(), "RIFF:ru:<speak>привет</speak>");
+        assert!(matches!(
+            tts.synthesize_ssml("<speak>привет</speak>", "piper:ru_RU-irina-medium").await,
+            Err(LocalTtsError::InvalidRequest { .. })
+        ));
+        let wav = tts.synthesize("привет", "piper:ru_RU-irina-medium").await.unwrap();
+        assert_eq!(String::from_utf8(wav).unwrap(), "RIFF:piper:привет");
+
+        assert!(matches!(
+            tts.synthesize("hi", "piper:../models/ru_RU-irina-medium").await,
+            Err(LocalTtsError::VoiceNotFound { .. })
+        ));
+        assert!(matches!(tts.synthesize("hi", "espeak:--help").await, Err(LocalTtsError::VoiceNotFound { .. })));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn reports_missing_binary_failures_and_timeouts() {
+        let dir = scratch_dir("errors");
+        let missing = LocalTts::with_paths(
+            dir.join("no-such-espeak").to_string_lossy().to_string(),
+            "no-such-piper".to_string(),
+            None,
+            Duration::from_secs(5),
+        );
+        assert!(matches!(
+            missing.synthesize("hi", "espeak:ru").await,
+            Err(LocalTtsError::BinaryNotFound { .. })
+        ));
+        // A missing engine is not an error when listing voices
+        assert_eq!(missing.list_voices().await.unwrap(), Vec::new());
+
+        let failing = script(&dir, "failing", "echo 'unknown voice' >&2\nexit 1");
+        let tts = LocalTts::with_paths(failing, "piper".to_string(), None, Duration::from_secs(5));
+        assert!(matches!(
+            tts.synthesize("hi", "espeak:xx").await,
+            Err(LocalTtsError::Failed { message }) if message.contains("unknown voice")
+        ));
+
+        let slow = script(&dir, "slow", "sleep 5");
+        let tts = LocalTts::with_paths(slow, "piper".to_string(), None, Duration::from_millis(200));
+        assert!(matches!(tts.synthesize("hi", "espeak:ru").await, Err(LocalTtsError::Timeout { .. })));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
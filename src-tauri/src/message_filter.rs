@@ -0,0 +1,428 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+
+use crate::chat::ChatMessage;
+use crate::storage::{load_json, save_json};
+
+/// Prefix that asks for a message to be read regardless of its language
+const READ_PREFIX: &str = "!г ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterAction {
+    /// Read the message; no later rule is looked at
+    Allow,
+    /// Drop the message; no later rule is looked at
+    Deny,
+    /// Apply the rule's rewrite and carry on with the next rule
+    Rewrite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Script {
+    Cyrillic,
+    Latin,
+}
+
+impl Script {
+    fn contains(self, c: char) -> bool {
+        match self {
+            Script::Cyrillic => matches!(c, '\u{0400}'..='\u{04FF}'),
+            Script::Latin => c.is_ascii_alphabetic() || matches!(c, '\u{00C0}'..='\u{024F}'),
+        }
+    }
+}
+
+/// Rough guess at the language of `text`: "uk", "ru" or "en", `None` without enough letters
+fn detect_language(text: &str) -> Option<&'static str> {
+    let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+    let cyrillic = letters.iter().filter(|c| Script::Cyrillic.contains(**c)).count();
+    let latin = letters.iter().filter(|c| Script::Latin.contains(**c)).count();
+    if cyrillic == 0 && latin == 0 {
+        return None;
+    }
+    if cyrillic >= latin {
+        // Letters that only Ukrainian uses
+        let ukrainian = letters.iter().any(|c| matches!(c, 'і' | 'ї' | 'є' | 'ґ' | 'І' | 'Ї' | 'Є' | 'Ґ'));
+        Some(if ukrainian { "uk" } else { "ru" })
+    } else {
+        Some("en")
+    }
+}
+
+/// What a rule looks at; every condition that is set must hold for the rule to match
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FilterConditions {
+    /// Case-insensitive text prefix
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub regex: Option<String>,
+    /// At least one letter of this script
+    #[serde(default)]
+    pub script: Option<Script>,
+    /// Guessed language of the message: "ru", "uk" or "en"
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Any of these badges, e.g. "moderator", "vip", "subscriber", "member"
+    #[serde(default)]
+    pub badges: Vec<String>,
+    /// Any of these user names
+    #[serde(default)]
+    pub users: Vec<String>,
+    /// In characters
+    #[serde(default)]
+    pub min_length: Option<usize>,
+    #[serde(default)]
+    pub max_length: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FilterRewrite {
+    /// Remove the matched `prefix` condition from the text
+    #[serde(default)]
+    pub strip_prefix: bool,
+    /// Replace every match of this regex with `replacement` (`$1` refers to groups)
+    #[serde(default)]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub replacement: String,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilterRule {
+    /// Shown in decisions and logs
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub action: FilterAction,
+    #[serde(default)]
+    pub when: FilterConditions,
+    #[serde(default)]
+    pub rewrite: Option<FilterRewrite>,
+}
+
+/// Ordered rules; the first allow or deny wins, otherwise `default_action` applies
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilterConfig {
+    pub rules: Vec<FilterRule>,
+    pub default_action: FilterAction,
+}
+
+impl Default for FilterConfig {
+    /// Read messages with Cyrillic text or the `!г ` prefix, which is stripped
+    fn default() -> Self {
+        FilterConfig {
+            rules: vec![
+                FilterRule {
+                    name: "Read prefix".to_string(),
+                    enabled: true,
+                    action: FilterAction::Allow,
+                    when: FilterConditions { prefix: Some(READ_PREFIX.to_string()), ..Default::default() },
+                    rewrite: Some(FilterRewrite { strip_prefix: true, ..Default::default() }),
+                },
+                FilterRule {
+                    name: "Cyrillic".to_string(),
+                    enabled: true,
+                    action: FilterAction::Allow,
+                    when: FilterConditions { script: Some(Script::Cyrillic), ..Default::default() },
+                    rewrite: None,
+                },
+            ],
+            default_action: FilterAction::Deny,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "decision", rename_all = "snake_case")]
+pub enum FilterDecision {
+    /// `text` is what should be read, after rewrites
+    Allow { text: String, rule: Option<String> },
+    Deny { rule: Option<String> },
+}
+
+struct CompiledRule {
+    rule: FilterRule,
+    regex: Option<Regex>,
+    rewrite_regex: Option<Regex>,
+}
+
+fn compile_regex(pattern: &str, rule: &str) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| format!("Rule {:?} has an invalid regex: {}", rule, e))
+}
+
+fn compile(config: &FilterConfig) -> Result<Vec<CompiledRule>, String> {
+    if config.default_action == FilterAction::Rewrite {
+        return Err("The default action must be allow or deny".to_string());
+    }
+    config
+        .rules
+        .iter()
+        .map(|rule| {
+            let regex = rule.when.regex.as_deref().map(|p| compile_regex(p, &rule.name)).transpose()?;
+            let rewrite_regex = rule
+                .rewrite
+                .as_ref()
+                .and_then(|r| r.pattern.as_deref())
+                .map(|p| compile_regex(p, &rule.name))
+                .transpose()?;
+            Ok(CompiledRule { rule: rule.clone(), regex, rewrite_regex })
+        })
+        .collect()
+}
+
+fn has_prefix<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = text.get(..prefix.len())?;
+    head.to_lowercase().eq(&prefix.to_lowercase()).then(|| &text[prefix.len()..])
+}
+
+impl CompiledRule {
+    fn matches(&self, message: &ChatMessage, text: &str) -> bool {
+        let when = &self.rule.when;
+        if let Some(ref prefix) = when.prefix {
+            if has_prefix(text, prefix).is_none() {
+                return false;
+            }
+        }
+        if let Some(ref regex) = self.regex {
+            if !regex.is_match(text) {
+                return false;
+            }
+        }
+        if let Some(script) = when.script {
+            if !text.chars().any(|c| script.contains(c)) {
+                return false;
+            }
+        }
+        if let Some(ref language) = when.language {
+            if !detect_language(text).is_some_and(|detected| detected.eq_ignore_ascii_case(language)) {
+                return false;
+            }
+        }
+        if !when.badges.is_empty()
+            && !message.badges.iter().any(|b| when.badges.iter().any(|wanted| wanted.eq_ignore_ascii_case(&b.name)))
+        {
+            return false;
+        }
+        if !when.users.is_empty()
+            && !when.users.iter().any(|user| {
+                user.eq_ignore_ascii_case(&message.user_name) || user.to_lowercase() == message.display_name.to_lowercase()
+            })
+        {
+            return false;
+        }
+        let length = text.chars().count();
+        when.min_length.map_or(true, |min| length >= min) && when.max_length.map_or(true, |max| length <= max)
+    }
+
+    fn rewrite(&self, text: String) -> String {
+        let Some(ref rewrite) = self.rule.rewrite else {
+            return text;
+        };
+        let mut text = text;
+        if rewrite.strip_prefix {
+            if let Some(rest) = self.rule.when.prefix.as_deref().and_then(|prefix| has_prefix(&text, prefix)) {
+                text = rest.trim_start().to_string();
+            }
+        }
+        if let Some(ref regex) = self.rewrite_regex {
+            text = regex.replace_all(&text, rewrite.replacement.as_str()).into_owned();
+        }
+        text
+    }
+}
+
+/// Decides which chat messages are read aloud, persisted in the app data directory
+pub struct MessageFilter {
+    path: Option<PathBuf>,
+    state: RwLock<(FilterConfig, Vec<CompiledRule>)>,
+}
+
+impl MessageFilter {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let stored = path.as_deref().and_then(load_json::<FilterConfig>);
+        let (config, rules) = match stored.map(|config| compile(&config).map(|rules| (config, rules))) {
+            Some(Ok(loaded)) => loaded,
+            Some(Err(e)) => {
+                log::warn!("Ignoring stored message filter: {}", e);
+                Self::defaults()
+            }
+            None => Self::defaults(),
+        };
+        MessageFilter { path, state: RwLock::new((config, rules)) }
+    }
+
+    fn defaults() -> (FilterConfig, Vec<CompiledRule>) {
+        let config = FilterConfig::default();
+        let rules = compile(&config).expect("default filter rules are valid");
+        (config, rules)
+    }
+
+    pub async fn config(&self) -> FilterConfig {
+        self.state.read().await.0.clone()
+    }
+
+    pub async fn set_config(&self, config: FilterConfig) -> Result<(), String> {
+        let rules = compile(&config)?;
+        if let Some(ref path) = self.path {
+            save_json(path, &config)?;
+        }
+        *self.state.write().await = (config, rules);
+        Ok(())
+    }
+
+    pub async fn evaluate(&self, message: &ChatMessage) -> FilterDecision {
+        let state = self.state.read().await;
+        let (config, rules) = &*state;
+        let mut text = message.text.trim().to_string();
+
+        for compiled in rules.iter().filter(|compiled| compiled.rule.enabled) {
+            if !compiled.matches(message, &text) {
+                continue;
+            }
+            let rule = Some(compiled.rule.name.clone());
+            match compiled.rule.action {
+                FilterAction::Rewrite => text = compiled.rewrite(text),
+                FilterAction::Allow => return FilterDecision::Allow { text: compiled.rewrite(text), rule },
+                FilterAction::Deny => return FilterDecision::Deny { rule },
+            }
+        }
+
+        match config.default_action {
+            FilterAction::Deny => FilterDecision::Deny { rule: None },
+            _ => FilterDecision::Allow { text, rule: None },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat::ChatBadge;
+
+    fn message(user_name: &str, badges: &[&str], text: &str) -> ChatMessage {
+        ChatMessage {
+            platform: "twitch".to_string(),
+            user_name: user_name.to_string(),
+            display_name: user_name.to_string(),
+            text: text.to_string(),
+            badges: badges
+                .iter()
+                .map(|name| ChatBadge { name: name.to_string(), version: "1".to_string() })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn allow(text: &str) -> Option<String> {
+        Some(text.to_string())
+    }
+
+    async fn run_cases(filter: &MessageFilter, cases: &[(ChatMessage, Option<String>)]) {
+        for (message, expected) in cases {
+            let decision = match filter.evaluate(message).await {
+                FilterDecision::Allow { text, .. } => Some(text),
+                FilterDecision::Deny { .. } => None,
+            };
+            assert_eq!(&decision, expected, "{:?}", message.text);
+        }
+    }
+
+    #[tokio::test]
+    async fn default_rules_match_the_old_behaviour() {
+        let filter = MessageFilter::load(None);
+        let cases = [
+            (message("a", &[], "Привет всем"), allow("Привет всем")),
+            (message("a", &[], "hello there"), None),
+            (message("a", &[], "!г hello there"), allow("hello there")),
+            (message("a", &[], "!Г   hello"), allow("hello")),
+            // Contains a Cyrillic letter, so the prefix rule is not needed
+            (message("a", &[], "!гhello"), allow("!гhello")),
+            (message("a", &[], "gg ez лол"), allow("gg ez лол")),
+            (message("a", &[], "12345"), None),
+        ];
+        run_cases(&filter, &cases).await;
+    }
+
+    #[tokio::test]
+    async fn ordered_rules_with_every_condition() {
+        let filter = MessageFilter::load(None);
+        let rule = |name: &str, action, when| FilterRule { name: name.to_string(), enabled: true, action, when, rewrite: None };
+        let config = FilterConfig {
+            rules: vec![
+                rule("blocked users", FilterAction::Deny, FilterConditions {
+                    users: vec!["Nightbot".to_string(), "StreamElements".to_string()],
+                    ..Default::default()
+                }),
+                rule("commands", FilterAction::Deny, FilterConditions { prefix: Some("!".to_string()), ..Default::default() }),
+                FilterRule {
+                    rewrite: Some(FilterRewrite {
+                        pattern: Some(r"https?://\S+".to_string()),
+                        replacement: "ссылка".to_string(),
+                        ..Default::default()
+                    }),
+                    ..rule("links", FilterAction::Rewrite, FilterConditions { regex: Some("https?://".to_string()), ..Default::default() })
+                },
+                rule("too long", FilterAction::Deny, FilterConditions { min_length: Some(40), ..Default::default() }),
+                rule("mods", FilterAction::Allow, FilterConditions {
+                    badges: vec!["moderator".to_string(), "broadcaster".to_string()],
+                    ..Default::default()
+                }),
+                rule("short", FilterAction::Deny, FilterConditions { max_length: Some(2), ..Default::default() }),
+                rule("ukrainian", FilterAction::Allow, FilterConditions { language: Some("uk".to_string()), ..Default::default() }),
+                rule("english subs", FilterAction::Allow, FilterConditions {
+                    script: Some(Script::Latin),
+                    badges: vec!["subscriber".to_string()],
+                    ..Default::default()
+                }),
+                FilterRule { enabled: false, ..rule("disabled", FilterAction::Allow, FilterConditions::default()) },
+            ],
+            default_action: FilterAction::Deny,
+        };
+        filter.set_config(config).await.unwrap();
+
+        let cases = [
+            (message("nightbot", &["moderator"], "hello"), None),
+            (message("viewer", &["moderator"], "!timeout someone"), None),
+            (message("viewer", &["moderator"], "see https://example.com ok"), allow("see ссылка ok")),
+            (message("viewer", &["moderator"], &"a".repeat(40)), None),
+            (message("viewer", &["moderator"], "english is fine"), allow("english is fine")),
+            (message("viewer", &[], "ок"), None),
+            (message("viewer", &[], "привіт, як справи"), allow("привіт, як справи")),
+            (message("viewer", &[], "привет, как дела"), None),
+            (message("viewer", &["subscriber"], "hello chat"), allow("hello chat")),
+            (message("viewer", &["vip"], "hello chat"), None),
+        ];
+        run_cases(&filter, &cases).await;
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_config_and_persists() {
+        let dir = std::env::temp_dir().join(format!("streamtts-filter-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = crate::storage::data_file(&dir, "message_filter.json");
+        let filter = MessageFilter::load(Some(path.clone()));
+
+        let mut config = FilterConfig::default();
+        config.rules[0].when.regex = Some("(".to_string());
+        assert!(filter.set_config(config).await.is_err());
+        let rewrite_default = FilterConfig { default_action: FilterAction::Rewrite, ..FilterConfig::default() };
+        assert!(filter.set_config(rewrite_default).await.is_err());
+
+        let allow_all = FilterConfig { rules: Vec::new(), default_action: FilterAction::Allow };
+        filter.set_config(allow_all.clone()).await.unwrap();
+        assert_eq!(MessageFilter::load(Some(path)).config().await, allow_all);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
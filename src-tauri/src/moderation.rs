@@ -0,0 +1,297 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::chat::ChatMessage;
+use crate::storage::JsonStore;
+use crate::tts_queue::TtsQueue;
+
+/// Chat commands handled here; the permission policy needs at least a moderator for them unless told otherwise
+pub const MODERATION_COMMANDS: &[&str] = &["!skip", "!stop", "!ttsmute", "!ttsunmute"];
+const DEFAULT_MUTE_MINUTES: u32 = 10;
+/// Oldest audit entries are dropped past this
+const MAX_AUDIT_ENTRIES: usize = 500;
+
+/// Muted viewers by `platform:user_name`, with when the mute ends
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MuteList {
+    #[serde(default)]
+    pub mutes: HashMap<String, DateTime<Utc>>,
+}
+
+pub type MuteStore = JsonStore<MuteList>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ModerationAction {
+    /// `skipped` is the viewer whose message was playing
+    Skip { skipped: Option<String> },
+    /// Cleared the queue, and paused it when `pause_minutes` is set
+    Stop { pause_minutes: Option<u32> },
+    Mute { user_name: String, until: DateTime<Utc> },
+    Unmute { user_name: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: DateTime<Utc>,
+    pub platform: String,
+    pub moderator: String,
+    #[serde(flatten)]
+    pub action: ModerationAction,
+}
+
+pub type AuditStore = JsonStore<Vec<AuditEntry>>;
+
+fn viewer_key(platform: &str, user_name: &str) -> String {
+    format!("{}:{}", platform, user_name.trim_start_matches('@').to_lowercase())
+}
+
+fn parse_minutes(argument: Option<&str>) -> Result<Option<u32>, String> {
+    match argument {
+        None => Ok(None),
+        Some(minutes) => match minutes.parse::<u32>() {
+            Ok(minutes) if minutes > 0 => Ok(Some(minutes)),
+            _ => Err(format!("{:?} is not a number of minutes", minutes)),
+        },
+    }
+}
+
+enum Command<'a> {
+    Skip,
+    Stop { pause_minutes: Option<u32> },
+    Mute { user_name: &'a str, minutes: u32 },
+    Unmute { user_name: &'a str },
+}
+
+/// `None` when `text` is no moderation command
+fn parse_command(text: &str) -> Option<Result<Command<'_>, String>> {
+    let mut words = text.split_whitespace();
+    let command = words.next()?.to_lowercase();
+    let first = words.next();
+    let second = words.next();
+    let parsed = match command.as_str() {
+        "!skip" => Ok(Command::Skip),
+        "!stop" => parse_minutes(first).map(|pause_minutes| Command::Stop { pause_minutes }),
+        "!ttsmute" => match first {
+            Some(user_name) => parse_minutes(second)
+                .map(|minutes| Command::Mute { user_name, minutes: minutes.unwrap_or(DEFAULT_MUTE_MINUTES) }),
+            None => Err("Usage: !ttsmute <user> [minutes]".to_string()),
+        },
+        "!ttsunmute" => first.map(|user_name| Command::Unmute { user_name }).ok_or_else(|| "Usage: !ttsunmute <user>".to_string()),
+        _ => return None,
+    };
+    Some(parsed)
+}
+
+/// Moderator commands from chat: skip, stop, timed pause and mutes, each written to the audit log
+pub struct Moderation {
+    queue: Arc<Mutex<TtsQueue>>,
+    mutes: Arc<MuteStore>,
+    audit: Arc<AuditStore>,
+    /// When a timed `!stop` ends; a newer pause replaces it, so only the latest timer resumes the queue
+    pause_until: Mutex<Option<DateTime<Utc>>>,
+    /// Serializes read-modify-write of the mute list and the audit log
+    updates: Mutex<()>,
+}
+
+impl Moderation {
+    pub fn new(queue: Arc<Mutex<TtsQueue>>, mutes: Arc<MuteStore>, audit: Arc<AuditStore>) -> Self {
+        Moderation { queue, mutes, audit, pause_until: Mutex::new(None), updates: Mutex::new(()) }
+    }
+
+    /// Active mutes; expired ones are left out
+    pub async fn mutes(&self) -> HashMap<String, DateTime<Utc>> {
+        let now = Utc::now();
+        let mut mutes = self.mutes.get().await.unwrap_or_default().mutes;
+        mutes.retain(|_, until| *until > now);
+        mutes
+    }
+
+    /// When the viewer's mute ends, `None` when they are not muted
+    pub async fn muted_until(&self, platform: &str, user_name: &str) -> Option<DateTime<Utc>> {
+        self.mutes().await.get(&viewer_key(platform, user_name)).copied()
+    }
+
+    pub async fn audit_log(&self) -> Vec<AuditEntry> {
+        self.audit.get().await.unwrap_or_default()
+    }
+
+    async fn set_mute(&self, key: String, until: Option<DateTime<Utc>>) -> Result<(), String> {
+        let _guard = self.updates.lock().await;
+        let mut mutes = self.mutes().await;
+        match until {
+            Some(until) => mutes.insert(key, until),
+            None => mutes.remove(&key),
+        };
+        self.mutes.set(MuteList { mutes }).await
+    }
+
+    async fn record(&self, message: &ChatMessage, action: ModerationAction) -> Result<AuditEntry, String> {
+        let entry = AuditEntry {
+            at: Utc::now(),
+            platform: message.platform.clone(),
+            moderator: message.user_name.clone(),
+            action,
+        };
+        let _guard = self.updates.lock().await;
+        let mut log = self.audit_log().await;
+        log.push(entry.clone());
+        let overflow = log.len().saturating_sub(MAX_AUDIT_ENTRIES);
+        log.drain(..overflow);
+        self.audit.set(log).await?;
+        Ok(entry)
+    }
+
+    /// Pause the queue and resume it after `duration`, unless another pause has taken over since
+    async fn pause_for(self: &Arc<Self>, duration: Duration) {
+        let until = Utc::now() + chrono::Duration::from_std(duration).unwrap_or_default();
+        *self.pause_until.lock().await = Some(until);
+        self.queue.lock().await.pause();
+
+        let moderation = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            let mut pause_until = moderation.pause_until.lock().await;
+            if *pause_until == Some(until) {
+                *pause_until = None;
+                moderation.queue.lock().await.resume();
+            }
+        });
+    }
+
+    /// Carry out a moderation command from chat; `None` when the message is not one.
+    ///
+    /// Whether the sender may use it is the permission policy's call, made before this.
+    pub async fn handle_command(self: &Arc<Self>, message: &ChatMessage) -> Option<Result<AuditEntry, String>> {
+        let command = match parse_command(&message.text)? {
+            Ok(command) => command,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let action = match command {
+            Command::Skip => {
+                let skipped = self.queue.lock().await.skip();
+                ModerationAction::Skip { skipped: skipped.map(|item| item.user_name) }
+            }
+            Command::Stop { pause_minutes } => {
+                self.queue.lock().await.clear();
+                if let Some(minutes) = pause_minutes {
+                    self.pause_for(Duration::from_secs(minutes as u64 * 60)).await;
+                }
+                ModerationAction::Stop { pause_minutes }
+            }
+            Command::Mute { user_name, minutes } => {
+                let until = Utc::now() + chrono::Duration::minutes(minutes as i64);
+                if let Err(e) = self.set_mute(viewer_key(&message.platform, user_name), Some(until)).await {
+                    return Some(Err(e));
+                }
+                ModerationAction::Mute { user_name: user_name.trim_start_matches('@').to_lowercase(), until }
+            }
+            Command::Unmute { user_name } => {
+                if let Err(e) = self.set_mute(viewer_key(&message.platform, user_name), None).await {
+                    return Some(Err(e));
+                }
+                ModerationAction::Unmute { user_name: user_name.trim_start_matches('@').to_lowercase() }
+            }
+        };
+        Some(self.record(message, action).await)
+    }
+
+    /// Lift a mute from the app rather than from chat
+    pub async fn unmute(&self, platform: &str, user_name: &str) -> Result<(), String> {
+        self.set_mute(viewer_key(platform, user_name), None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tts_queue::{NewQueueItem, QueuePriority};
+    use tokio::sync::broadcast;
+
+    fn moderation() -> Arc<Moderation> {
+        let (events, _) = broadcast::channel(64);
+        Arc::new(Moderation::new(
+            Arc::new(Mutex::new(TtsQueue::new(events))),
+            Arc::new(MuteStore::load(None)),
+            Arc::new(AuditStore::load(None)),
+        ))
+    }
+
+    fn chat(text: &str) -> ChatMessage {
+        ChatMessage {
+            platform: "twitch".to_string(),
+            user_name: "a_mod".to_string(),
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn enqueue(moderation: &Moderation, user_name: &str) {
+        moderation.queue.lock().await.enqueue(NewQueueItem {
+            platform: "twitch".to_string(),
+            user_name: user_name.to_string(),
+            text: "hello".to_string(),
+            priority: QueuePriority::Chat,
+            speech: None,
+            voice: None,
+        });
+    }
+
+    #[tokio::test]
+    async fn skip_stop_and_mutes_are_audited() {
+        let moderation = moderation();
+        assert!(moderation.handle_command(&chat("skip this")).await.is_none());
+
+        enqueue(&moderation, "spammer").await;
+        enqueue(&moderation, "viewer").await;
+        let entry = moderation.handle_command(&chat("!SKIP")).await.unwrap().unwrap();
+        assert_eq!(entry.action, ModerationAction::Skip { skipped: Some("spammer".to_string()) });
+        assert_eq!(entry.moderator, "a_mod");
+        assert_eq!(moderation.queue.lock().await.snapshot().current.unwrap().user_name, "viewer");
+
+        let entry = moderation.handle_command(&chat("!stop")).await.unwrap().unwrap();
+        assert_eq!(entry.action, ModerationAction::Stop { pause_minutes: None });
+        let snapshot = moderation.queue.lock().await.snapshot();
+        assert!(snapshot.current.is_none() && snapshot.pending.is_empty() && !snapshot.paused);
+
+        let entry = moderation.handle_command(&chat("!ttsmute @Spammer 5")).await.unwrap().unwrap();
+        let ModerationAction::Mute { ref user_name, until } = entry.action else { panic!("{:?}", entry.action) };
+        assert_eq!(user_name, "spammer");
+        assert_eq!(moderation.muted_until("twitch", "spammer").await, Some(until));
+        assert_eq!(moderation.muted_until("youtube", "spammer").await, None);
+        assert!((until - Utc::now()).num_minutes() >= 4);
+
+        moderation.handle_command(&chat("!ttsunmute spammer")).await.unwrap().unwrap();
+        assert_eq!(moderation.muted_until("twitch", "spammer").await, None);
+
+        assert!(moderation.handle_command(&chat("!ttsmute")).await.unwrap().is_err());
+        assert!(moderation.handle_command(&chat("!stop soon")).await.unwrap().is_err());
+        assert!(moderation.handle_command(&chat("!ttsmute bob 0")).await.unwrap().is_err());
+
+        let logged: Vec<String> = moderation
+            .audit_log()
+            .await
+            .iter()
+            .map(|entry| serde_json::to_value(entry).unwrap()["action"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(logged, ["skip", "stop", "mute", "unmute"]);
+    }
+
+    #[tokio::test]
+    async fn timed_pause_resumes_only_for_the_latest_stop() {
+        let moderation = moderation();
+        moderation.pause_for(Duration::from_millis(50)).await;
+        moderation.pause_for(Duration::from_millis(200)).await;
+        assert!(moderation.queue.lock().await.snapshot().paused);
+
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert!(moderation.queue.lock().await.snapshot().paused, "the first timer was superseded");
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(!moderation.queue.lock().await.snapshot().paused);
+    }
+}
@@ -0,0 +1,770 @@
+//! Rewrites chat text so a Russian voice reads it naturally: numbers, amounts and
+//! percentages become words, links and emoji are replaced and stretched words are collapsed.
+
+const URL_REPLACEMENT: &str = "ссылка";
+/// Longer digit runs (ids, phone numbers) are read digit by digit
+const MAX_NUMBER_DIGITS: usize = 15;
+
+const KNOWN_TLDS: &[&str] = &[
+    "com", "net", "org", "ru", "su", "рф", "ua", "by", "kz", "tv", "gg", "io", "be", "me", "ly", "co", "uk", "de",
+    "info", "xyz",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Gender {
+    Masculine,
+    Feminine,
+    Neuter,
+}
+
+/// Which noun form follows a number: 1 and 21 take `One`, 2-4 and 22-24 take `Few`, the rest `Many`.
+/// `Few` doubles as the genitive singular used after fractions ("2,5 процента").
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Agreement {
+    One,
+    Few,
+    Many,
+}
+
+fn agreement(n: u64) -> Agreement {
+    match (n % 10, n % 100) {
+        (_, 11..=14) => Agreement::Many,
+        (1, _) => Agreement::One,
+        (2..=4, _) => Agreement::Few,
+        _ => Agreement::Many,
+    }
+}
+
+struct Noun {
+    forms: [&'static str; 3],
+    gender: Gender,
+}
+
+impl Noun {
+    fn form(&self, agreement: Agreement) -> &'static str {
+        match agreement {
+            Agreement::One => self.forms[0],
+            Agreement::Few => self.forms[1],
+            Agreement::Many => self.forms[2],
+        }
+    }
+}
+
+const fn noun(one: &'static str, few: &'static str, many: &'static str, gender: Gender) -> Noun {
+    Noun { forms: [one, few, many], gender }
+}
+
+const THOUSAND: Noun = noun("тысяча", "тысячи", "тысяч", Gender::Feminine);
+const MILLION: Noun = noun("миллион", "миллиона", "миллионов", Gender::Masculine);
+const BILLION: Noun = noun("миллиард", "миллиарда", "миллиардов", Gender::Masculine);
+const TRILLION: Noun = noun("триллион", "триллиона", "триллионов", Gender::Masculine);
+const SCALES: [(u64, &Noun); 4] = [
+    (1_000_000_000_000, &TRILLION),
+    (1_000_000_000, &BILLION),
+    (1_000_000, &MILLION),
+    (1_000, &THOUSAND),
+];
+
+const PERCENT: Noun = noun("процент", "процента", "процентов", Gender::Masculine);
+const WHOLE: Noun = noun("целая", "целых", "целых", Gender::Feminine);
+/// Indexed by the number of fraction digits
+const FRACTIONS: [Noun; 3] = [
+    noun("десятая", "десятых", "десятых", Gender::Feminine),
+    noun("сотая", "сотых", "сотых", Gender::Feminine),
+    noun("тысячная", "тысячных", "тысячных", Gender::Feminine),
+];
+
+struct Currency {
+    /// Lowercase codes and abbreviations that may follow a number, e.g. "usd" or "руб"
+    codes: &'static [&'static str],
+    symbols: &'static [char],
+    major: Noun,
+    minor: Option<Noun>,
+}
+
+const KOPECK: Noun = noun("копейка", "копейки", "копеек", Gender::Feminine);
+
+const CURRENCIES: &[Currency] = &[
+    Currency {
+        codes: &["rub", "rur", "руб", "р"],
+        symbols: &['₽'],
+        major: noun("рубль", "рубля", "рублей", Gender::Masculine),
+        minor: Some(KOPECK),
+    },
+    Currency {
+        codes: &["usd"],
+        symbols: &['$'],
+        major: noun("доллар", "доллара", "долларов", Gender::Masculine),
+        minor: Some(noun("цент", "цента", "центов", Gender::Masculine)),
+    },
+    Currency {
+        codes: &["eur"],
+        symbols: &['€'],
+        major: noun("евро", "евро", "евро", Gender::Masculine),
+        minor: Some(noun("евроцент", "евроцента", "евроцентов", Gender::Masculine)),
+    },
+    Currency {
+        codes: &["gbp"],
+        symbols: &['£'],
+        major: noun("фунт", "фунта", "фунтов", Gender::Masculine),
+        minor: Some(noun("пенс", "пенса", "пенсов", Gender::Masculine)),
+    },
+    Currency {
+        codes: &["uah", "грн"],
+        symbols: &['₴'],
+        major: noun("гривна", "гривны", "гривен", Gender::Feminine),
+        minor: Some(KOPECK),
+    },
+    Currency {
+        codes: &["kzt"],
+        symbols: &['₸'],
+        major: noun("тенге", "тенге", "тенге", Gender::Masculine),
+        minor: None,
+    },
+    Currency {
+        codes: &["jpy"],
+        symbols: &['¥'],
+        major: noun("иена", "иены", "иен", Gender::Feminine),
+        minor: None,
+    },
+    // Twitch cheers
+    Currency {
+        codes: &["bits"],
+        symbols: &[],
+        major: noun("бит", "бита", "битов", Gender::Masculine),
+        minor: None,
+    },
+];
+
+fn currency_for_code(code: &str) -> Option<&'static Currency> {
+    let code = code.to_lowercase();
+    CURRENCIES.iter().find(|currency| currency.codes.contains(&code.as_str()))
+}
+
+fn currency_for_symbol(symbol: char) -> Option<&'static Currency> {
+    CURRENCIES.iter().find(|currency| currency.symbols.contains(&symbol))
+}
+
+/// Stems of common nouns counted in chat that are not masculine ("1 минута", "2 подписки")
+const FEMININE_STEMS: &[&str] = &["минут", "секунд", "недел", "копе", "штук", "подписк", "тысяч", "гривн", "ставк", "попытк"];
+const NEUTER_STEMS: &[&str] = &["сообщени", "очк"];
+
+const UNITS: [&str; 10] = ["", "один", "два", "три", "четыре", "пять", "шесть", "семь", "восемь", "девять"];
+const TEENS: [&str; 10] = [
+    "десять", "одиннадцать", "двенадцать", "тринадцать", "четырнадцать", "пятнадцать", "шестнадцать", "семнадцать",
+    "восемнадцать", "девятнадцать",
+];
+const TENS: [&str; 10] = [
+    "", "", "двадцать", "тридцать", "сорок", "пятьдесят", "шестьдесят", "семьдесят", "восемьдесят", "девяносто",
+];
+const HUNDREDS: [&str; 10] = [
+    "", "сто", "двести", "триста", "четыреста", "пятьсот", "шестьсот", "семьсот", "восемьсот", "девятьсот",
+];
+const DIGITS: [&str; 10] = ["ноль", "один", "два", "три", "четыре", "пять", "шесть", "семь", "восемь", "девять"];
+
+/// Words for 1..=999
+fn push_hundreds(words: &mut Vec<&'static str>, n: u64, gender: Gender) {
+    let hundreds = (n / 100) as usize;
+    if hundreds > 0 {
+        words.push(HUNDREDS[hundreds]);
+    }
+    let rest = (n % 100) as usize;
+    if (10..20).contains(&rest) {
+        words.push(TEENS[rest - 10]);
+        return;
+    }
+    if rest >= 20 {
+        words.push(TENS[rest / 10]);
+    }
+    let unit = rest % 10;
+    if unit > 0 {
+        words.push(match (unit, gender) {
+            (1, Gender::Feminine) => "одна",
+            (1, Gender::Neuter) => "одно",
+            (2, Gender::Feminine) => "две",
+            _ => UNITS[unit],
+        });
+    }
+}
+
+/// Cardinal number in the nominative case; `gender` agrees with the counted noun
+fn number_to_words(n: u64, gender: Gender) -> String {
+    if n == 0 {
+        return DIGITS[0].to_string();
+    }
+    let mut words = Vec::new();
+    let mut rest = n;
+    for (scale, noun) in SCALES {
+        let chunk = rest / scale;
+        rest %= scale;
+        if chunk == 0 {
+            continue;
+        }
+        // "тысяча двести", not "одна тысяча двести"
+        if !(chunk == 1 && scale == 1_000 && words.is_empty()) {
+            push_hundreds(&mut words, chunk, noun.gender);
+        }
+        words.push(noun.form(agreement(chunk)));
+    }
+    if rest > 0 {
+        push_hundreds(&mut words, rest, gender);
+    }
+    words.join(" ")
+}
+
+fn spell_digits(digits: &str) -> String {
+    digits
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .map(|d| DIGITS[d as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A number as written in the text: "42", "2,5", "3.14"
+struct Number {
+    integer: String,
+    fraction: Option<String>,
+    /// Index just past the number
+    end: usize,
+}
+
+impl Number {
+    /// `None` for values that are read digit by digit instead
+    fn integer_value(&self) -> Option<u64> {
+        let spell = self.integer.len() > MAX_NUMBER_DIGITS || (self.integer.len() > 1 && self.integer.starts_with('0'));
+        if spell {
+            None
+        } else {
+            self.integer.parse().ok()
+        }
+    }
+
+    /// "5.00" is read as a whole amount
+    fn without_zero_fraction(mut self) -> Self {
+        if self.fraction.as_deref().is_some_and(|fraction| fraction.chars().all(|c| c == '0')) {
+            self.fraction = None;
+        }
+        self
+    }
+}
+
+fn digit_run(chars: &[char], start: usize) -> usize {
+    let mut end = start;
+    while end < chars.len() && chars[end].is_ascii_digit() {
+        end += 1;
+    }
+    end
+}
+
+fn parse_number(chars: &[char], start: usize) -> Option<Number> {
+    let end = digit_run(chars, start);
+    if end == start {
+        return None;
+    }
+    let integer: String = chars[start..end].iter().collect();
+
+    // The "2" in "1.2.3" is not the start of a fraction either
+    let after_separator =
+        start >= 2 && matches!(chars[start - 1], '.' | ',') && chars[start - 2].is_ascii_digit();
+    let has_fraction = !after_separator
+        && matches!(chars.get(end), Some('.') | Some(','))
+        && chars.get(end + 1).is_some_and(|c| c.is_ascii_digit());
+    if has_fraction {
+        let fraction_end = digit_run(chars, end + 1);
+        // "1.2.3" and "17.10.2026" are versions and dates, not fractions
+        let dotted = matches!(chars.get(fraction_end), Some('.') | Some(','))
+            && chars.get(fraction_end + 1).is_some_and(|c| c.is_ascii_digit());
+        if !dotted {
+            return Some(Number {
+                integer,
+                fraction: Some(chars[end + 1..fraction_end].iter().collect()),
+                end: fraction_end,
+            });
+        }
+    }
+    Some(Number { integer, fraction: None, end })
+}
+
+/// Read a number whose noun has `gender`, returning the words and the noun form to follow
+fn read_number(number: &Number, gender: Gender) -> (String, Agreement) {
+    let Some(integer) = number.integer_value() else {
+        let mut words = spell_digits(&number.integer);
+        if let Some(fraction) = &number.fraction {
+            words = format!("{} запятая {}", words, spell_digits(fraction));
+        }
+        return (words, Agreement::Many);
+    };
+
+    match &number.fraction {
+        None => (number_to_words(integer, gender), agreement(integer)),
+        Some(fraction) if fraction.len() <= FRACTIONS.len() => {
+            let fraction_value: u64 = fraction.parse().unwrap_or(0);
+            let words = format!(
+                "{} {} {} {}",
+                number_to_words(integer, Gender::Feminine),
+                WHOLE.form(agreement(integer)),
+                number_to_words(fraction_value, Gender::Feminine),
+                FRACTIONS[fraction.len() - 1].form(agreement(fraction_value)),
+            );
+            (words, Agreement::Few)
+        }
+        Some(fraction) => (
+            format!("{} запятая {}", number_to_words(integer, Gender::Masculine), spell_digits(fraction)),
+            Agreement::Few,
+        ),
+    }
+}
+
+fn counted(number: &Number, noun: &Noun) -> String {
+    let (words, agreement) = read_number(number, noun.gender);
+    format!("{} {}", words, noun.form(agreement))
+}
+
+fn amount_words(number: Number, currency: &Currency) -> String {
+    let number = &number.without_zero_fraction();
+    let Some(minor) = &currency.minor else {
+        return counted(number, &currency.major);
+    };
+    let (Some(integer), Some(fraction)) = (number.integer_value(), &number.fraction) else {
+        return counted(number, &currency.major);
+    };
+    if fraction.len() > 2 {
+        return counted(number, &currency.major);
+    }
+
+    // "5.5" is five and fifty hundredths
+    let cents: u64 = format!("{:0<2}", fraction).parse().unwrap_or(0);
+    let major = format!(
+        "{} {}",
+        number_to_words(integer, currency.major.gender),
+        currency.major.form(agreement(integer))
+    );
+    let minor = format!("{} {}", number_to_words(cents, minor.gender), minor.form(agreement(cents)));
+    match (integer, cents) {
+        (_, 0) => major,
+        (0, _) => minor,
+        _ => format!("{} {}", major, minor),
+    }
+}
+
+fn gender_of_word(word: &str) -> Gender {
+    let word = word.to_lowercase();
+    if FEMININE_STEMS.iter().any(|stem| word.starts_with(stem)) {
+        Gender::Feminine
+    } else if NEUTER_STEMS.iter().any(|stem| word.starts_with(stem)) {
+        Gender::Neuter
+    } else {
+        Gender::Masculine
+    }
+}
+
+fn word_at(chars: &[char], start: usize) -> (String, usize) {
+    let mut end = start;
+    while end < chars.len() && chars[end].is_alphabetic() {
+        end += 1;
+    }
+    (chars[start..end].iter().collect(), end)
+}
+
+/// What follows a number and changes how it is read
+enum Suffix {
+    Percent,
+    Currency(&'static Currency),
+}
+
+/// Look for "%", a currency symbol or a currency code right after a number, optionally
+/// after one space; returns the suffix and the index past it
+fn parse_suffix(chars: &[char], start: usize) -> Option<(Suffix, usize)> {
+    let start = if chars.get(start) == Some(&' ') { start + 1 } else { start };
+    let c = *chars.get(start)?;
+    if c == '%' {
+        return Some((Suffix::Percent, start + 1));
+    }
+    if let Some(currency) = currency_for_symbol(c) {
+        return Some((Suffix::Currency(currency), start + 1));
+    }
+
+    let (word, mut end) = word_at(chars, start);
+    let currency = currency_for_code(&word)?;
+    // "100 руб." - the abbreviation dot is not a sentence end
+    if chars.get(end) == Some(&'.') && !word.is_ascii() {
+        end += 1;
+    }
+    Some((Suffix::Currency(currency), end))
+}
+
+/// Append `words` to `out`, keeping them apart from neighbouring letters and digits
+fn push_words(out: &mut String, words: &str, next: Option<&char>) {
+    if out.chars().last().is_some_and(|c| c.is_alphanumeric()) {
+        out.push(' ');
+    }
+    out.push_str(words);
+    if next.is_some_and(|c| c.is_alphanumeric()) {
+        out.push(' ');
+    }
+}
+
+fn expand_numbers(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next_is_digit = chars.get(i + 1).is_some_and(|c| c.is_ascii_digit());
+
+        // "$5", "€10"
+        if let Some(currency) = currency_for_symbol(c).filter(|_| next_is_digit) {
+            let number = parse_number(&chars, i + 1).expect("a digit follows");
+            i = number.end;
+            push_words(&mut out, &amount_words(number, currency), chars.get(i));
+            continue;
+        }
+
+        if c == '-' && next_is_digit && (i == 0 || chars[i - 1].is_whitespace()) {
+            push_words(&mut out, "минус ", None);
+            i += 1;
+            continue;
+        }
+
+        let Some(number) = parse_number(&chars, i) else {
+            out.push(c);
+            i += 1;
+            continue;
+        };
+
+        let words = match parse_suffix(&chars, number.end) {
+            Some((Suffix::Percent, end)) => {
+                i = end;
+                counted(&number, &PERCENT)
+            }
+            Some((Suffix::Currency(currency), end)) => {
+                i = end;
+                amount_words(number, currency)
+            }
+            None => {
+                i = number.end;
+                let next_word_start = if chars.get(i) == Some(&' ') { i + 1 } else { i };
+                let gender = gender_of_word(&word_at(&chars, next_word_start).0);
+                read_number(&number, gender).0
+            }
+        };
+        push_words(&mut out, &words, chars.get(i));
+    }
+    out
+}
+
+fn is_url(word: &str) -> bool {
+    let lower = word.to_lowercase();
+    if lower.starts_with("http://") || lower.starts_with("https://") || lower.starts_with("www.") {
+        return true;
+    }
+    // Bare links such as twitch.tv/name or youtu.be/id
+    let host = lower.split('/').next().unwrap_or("");
+    let Some((name, tld)) = host.rsplit_once('.') else {
+        return false;
+    };
+    !name.is_empty()
+        && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '.')
+        && KNOWN_TLDS.contains(&tld)
+}
+
+fn replace_urls(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            let trimmed = word.trim_end_matches(['.', ',', '!', '?', ')', ';', ':']);
+            if is_url(trimmed) {
+                format!("{}{}", URL_REPLACEMENT, &word[trimmed.len()..])
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+const EMOJI_NAMES: &[(char, &str)] = &[
+    ('😂', "смех"),
+    ('🤣', "смех"),
+    ('😆', "смех"),
+    ('😀', "улыбка"),
+    ('😃', "улыбка"),
+    ('😄', "улыбка"),
+    ('😊', "улыбка"),
+    ('🙂', "улыбка"),
+    ('😉', "подмигивание"),
+    ('😍', "влюблённый смайлик"),
+    ('😘', "поцелуй"),
+    ('😎', "крутой смайлик"),
+    ('🤔', "задумчивый смайлик"),
+    ('😢', "грусть"),
+    ('😭', "слёзы"),
+    ('😡', "злость"),
+    ('😱', "ужас"),
+    ('💀', "череп"),
+    ('❤', "сердце"),
+    ('💔', "разбитое сердце"),
+    ('👍', "класс"),
+    ('👎', "дизлайк"),
+    ('👏', "аплодисменты"),
+    ('🙏', "спасибо"),
+    ('👋', "привет"),
+    ('🔥', "огонь"),
+    ('🎉', "праздник"),
+    ('💯', "сто из ста"),
+    ('🤡', "клоун"),
+    ('🐐', "козёл"),
+];
+
+/// Modifiers and joiners that only decorate the emoji before them
+fn is_emoji_modifier(c: char) -> bool {
+    matches!(c as u32, 0x200D | 0x20E3 | 0xFE0E | 0xFE0F | 0x1F3FB..=0x1F3FF | 0xE0020..=0xE007F)
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32, 0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF)
+}
+
+/// Named emoji become words (a repeated one is said once), the rest are dropped
+fn replace_emoji(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last_name = None;
+    for c in text.chars() {
+        if is_emoji_modifier(c) {
+            continue;
+        }
+        if let Some((_, name)) = EMOJI_NAMES.iter().find(|(emoji, _)| *emoji == c) {
+            if last_name != Some(*name) {
+                out.push(' ');
+                out.push_str(name);
+                out.push(' ');
+                last_name = Some(*name);
+            }
+        } else if is_emoji(c) {
+            out.push(' ');
+        } else {
+            if !c.is_whitespace() {
+                last_name = None;
+            }
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Shorten stretched words and punctuation: "дааааа" -> "да", "!!!" -> "!", ")))" -> ""
+fn collapse_repeats(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let is_exclamation = |c: char| c == '!' || c == '?';
+        let mut end = i + 1;
+        if is_exclamation(c) {
+            while end < chars.len() && is_exclamation(chars[end]) {
+                end += 1;
+            }
+            // "?!?!!" -> "?!"
+            let mut marks: Vec<char> = Vec::new();
+            for &mark in &chars[i..end] {
+                if !marks.contains(&mark) {
+                    marks.push(mark);
+                }
+            }
+            out.extend(marks);
+            i = end;
+            continue;
+        }
+
+        while end < chars.len() && chars[end] == c {
+            end += 1;
+        }
+        let run = end - i;
+        match c {
+            c if c.is_alphabetic() && run >= 3 => out.push(c),
+            // Bracket smileys: "привет)))"
+            ')' | '(' if run >= 2 => {}
+            '.' if run >= 3 => out.push_str("..."),
+            c if c.is_alphanumeric() || c.is_whitespace() => out.extend(&chars[i..end]),
+            c => out.push(c),
+        }
+        i = end;
+    }
+    out
+}
+
+/// Prepare chat or alert text for a Russian voice
+pub fn normalize(text: &str) -> String {
+    let text = replace_urls(text);
+    let text = replace_emoji(&text);
+    let text = collapse_repeats(&text);
+    let text = expand_numbers(&text);
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Read an alert amount such as ("5.00", "USD") or ("1000", "bits") aloud.
+///
+/// Unknown currencies keep their code after the number; `None` when `amount` is not a number.
+pub fn speak_amount(amount: &str, currency: Option<&str>) -> Option<String> {
+    let chars: Vec<char> = amount.trim().chars().collect();
+    let number = parse_number(&chars, 0).filter(|number| number.end == chars.len())?.without_zero_fraction();
+    let words = match currency {
+        Some(code) => match currency_for_code(code) {
+            Some(currency) => amount_words(number, currency),
+            None => format!("{} {}", read_number(&number, Gender::Masculine).0, code),
+        },
+        None => read_number(&number, Gender::Masculine).0,
+    };
+    Some(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cardinal_numbers() {
+        let cases: &[(u64, Gender, &str)] = &[
+            (0, Gender::Masculine, "ноль"),
+            (1, Gender::Masculine, "один"),
+            (1, Gender::Feminine, "одна"),
+            (1, Gender::Neuter, "одно"),
+            (2, Gender::Feminine, "две"),
+            (2, Gender::Neuter, "два"),
+            (11, Gender::Masculine, "одиннадцать"),
+            (19, Gender::Masculine, "девятнадцать"),
+            (20, Gender::Masculine, "двадцать"),
+            (21, Gender::Feminine, "двадцать одна"),
+            (40, Gender::Masculine, "сорок"),
+            (99, Gender::Masculine, "девяносто девять"),
+            (100, Gender::Masculine, "сто"),
+            (101, Gender::Masculine, "сто один"),
+            (112, Gender::Masculine, "сто двенадцать"),
+            (999, Gender::Masculine, "девятьсот девяносто девять"),
+            (1_000, Gender::Masculine, "тысяча"),
+            (1_001, Gender::Masculine, "тысяча один"),
+            (2_000, Gender::Masculine, "две тысячи"),
+            (5_000, Gender::Masculine, "пять тысяч"),
+            (11_000, Gender::Masculine, "одиннадцать тысяч"),
+            (21_000, Gender::Masculine, "двадцать одна тысяча"),
+            (22_500, Gender::Masculine, "двадцать две тысячи пятьсот"),
+            (1_000_000, Gender::Masculine, "один миллион"),
+            (3_000_000, Gender::Masculine, "три миллиона"),
+            (2_001_000, Gender::Masculine, "два миллиона одна тысяча"),
+            (7_000_000_000, Gender::Masculine, "семь миллиардов"),
+            (1_000_000_000_000, Gender::Masculine, "один триллион"),
+        ];
+        for (n, gender, expected) in cases {
+            assert_eq!(number_to_words(*n, *gender), *expected, "{} ({:?})", n, gender);
+        }
+    }
+
+    #[test]
+    fn noun_agreement() {
+        let cases: &[(u64, Agreement)] = &[
+            (0, Agreement::Many),
+            (1, Agreement::One),
+            (2, Agreement::Few),
+            (4, Agreement::Few),
+            (5, Agreement::Many),
+            (11, Agreement::Many),
+            (12, Agreement::Many),
+            (14, Agreement::Many),
+            (21, Agreement::One),
+            (22, Agreement::Few),
+            (111, Agreement::Many),
+            (101, Agreement::One),
+            (1_000_000, Agreement::Many),
+        ];
+        for (n, expected) in cases {
+            assert_eq!(agreement(*n), *expected, "{}", n);
+        }
+    }
+
+    #[test]
+    fn normalizes_chat_text() {
+        let cases: &[(&str, &str)] = &[
+            // Numbers
+            ("у меня 5 котов", "у меня пять котов"),
+            ("осталась 1 минута", "осталась одна минута"),
+            ("2 недели", "две недели"),
+            ("уже 21 сообщение", "уже двадцать одно сообщение"),
+            ("сейчас -5 градусов", "сейчас минус пять градусов"),
+            ("счёт 3-2", "счёт три-два"),
+            ("100500", "сто тысяч пятьсот"),
+            ("x2 урон", "x два урон"),
+            ("агент 007", "агент ноль ноль семь"),
+            ("id 12345678901234567", "id один два три четыре пять шесть семь восемь девять ноль один два три четыре пять шесть семь"),
+            ("пи это 3.14", "пи это три целых четырнадцать сотых"),
+            ("рейтинг 4,5", "рейтинг четыре целых пять десятых"),
+            ("версия 1.2.3", "версия один.два.три"),
+            // Percentages
+            ("скидка 50%", "скидка пятьдесят процентов"),
+            ("1%", "один процент"),
+            ("23 %", "двадцать три процента"),
+            ("шанс 2,5%", "шанс две целых пять десятых процента"),
+            // Currencies
+            ("$5", "пять долларов"),
+            ("задонатил 100 руб.", "задонатил сто рублей"),
+            ("500₽ на стрим", "пятьсот рублей на стрим"),
+            ("1 USD", "один доллар"),
+            ("$1.50", "один доллар пятьдесят центов"),
+            ("€3", "три евро"),
+            ("0.99$", "девяносто девять центов"),
+            ("21 грн", "двадцать одна гривна"),
+            ("100 bits", "сто битов"),
+            // Links
+            ("смотри https://example.com/a?b=c", "смотри ссылка"),
+            ("заходи на twitch.tv/streamer!", "заходи на ссылка!"),
+            ("www.youtube.com", "ссылка"),
+            ("т.е. не ссылка", "т.е. не ссылка"),
+            // Emoji
+            ("привет 👋", "привет привет"),
+            ("ахах 😂😂😂", "ахах смех"),
+            ("огонь🔥 🔥", "огонь огонь"),
+            ("класс 👍🏽", "класс класс"),
+            ("❤️ тебя", "сердце тебя"),
+            ("🦄 единорог", "единорог"),
+            ("🇷🇺", ""),
+            // Repeated characters
+            ("дааааа", "да"),
+            ("ооочень круто!!!", "очень круто!"),
+            ("что?!?!?!", "что?!"),
+            ("привет)))", "привет"),
+            ("ну......", "ну..."),
+            ("ок.", "ок."),
+            ("1000000", "один миллион"),
+            // Combined
+            ("ДАААА 3 подписки за 15 минут!!! 🎉", "ДА три подписки за пятнадцать минут! праздник"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(normalize(input), *expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn speaks_alert_amounts() {
+        let cases: &[(&str, Option<&str>, Option<&str>)] = &[
+            ("5.00", Some("USD"), Some("пять долларов")),
+            ("1.01", Some("USD"), Some("один доллар один цент")),
+            ("2.5", Some("EUR"), Some("два евро пятьдесят евроцентов")),
+            ("100.00", Some("RUB"), Some("сто рублей")),
+            ("1.02", Some("RUB"), Some("один рубль две копейки")),
+            ("22.00", Some("UAH"), Some("двадцать две гривны")),
+            ("1000", Some("bits"), Some("тысяча битов")),
+            ("1", Some("bits"), Some("один бит")),
+            ("500.00", Some("JPY"), Some("пятьсот иен")),
+            ("7.00", Some("CHF"), Some("семь CHF")),
+            ("42", None, Some("сорок два")),
+            ("0.50", Some("USD"), Some("пятьдесят центов")),
+            ("2.5", Some("JPY"), Some("две целых пять десятых иены")),
+            ("abc", Some("USD"), None),
+        ];
+        for (amount, currency, expected) in cases {
+            assert_eq!(speak_amount(amount, *currency).as_deref(), *expected, "{} {:?}", amount, currency);
+        }
+    }
+}
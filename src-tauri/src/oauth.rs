@@ -14,11 +14,14 @@ use tokio::net::TcpListener;
 use tokio::sync::broadcast;
 use tokio::sync::RwLock;
 use hmac::{Hmac, Mac};
+use sha1::Sha1;
 use sha2::Sha256;
 
-use crate::alerts::{AlertPayload, TwitchEventSubPayload, process_twitch_event, process_youtube_alert};
+use crate::alerts::{AlertPayload, TwitchEventSubPayload, TwitchSubscription, process_youtube_alert, youtube_announced_videos};
+use crate::eventsub::{EventSubSinks, MessageIdCache, is_message_fresh, parse_revocation};
 
 type HmacSha256 = Hmac<Sha256>;
+type HmacSha1 = Hmac<Sha1>;
 
 fn get_youtube_client_id() -> Result<String, String> {
     std::env::var("YOUTUBE_CLIENT_ID")
@@ -34,6 +37,18 @@ fn get_twitch_eventsub_secret() -> String {
         .unwrap_or_else(|_| "streamtts-default-secret".to_string())
 }
 
+/// The `hub.secret` the YouTube subscription was made with; there is no default, a public one would let anyone sign pushes
+fn get_youtube_websub_secret() -> Option<String> {
+    std::env::var("YOUTUBE_WEBSUB_SECRET")
+        .ok()
+        .filter(|secret| !secret.trim().is_empty())
+}
+
+fn get_google_token_url() -> String {
+    std::env::var("GOOGLE_TOKEN_URL")
+        .unwrap_or_else(|_| "https://oauth2.googleapis.com/token".to_string())
+}
+
 fn is_youtube_auth_configured() -> bool {
     get_youtube_client_secret().is_some()
 }
@@ -89,8 +104,11 @@ pub struct ErrorResponse {
 struct OAuthServerState {
     sender: broadcast::Sender<OAuthCallback>,
     alert_sender: broadcast::Sender<AlertPayload>,
+    eventsub: EventSubSinks,
     rate_limiter: Arc<RwLock<RateLimiter>>,
     oauth_states: Arc<RwLock<HashMap<String, (String, u64)>>>,
+    eventsub_message_ids: Arc<RwLock<MessageIdCache>>,
+    youtube_announced: Arc<RwLock<MessageIdCache>>,
 }
 
 #[derive(Clone)]
@@ -213,12 +231,47 @@ fn verify_twitch_signature(headers: &HeaderMap, body: &str) -> bool {
     let result = mac.finalize();
     let expected = format!("sha256={}", hex::encode(result.into_bytes()));
     
-    // Constant-time comparison
-    if expected.len() != signature.len() {
+    constant_time_eq(expected.as_bytes(), signature.as_bytes())
+}
+
+/// Verify a YouTube PubSubHubbub push signed with the `hub.secret` of the subscription.
+///
+/// Without a configured secret nothing verifies.
+fn verify_youtube_signature(secret: Option<&str>, headers: &HeaderMap, body: &str) -> bool {
+    let Some(secret) = secret else {
+        return false;
+    };
+    let signature = headers.get("X-Hub-Signature")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("");
+    
+    verify_hub_signature(secret, signature, body)
+}
+
+/// Check an `X-Hub-Signature` value of the form `sha1=<hex digest>` against the body
+fn verify_hub_signature(secret: &str, signature: &str, body: &str) -> bool {
+    let Some(received) = signature.trim().strip_prefix("sha1=") else {
+        return false;
+    };
+    
+    let mut mac = match HmacSha1::new_from_slice(secret.as_bytes()) {
+        Ok(mac) => mac,
+        Err(_) => return false,
+    };
+    
+    mac.update(body.as_bytes());
+    let expected = hex::encode(mac.finalize().into_bytes());
+    
+    constant_time_eq(expected.as_bytes(), received.to_ascii_lowercase().as_bytes())
+}
+
+/// Compare two byte strings without short-circuiting on the first mismatch
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
         return false;
     }
     
-    expected.bytes().zip(signature.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
 }
 
 /// Sanitize a string for safe display, stripping HTML and control characters
@@ -266,6 +319,7 @@ fn is_allowed_origin(headers: &HeaderMap) -> bool {
 pub async fn start_oauth_server(
     sender: broadcast::Sender<OAuthCallback>,
     alert_sender: broadcast::Sender<AlertPayload>,
+    eventsub: EventSubSinks,
 ) -> anyhow::Result<()> {
     if !is_youtube_auth_configured() {
         log::warn!("YOUTUBE_CLIENT_SECRET not set. YouTube OAuth will return configuration errors.");
@@ -274,8 +328,11 @@ pub async fn start_oauth_server(
     let state = Arc::new(OAuthServerState {
         sender,
         alert_sender,
+        eventsub,
         rate_limiter: Arc::new(RwLock::new(RateLimiter::new())),
         oauth_states: Arc::new(RwLock::new(HashMap::new())),
+        eventsub_message_ids: Arc::new(RwLock::new(MessageIdCache::new())),
+        youtube_announced: Arc::new(RwLock::new(youtube_announced_videos())),
     });
     let app = Router::new()
         .route("/callback", get(handle_callback))
@@ -370,32 +427,100 @@ async fn handle_twitch_alerts(
             if let Ok(payload) = serde_json::from_str::<serde_json::Value>(&body) {
                 let challenge = payload["challenge"].as_str().unwrap_or("");
                 log::info!("Twitch EventSub verification challenge received");
+                if let Ok(subscription) = serde_json::from_value::<TwitchSubscription>(payload["subscription"].clone()) {
+                    state.eventsub.health.write().await.record_enabled(&subscription, chrono::Utc::now());
+                }
                 challenge.to_string().into_response()
             } else {
                 StatusCode::BAD_REQUEST.into_response()
             }
         }
         "notification" => {
+            let Some(message_id) = twitch_message_id(&headers) else {
+                log::warn!("Twitch EventSub notification without a message id - rejected");
+                return StatusCode::BAD_REQUEST.into_response();
+            };
+            
             // Verify Twitch EventSub signature
             if !verify_twitch_signature(&headers, &body) {
                 log::warn!("Twitch EventSub notification with invalid signature - rejected");
                 return StatusCode::FORBIDDEN.into_response();
             }
             
+            if !is_twitch_message_fresh(&headers) {
+                log::warn!("Twitch EventSub notification outside the replay window - rejected");
+                return StatusCode::FORBIDDEN.into_response();
+            }
+            
+            // Twitch retries until it sees a 2xx, so acknowledge duplicates without reprocessing
+            if !record_twitch_message_id(&state, message_id).await {
+                log::info!("Duplicate Twitch EventSub notification ignored");
+                return StatusCode::OK.into_response();
+            }
+            
             if let Ok(payload) = serde_json::from_str::<serde_json::Value>(&body) {
                 if let Ok(twitch_payload) = serde_json::from_value::<TwitchEventSubPayload>(payload) {
-                    if let Some(alert) = process_twitch_event(twitch_payload) {
-                        log::info!("Twitch alert processed: {} - {}", alert.alert_type, alert.user_name);
-                        let _ = state.alert_sender.send(alert);
-                    }
+                    state.eventsub.deliver_notification(twitch_payload).await;
                 }
             }
             StatusCode::OK.into_response()
         }
+        "revocation" => {
+            let Some(message_id) = twitch_message_id(&headers) else {
+                log::warn!("Twitch EventSub revocation without a message id - rejected");
+                return StatusCode::BAD_REQUEST.into_response();
+            };
+            
+            if !verify_twitch_signature(&headers, &body) {
+                log::warn!("Twitch EventSub revocation with invalid signature - rejected");
+                return StatusCode::FORBIDDEN.into_response();
+            }
+            
+            if !is_twitch_message_fresh(&headers) {
+                log::warn!("Twitch EventSub revocation outside the replay window - rejected");
+                return StatusCode::FORBIDDEN.into_response();
+            }
+            
+            if !record_twitch_message_id(&state, message_id).await {
+                return StatusCode::OK.into_response();
+            }
+            
+            match parse_revocation(&body, chrono::Utc::now()) {
+                Some(revocation) => state.eventsub.deliver_revocation(revocation).await,
+                None => log::warn!("Failed to parse Twitch EventSub revocation body"),
+            }
+            StatusCode::OK.into_response()
+        }
         _ => StatusCode::OK.into_response(),
     }
 }
 
+fn is_twitch_message_fresh(headers: &HeaderMap) -> bool {
+    let timestamp = headers.get("Twitch-Eventsub-Message-Timestamp")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("");
+    
+    is_message_fresh(timestamp, chrono::Utc::now())
+}
+
+/// The `Twitch-Eventsub-Message-Id` header, `None` when missing or blank
+fn twitch_message_id(headers: &HeaderMap) -> Option<&str> {
+    headers.get("Twitch-Eventsub-Message-Id")
+        .and_then(|h| h.to_str().ok())
+        .filter(|id| !id.trim().is_empty())
+}
+
+/// Returns `false` when the message id has already been delivered
+async fn record_twitch_message_id(state: &Arc<OAuthServerState>, message_id: &str) -> bool {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    
+    let mut seen = state.eventsub_message_ids.write().await;
+    seen.check_and_record(message_id, now)
+}
+
 async fn handle_youtube_challenge(
     Query(params): Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
@@ -408,11 +533,27 @@ async fn handle_youtube_challenge(
 }
 
 async fn handle_youtube_alerts(
+    headers: HeaderMap,
     State(state): State<Arc<OAuthServerState>>,
     body: String,
 ) -> impl IntoResponse {
+    let secret = get_youtube_websub_secret();
+    if secret.is_none() {
+        log::warn!("YouTube notification rejected: YOUTUBE_WEBSUB_SECRET is not set");
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if !verify_youtube_signature(secret.as_deref(), &headers, &body) {
+        log::warn!("YouTube notification with missing or invalid signature - rejected");
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    
     log::info!("Received YouTube alert notification");
-    if let Some(alert) = process_youtube_alert(&body) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let alert = process_youtube_alert(&body, &mut *state.youtube_announced.write().await, now);
+    if let Some(alert) = alert {
         let _ = state.alert_sender.send(alert);
     }
     StatusCode::OK.into_response()
@@ -460,7 +601,7 @@ async fn handle_auth_exchange(
     ];
     
     let response = match client
-        .post("https://oauth2.googleapis.com/token")
+        .post(get_google_token_url())
         .form(&params)
         .send()
         .await
@@ -513,82 +654,113 @@ async fn handle_auth_exchange(
     }).into_response()
 }
 
-async fn handle_auth_refresh(
-    headers: HeaderMap,
-    State(_state): State<Arc<OAuthServerState>>,
-    Json(payload): Json<RefreshTokenRequest>,
-) -> impl IntoResponse {
-    if !is_allowed_origin(&headers) {
-        log::warn!("Rejected auth-refresh from disallowed origin");
-        return StatusCode::FORBIDDEN.into_response();
-    }
-    
-    log::info!("Received token refresh request for YouTube");
-    
-    let client_secret = match get_youtube_client_secret() {
-        Some(secret) => secret,
-        None => {
-            return (StatusCode::SERVICE_UNAVAILABLE, Json(ErrorResponse {
-                error: "YouTube authentication is not configured.".to_string(),
-            })).into_response();
-        }
-    };
-    
-    let client = reqwest::Client::new();
-    let client_id = match get_youtube_client_id() {
-        Ok(id) => id,
-        Err(e) => {
-            log::error!("YouTube client ID not configured: {}", e);
-            return (StatusCode::SERVICE_UNAVAILABLE, Json(ErrorResponse {
-                error: "YouTube client ID is not configured".to_string(),
-            })).into_response();
+/// Why a YouTube token refresh did not produce a new access token
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenRefreshError {
+    NotConfigured,
+    MissingClientId,
+    Unreachable,
+    Rejected,
+    InvalidResponse,
+}
+
+/// Google token endpoint and the YouTube OAuth client to refresh tokens as
+#[derive(Debug, Clone)]
+pub struct GoogleOAuthClient {
+    pub token_url: String,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+impl GoogleOAuthClient {
+    /// Read from `GOOGLE_TOKEN_URL`, `YOUTUBE_CLIENT_ID` and `YOUTUBE_CLIENT_SECRET`
+    pub fn from_env() -> Self {
+        GoogleOAuthClient {
+            token_url: get_google_token_url(),
+            client_id: get_youtube_client_id().ok(),
+            client_secret: get_youtube_client_secret(),
         }
-    };
+    }
+}
+
+/// Exchange a refresh token for a new YouTube access token.
+///
+/// Shared by the `/auth-refresh` endpoint and the backend live chat poller.
+pub async fn refresh_youtube_token(
+    client: &reqwest::Client,
+    oauth: &GoogleOAuthClient,
+    refresh_token: &str,
+) -> Result<TokenResponse, TokenRefreshError> {
+    let client_secret = oauth.client_secret.as_deref().ok_or(TokenRefreshError::NotConfigured)?;
+    let client_id = oauth.client_id.as_deref().ok_or_else(|| {
+        log::error!("YouTube client ID not configured");
+        TokenRefreshError::MissingClientId
+    })?;
     
     let params = [
-        ("refresh_token", payload.refresh_token.as_str()),
-        ("client_id", &client_id),
-        ("client_secret", &client_secret),
+        ("refresh_token", refresh_token),
+        ("client_id", client_id),
+        ("client_secret", client_secret),
         ("grant_type", "refresh_token"),
     ];
     
-    let response = match client
-        .post("https://oauth2.googleapis.com/token")
+    let response = client
+        .post(&oauth.token_url)
         .form(&params)
         .send()
         .await
-    {
-        Ok(resp) => resp,
-        Err(_) => {
-            return (StatusCode::BAD_GATEWAY, Json(ErrorResponse {
-                error: "Failed to connect to authentication provider.".to_string(),
-            })).into_response();
-        }
-    };
+        .map_err(|_| TokenRefreshError::Unreachable)?;
     
     if !response.status().is_success() {
-        return (StatusCode::BAD_REQUEST, Json(ErrorResponse {
-            error: "Token refresh failed. Please log in again.".to_string(),
-        })).into_response();
+        return Err(TokenRefreshError::Rejected);
     }
     
-    let token_response: GoogleTokenResponse = match response.json().await {
-        Ok(resp) => resp,
-        Err(_) => {
-            return (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
-                error: "Failed to process refresh response.".to_string(),
-            })).into_response();
-        }
-    };
+    let token_response: GoogleTokenResponse = response
+        .json()
+        .await
+        .map_err(|_| TokenRefreshError::InvalidResponse)?;
     
     log::info!("Successfully refreshed access token");
     
-    Json(TokenResponse {
+    Ok(TokenResponse {
         access_token: token_response.access_token,
         refresh_token: token_response.refresh_token,
         expires_in: token_response.expires_in,
         token_type: token_response.token_type,
-    }).into_response()
+    })
+}
+
+async fn handle_auth_refresh(
+    headers: HeaderMap,
+    State(_state): State<Arc<OAuthServerState>>,
+    Json(payload): Json<RefreshTokenRequest>,
+) -> impl IntoResponse {
+    if !is_allowed_origin(&headers) {
+        log::warn!("Rejected auth-refresh from disallowed origin");
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    
+    log::info!("Received token refresh request for YouTube");
+    
+    let client = reqwest::Client::new();
+    match refresh_youtube_token(&client, &GoogleOAuthClient::from_env(), &payload.refresh_token).await {
+        Ok(tokens) => Json(tokens).into_response(),
+        Err(TokenRefreshError::NotConfigured) => (StatusCode::SERVICE_UNAVAILABLE, Json(ErrorResponse {
+            error: "YouTube authentication is not configured.".to_string(),
+        })).into_response(),
+        Err(TokenRefreshError::MissingClientId) => (StatusCode::SERVICE_UNAVAILABLE, Json(ErrorResponse {
+            error: "YouTube client ID is not configured".to_string(),
+        })).into_response(),
+        Err(TokenRefreshError::Unreachable) => (StatusCode::BAD_GATEWAY, Json(ErrorResponse {
+            error: "Failed to connect to authentication provider.".to_string(),
+        })).into_response(),
+        Err(TokenRefreshError::Rejected) => (StatusCode::BAD_REQUEST, Json(ErrorResponse {
+            error: "Token refresh failed. Please log in again.".to_string(),
+        })).into_response(),
+        Err(TokenRefreshError::InvalidResponse) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
+            error: "Failed to process refresh response.".to_string(),
+        })).into_response(),
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -598,3 +770,90 @@ struct GoogleTokenResponse {
     expires_in: i64,
     token_type: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eventsub::SubscriptionHealthRegistry;
+
+    fn sign(secret: &str, body: &str) -> String {
+        let mut mac = HmacSha1::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body.as_bytes());
+        format!("sha1={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn hub_signature_accepts_matching_body() {
+        let body = "<feed><entry/></feed>";
+        assert!(verify_hub_signature("secret", &sign("secret", body), body));
+    }
+
+    #[test]
+    fn hub_signature_accepts_uppercase_digest() {
+        let body = "<feed/>";
+        let signature = sign("secret", body).to_uppercase().replacen("SHA1=", "sha1=", 1);
+        assert!(verify_hub_signature("secret", &signature, body));
+    }
+
+    #[test]
+    fn hub_signature_rejects_tampered_body_or_wrong_secret() {
+        let body = "<feed><entry/></feed>";
+        let signature = sign("secret", body);
+        assert!(!verify_hub_signature("secret", &signature, "<feed/>"));
+        assert!(!verify_hub_signature("other", &signature, body));
+    }
+
+    fn server_state() -> Arc<OAuthServerState> {
+        let (alert_sender, _) = broadcast::channel(16);
+        let (revocation_sender, _) = broadcast::channel(16);
+        Arc::new(OAuthServerState {
+            sender: broadcast::channel(16).0,
+            alert_sender: alert_sender.clone(),
+            eventsub: EventSubSinks {
+                alert_sender,
+                revocation_sender,
+                health: Arc::new(RwLock::new(SubscriptionHealthRegistry::new())),
+            },
+            rate_limiter: Arc::new(RwLock::new(RateLimiter::new())),
+            oauth_states: Arc::new(RwLock::new(HashMap::new())),
+            eventsub_message_ids: Arc::new(RwLock::new(MessageIdCache::new())),
+            youtube_announced: Arc::new(RwLock::new(youtube_announced_videos())),
+        })
+    }
+
+    #[tokio::test]
+    async fn twitch_message_without_id_is_rejected_before_dedupe() {
+        let state = server_state();
+        for message_type in ["notification", "revocation"] {
+            for message_id in [None, Some(""), Some("  ")] {
+                let mut headers = HeaderMap::new();
+                headers.insert("Twitch-Eventsub-Message-Type", message_type.parse().unwrap());
+                if let Some(id) = message_id {
+                    headers.insert("Twitch-Eventsub-Message-Id", id.parse().unwrap());
+                }
+                let response = handle_twitch_alerts(headers, State(state.clone()), "{}".to_string()).await.into_response();
+                assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{} with id {:?}", message_type, message_id);
+            }
+        }
+        // Nothing was recorded, so a real message can never be mistaken for a retry of a blank id
+        assert!(state.eventsub_message_ids.write().await.check_and_record("", 0));
+    }
+
+    #[test]
+    fn youtube_push_needs_a_configured_secret() {
+        let body = "<feed/>";
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Hub-Signature", sign("streamtts-default-secret", body).parse().unwrap());
+        assert!(!verify_youtube_signature(None, &headers, body));
+        assert!(!verify_youtube_signature(Some("secret"), &headers, body));
+        assert!(verify_youtube_signature(Some("streamtts-default-secret"), &headers, body));
+    }
+
+    #[test]
+    fn hub_signature_rejects_missing_or_malformed_header() {
+        let body = "<feed/>";
+        assert!(!verify_hub_signature("secret", "", body));
+        assert!(!verify_hub_signature("secret", &sign("secret", body).replace("sha1=", "sha256="), body));
+        assert!(!verify_hub_signature("secret", "sha1=deadbeef", body));
+    }
+}
@@ -0,0 +1,307 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::chat::{ChatMessage, ChatRoles};
+use crate::moderation::MODERATION_COMMANDS;
+use crate::storage::JsonStore;
+use crate::twitch_api::{HelixClient, TwitchCredentialStore};
+
+/// Needed for `GET /channels/followers`
+const FOLLOWER_SCOPE: &str = "moderator:read:followers";
+/// Follow dates rarely change; this keeps busy chats from hammering Helix
+const FOLLOW_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Chatter roles from least to most trusted; a role includes everything below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Everyone,
+    Follower,
+    Subscriber,
+    Vip,
+    Moderator,
+    Broadcaster,
+}
+
+impl Role {
+    /// Highest role the sender holds
+    pub fn of(roles: &ChatRoles, follower_min_days: u32, now: DateTime<Utc>) -> Role {
+        let followed_long_enough = roles
+            .followed_at
+            .is_some_and(|since| now.signed_duration_since(since).num_days() >= follower_min_days as i64);
+        [
+            (roles.broadcaster, Role::Broadcaster),
+            (roles.moderator, Role::Moderator),
+            (roles.vip, Role::Vip),
+            (roles.subscriber, Role::Subscriber),
+            (followed_long_enough, Role::Follower),
+        ]
+        .into_iter()
+        .find_map(|(held, role)| held.then_some(role))
+        .unwrap_or(Role::Everyone)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandPermission {
+    /// First word of the message, e.g. `!skip` or `!voice`
+    pub prefix: String,
+    pub min_role: Role,
+}
+
+/// Who may have messages read and who may use which chat command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PermissionPolicy {
+    /// Needed to have ordinary messages read
+    pub speak: Role,
+    /// Checked before `speak`; the first matching prefix decides
+    #[serde(default)]
+    pub commands: Vec<CommandPermission>,
+    /// How long someone must have followed to count as a follower
+    #[serde(default)]
+    pub follower_min_days: u32,
+}
+
+impl Default for PermissionPolicy {
+    fn default() -> Self {
+        let command = |prefix: &str, min_role| CommandPermission { prefix: prefix.to_string(), min_role };
+        PermissionPolicy {
+            speak: Role::Everyone,
+            commands: vec![
+                command("!skip", Role::Moderator),
+                command("!stop", Role::Moderator),
+                command("!ttsmute", Role::Moderator),
+                command("!ttsunmute", Role::Moderator),
+                command("!voice", Role::Everyone),
+                command("!голос", Role::Everyone),
+            ],
+            follower_min_days: 0,
+        }
+    }
+}
+
+impl PermissionPolicy {
+    /// Role needed for `text`, by its command prefix or else the `speak` role.
+    ///
+    /// Moderation commands missing from `commands` still need a moderator.
+    pub fn required_role(&self, text: &str) -> Role {
+        let first_word = text.split_whitespace().next().unwrap_or("").to_lowercase();
+        match self.commands.iter().find(|command| command.prefix.trim().to_lowercase() == first_word) {
+            Some(command) => command.min_role,
+            None if MODERATION_COMMANDS.contains(&first_word.as_str()) => self.speak.max(Role::Moderator),
+            None => self.speak,
+        }
+    }
+
+    pub fn permits(&self, roles: &ChatRoles, text: &str, now: DateTime<Utc>) -> bool {
+        Role::of(roles, self.follower_min_days, now) >= self.required_role(text)
+    }
+}
+
+pub type PermissionStore = JsonStore<PermissionPolicy>;
+
+/// When a follow date was fetched, and the date itself
+type CachedFollow = (Instant, Option<DateTime<Utc>>);
+
+/// Follow dates from Helix for the connected Twitch channel, cached per viewer
+pub struct FollowerLookup {
+    helix: HelixClient,
+    credentials: Arc<TwitchCredentialStore>,
+    cache: Mutex<HashMap<String, CachedFollow>>,
+}
+
+impl FollowerLookup {
+    pub fn new(helix: HelixClient, credentials: Arc<TwitchCredentialStore>) -> Self {
+        FollowerLookup { helix, credentials, cache: Mutex::new(HashMap::new()) }
+    }
+
+    /// `None` when unknown: not Twitch, another channel, a missing scope or a failed request
+    async fn followed_at(&self, message: &ChatMessage) -> Option<DateTime<Utc>> {
+        if message.platform != "twitch" || message.user_id.is_empty() {
+            return None;
+        }
+        let credentials = self.credentials.get().await?;
+        if !credentials.login.eq_ignore_ascii_case(&message.channel) || !credentials.scopes.iter().any(|s| s == FOLLOWER_SCOPE) {
+            return None;
+        }
+
+        if let Some((fetched, followed_at)) = self.cache.lock().await.get(&message.user_id) {
+            if fetched.elapsed() < FOLLOW_CACHE_TTL {
+                return *followed_at;
+            }
+        }
+        match self.helix.get_followed_at(&credentials, &message.user_id).await {
+            Ok(followed_at) => {
+                self.cache.lock().await.insert(message.user_id.clone(), (Instant::now(), followed_at));
+                followed_at
+            }
+            Err(e) => {
+                log::warn!("Could not look up whether {} follows: {}", message.user_name, e);
+                None
+            }
+        }
+    }
+}
+
+pub struct Permissions {
+    store: Arc<PermissionStore>,
+    followers: FollowerLookup,
+}
+
+impl Permissions {
+    pub fn new(store: Arc<PermissionStore>, followers: FollowerLookup) -> Self {
+        Permissions { store, followers }
+    }
+
+    pub async fn policy(&self) -> PermissionPolicy {
+        self.store.get().await.unwrap_or_default()
+    }
+
+    pub async fn set_policy(&self, policy: PermissionPolicy) -> Result<(), String> {
+        if let Some(blank) = policy.commands.iter().find(|c| c.prefix.trim().is_empty()) {
+            return Err(format!("Command permission for {:?} needs a prefix", blank.min_role));
+        }
+        self.store.set(policy).await
+    }
+
+    /// Whether the sender may do what `message` asks for.
+    ///
+    /// The follow date is only fetched when being a follower is what decides it, and is kept on the message.
+    pub async fn check(&self, message: &mut ChatMessage) -> bool {
+        let policy = self.policy().await;
+        let now = Utc::now();
+        let required = policy.required_role(&message.text);
+        let role = Role::of(&message.roles, policy.follower_min_days, now);
+        if role >= required {
+            return true;
+        }
+        if required != Role::Follower || message.roles.followed_at.is_some() {
+            return false;
+        }
+        message.roles.followed_at = self.followers.followed_at(message).await;
+        policy.permits(&message.roles, &message.text, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::twitch_api::TwitchCredentials;
+    use axum::extract::{Query, State};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::TcpListener;
+
+    fn roles(role: &str) -> ChatRoles {
+        ChatRoles {
+            broadcaster: role == "broadcaster",
+            moderator: role == "moderator",
+            vip: role == "vip",
+            subscriber: role == "subscriber",
+            followed_at: None,
+        }
+    }
+
+    #[test]
+    fn policy_decides_by_prefix_and_role() {
+        let now = Utc::now();
+        let policy = PermissionPolicy {
+            speak: Role::Subscriber,
+            follower_min_days: 7,
+            ..PermissionPolicy::default()
+        };
+        let new_follower = ChatRoles { followed_at: Some(now - chrono::Duration::days(2)), ..roles("") };
+        let old_follower = ChatRoles { followed_at: Some(now - chrono::Duration::days(30)), ..roles("") };
+        assert_eq!(Role::of(&new_follower, 7, now), Role::Everyone);
+        assert_eq!(Role::of(&old_follower, 7, now), Role::Follower);
+
+        let cases = [
+            (roles(""), "привет", false),
+            (old_follower.clone(), "привет", false),
+            (roles("subscriber"), "привет", true),
+            (roles("vip"), "привет", true),
+            (roles(""), "!voice robot", true),
+            (roles(""), "!ГОЛОС робот", true),
+            (roles("vip"), "!skip", false),
+            (roles("moderator"), "!Skip now", true),
+            (roles("broadcaster"), "!skip", true),
+            (roles("vip"), "!ttsmute someone", false),
+            // Only the first word counts as a command
+            (roles("subscriber"), "please !skip", true),
+        ];
+        for (roles, text, expected) in cases {
+            assert_eq!(policy.permits(&roles, text, now), expected, "{:?} {:?}", roles, text);
+        }
+    }
+
+    #[tokio::test]
+    async fn looks_up_follow_date_only_when_needed() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        async fn followers(
+            State(requests): State<Arc<AtomicUsize>>,
+            Query(params): Query<HashMap<String, String>>,
+        ) -> Json<serde_json::Value> {
+            requests.fetch_add(1, Ordering::SeqCst);
+            assert_eq!(params["broadcaster_id"], "1337");
+            let data = match params["user_id"].as_str() {
+                "42" => serde_json::json!([{"user_id": "42", "followed_at": "2020-01-01T00:00:00Z"}]),
+                _ => serde_json::json!([]),
+            };
+            Json(serde_json::json!({"data": data, "total": 1}))
+        }
+        let app = Router::new().route("/helix/channels/followers", get(followers)).with_state(requests.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let credentials = Arc::new(TwitchCredentialStore::load(None));
+        credentials
+            .set(TwitchCredentials {
+                access_token: "token-1".to_string(),
+                client_id: "client-1".to_string(),
+                user_id: "1337".to_string(),
+                login: "streamer".to_string(),
+                scopes: vec![FOLLOWER_SCOPE.to_string()],
+            })
+            .await
+            .unwrap();
+        let permissions = Permissions::new(
+            Arc::new(PermissionStore::load(None)),
+            FollowerLookup::new(HelixClient::with_base_urls(format!("{}/helix", base), base.clone()), credentials),
+        );
+        permissions
+            .set_policy(PermissionPolicy { speak: Role::Follower, ..PermissionPolicy::default() })
+            .await
+            .unwrap();
+
+        let message = |user_id: &str, role: &str| ChatMessage {
+            platform: "twitch".to_string(),
+            channel: "streamer".to_string(),
+            user_id: user_id.to_string(),
+            text: "привет".to_string(),
+            roles: roles(role),
+            ..Default::default()
+        };
+        let mut follower = message("42", "");
+        assert!(permissions.check(&mut follower).await);
+        assert!(follower.roles.followed_at.is_some());
+        assert!(permissions.check(&mut message("42", "")).await);
+        assert!(!permissions.check(&mut message("7", "")).await);
+        assert!(permissions.check(&mut message("8", "subscriber")).await);
+        // One request per viewer; the second check was cached and the subscriber needed none
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        let blank = PermissionPolicy {
+            commands: vec![CommandPermission { prefix: " ".to_string(), min_role: Role::Everyone }],
+            ..PermissionPolicy::default()
+        };
+        assert!(permissions.set_policy(blank).await.is_err());
+    }
+}
@@ -0,0 +1,378 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+
+use crate::storage::{load_json, save_json};
+
+/// Read instead of a flagged name when the action skips words
+const NEUTRAL_NAME: &str = "зритель";
+
+const RUSSIAN_WORDS: &str = include_str!("../wordlists/ru.txt");
+const ENGLISH_WORDS: &str = include_str!("../wordlists/en.txt");
+
+/// Prefixes a Russian stem may carry, as in за-ебал, от-ъ-ебись, о-хуеть
+const RUSSIAN_PREFIXES: &[&str] = &[
+    "вы", "за", "от", "отъ", "ото", "на", "по", "при", "раз", "разъ", "рас", "у", "о", "об", "объ", "обо", "с", "съ",
+    "до", "под", "подъ", "пере", "про", "недо", "не", "из", "изъ", "вз", "взъ", "в", "въ",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfanityAction {
+    /// Say `beep_text` instead of the word
+    Beep,
+    SkipWord,
+    DropMessage,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfanityConfig {
+    pub enabled: bool,
+    pub action: ProfanityAction,
+    #[serde(default = "default_beep_text")]
+    pub beep_text: String,
+    /// More entries in the word list syntax: `word`, `stem*`, `*root*`, `!exception`
+    #[serde(default)]
+    pub extra_words: Vec<String>,
+}
+
+fn default_beep_text() -> String {
+    "пи-ип".to_string()
+}
+
+impl Default for ProfanityConfig {
+    fn default() -> Self {
+        ProfanityConfig {
+            enabled: true,
+            action: ProfanityAction::Beep,
+            beep_text: default_beep_text(),
+            extra_words: Vec::new(),
+        }
+    }
+}
+
+/// Lowercase with runs of one letter squeezed, so "ПИИИЗДА" and "пизда" compare equal
+fn squeeze(word: &str) -> String {
+    let mut squeezed = String::with_capacity(word.len());
+    for c in word.chars().flat_map(char::to_lowercase) {
+        if !squeezed.ends_with(c) {
+            squeezed.push(c);
+        }
+    }
+    squeezed
+}
+
+fn is_cyrillic(c: char) -> bool {
+    matches!(c, '\u{0400}'..='\u{04FF}')
+}
+
+/// Latin letters and digits that pass for Cyrillic ones inside a Russian word
+fn cyrillic_lookalike(c: char) -> char {
+    match c {
+        'a' | '@' => 'а',
+        'b' | '6' => 'б',
+        'c' => 'с',
+        'e' => 'е',
+        'h' => 'н',
+        'k' => 'к',
+        'm' => 'м',
+        'n' => 'п',
+        'o' | '0' => 'о',
+        'p' => 'р',
+        'r' => 'г',
+        't' => 'т',
+        'u' | '1' => 'и',
+        'x' => 'х',
+        'y' => 'у',
+        '3' => 'з',
+        '4' => 'ч',
+        _ => c,
+    }
+}
+
+fn latin_leet(c: char) -> char {
+    match c {
+        '0' => 'o',
+        '1' | '!' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' => 't',
+        _ => c,
+    }
+}
+
+/// Russian written in Latin letters: "pizdec" → "пиздец", "blyat" → "блят"
+fn transliterate(word: &str) -> String {
+    const DIGRAPHS: &[(&str, &str)] = &[
+        ("sch", "щ"),
+        ("sh", "ш"),
+        ("ch", "ч"),
+        ("zh", "ж"),
+        ("kh", "х"),
+        ("ts", "ц"),
+        ("ya", "я"),
+        ("yu", "ю"),
+        ("yo", "ё"),
+    ];
+    let mut out = String::with_capacity(word.len() * 2);
+    let mut rest = word;
+    'outer: while let Some(c) = rest.chars().next() {
+        for (latin, cyrillic) in DIGRAPHS {
+            if let Some(after) = rest.strip_prefix(latin) {
+                out.push_str(cyrillic);
+                rest = after;
+                continue 'outer;
+            }
+        }
+        out.push(match c {
+            'a' => 'а',
+            'b' => 'б',
+            'v' | 'w' => 'в',
+            'g' => 'г',
+            'd' => 'д',
+            'e' => 'е',
+            'z' => 'з',
+            'i' => 'и',
+            'j' | 'y' => 'й',
+            'k' | 'q' => 'к',
+            'l' => 'л',
+            'm' => 'м',
+            'n' => 'н',
+            'o' => 'о',
+            'p' => 'п',
+            'r' => 'р',
+            's' => 'с',
+            't' => 'т',
+            'u' => 'у',
+            'f' => 'ф',
+            'h' | 'x' => 'х',
+            'c' => 'ц',
+            other => other,
+        });
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+/// The spellings `word` might stand for, each squeezed, one per way of reading it
+fn candidates(word: &str) -> Vec<String> {
+    let word: String = word.chars().flat_map(char::to_lowercase).filter(|c| !matches!(c, '.' | '-' | '_' | '\'' | '`')).collect();
+    if !word.chars().any(char::is_alphabetic) {
+        return Vec::new();
+    }
+    if word.chars().any(is_cyrillic) {
+        return vec![squeeze(&word.chars().map(cyrillic_lookalike).collect::<String>())];
+    }
+    let latin: String = word.chars().map(latin_leet).collect();
+    if !latin.chars().all(|c| c.is_ascii_lowercase()) {
+        return vec![squeeze(&word)];
+    }
+    vec![squeeze(&latin), squeeze(&transliterate(&latin))]
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Exact(String),
+    Stem(String),
+    Root(String),
+}
+
+impl Pattern {
+    fn parse(entry: &str) -> Option<Pattern> {
+        let entry = squeeze(entry.trim());
+        if entry.is_empty() || entry.starts_with('#') {
+            return None;
+        }
+        Some(match (entry.strip_prefix('*'), entry.strip_suffix('*')) {
+            (Some(root), Some(_)) => Pattern::Root(root.trim_end_matches('*').to_string()),
+            (None, Some(stem)) => Pattern::Stem(stem.to_string()),
+            _ => Pattern::Exact(entry),
+        })
+    }
+
+    fn matches(&self, word: &str) -> bool {
+        match self {
+            Pattern::Exact(exact) => word == exact,
+            Pattern::Root(root) => word.contains(root.as_str()),
+            Pattern::Stem(stem) if stem.chars().any(is_cyrillic) => {
+                std::iter::once("").chain(RUSSIAN_PREFIXES.iter().copied()).any(|prefix| {
+                    word.strip_prefix(prefix).is_some_and(|rest| rest.starts_with(stem.as_str()))
+                })
+            }
+            Pattern::Stem(stem) => word.starts_with(stem.as_str()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct WordList {
+    flagged: Vec<Pattern>,
+    exceptions: Vec<Pattern>,
+}
+
+impl WordList {
+    fn build(config: &ProfanityConfig) -> Self {
+        let mut list = WordList::default();
+        let bundled = RUSSIAN_WORDS.lines().chain(ENGLISH_WORDS.lines());
+        for entry in bundled.chain(config.extra_words.iter().map(String::as_str)) {
+            match entry.trim().strip_prefix('!') {
+                Some(exception) => list.exceptions.extend(Pattern::parse(exception)),
+                None => list.flagged.extend(Pattern::parse(entry)),
+            }
+        }
+        list
+    }
+
+    /// Exceptions win over every reading of the word
+    fn is_offensive(&self, word: &str) -> bool {
+        let candidates = candidates(word);
+        let any = |patterns: &[Pattern]| candidates.iter().any(|c| patterns.iter().any(|p| p.matches(c)));
+        !any(&self.exceptions) && any(&self.flagged)
+    }
+}
+
+/// Split a whitespace-free token into leading punctuation, the word and trailing punctuation
+fn split_token(token: &str) -> (&str, &str, &str) {
+    let word_start = token.find(|c: char| c.is_alphanumeric() || matches!(c, '$' | '@')).unwrap_or(token.len());
+    let word_end = token
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_alphanumeric())
+        .map_or(word_start, |(i, c)| i + c.len_utf8())
+        .max(word_start);
+    (&token[..word_start], &token[word_start..word_end], &token[word_end..])
+}
+
+/// Masks, skips or drops offensive words before they are read aloud
+pub struct ProfanityFilter {
+    path: Option<PathBuf>,
+    state: RwLock<(ProfanityConfig, WordList)>,
+}
+
+impl ProfanityFilter {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let config = path.as_deref().and_then(load_json::<ProfanityConfig>).unwrap_or_default();
+        let words = WordList::build(&config);
+        ProfanityFilter { path, state: RwLock::new((config, words)) }
+    }
+
+    pub async fn config(&self) -> ProfanityConfig {
+        self.state.read().await.0.clone()
+    }
+
+    pub async fn set_config(&self, config: ProfanityConfig) -> Result<(), String> {
+        let words = WordList::build(&config);
+        if let Some(ref path) = self.path {
+            save_json(path, &config)?;
+        }
+        *self.state.write().await = (config, words);
+        Ok(())
+    }
+
+    /// The text to read, or `None` when the whole message should be dropped
+    pub async fn apply(&self, text: &str) -> Option<String> {
+        let state = self.state.read().await;
+        let (config, words) = &*state;
+        if !config.enabled {
+            return Some(text.to_string());
+        }
+
+        let mut kept = Vec::new();
+        for token in text.split_whitespace() {
+            let (before, word, after) = split_token(token);
+            if !words.is_offensive(word) {
+                kept.push(token.to_string());
+                continue;
+            }
+            match config.action {
+                ProfanityAction::DropMessage => return None,
+                ProfanityAction::Beep => kept.push(format!("{}{}{}", before, config.beep_text, after)),
+                ProfanityAction::SkipWord => {
+                    let punctuation = format!("{}{}", before, after);
+                    if !punctuation.is_empty() {
+                        kept.push(punctuation);
+                    }
+                }
+            }
+        }
+        Some(kept.join(" "))
+    }
+
+    /// The name to read for a viewer or alert sender, or `None` when their message should be dropped.
+    ///
+    /// Names run words together, so each piece between separators is checked as well as the whole.
+    pub async fn apply_name(&self, name: &str) -> Option<String> {
+        let state = self.state.read().await;
+        let (config, words) = &*state;
+        let flagged = config.enabled
+            && (words.is_offensive(name) || name.split(|c: char| !c.is_alphanumeric()).any(|part| words.is_offensive(part)));
+        if !flagged {
+            return Some(name.to_string());
+        }
+        match config.action {
+            ProfanityAction::DropMessage => None,
+            ProfanityAction::Beep => Some(config.beep_text.clone()),
+            ProfanityAction::SkipWord => Some(NEUTRAL_NAME.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catches_evasions_but_not_innocent_words() {
+        let words = WordList::build(&ProfanityConfig::default());
+        let offensive = [
+            "хуй", "ХУУУУЙ", "xуй", "х.у.й", "пи3да", "п1зда", "nизда", "заебал", "отъебись", "охуеть", "ёбаный",
+            "уёбок", "долбоёб", "ссука", "блядь", "pizdec", "blyat", "huy", "suka", "fuck", "FUUUCK", "motherfucker",
+            "sh1t", "$hit", "b1tch", "f.u.c.k",
+        ];
+        for word in offensive {
+            assert!(words.is_offensive(word), "{} should be flagged", word);
+        }
+        let innocent = [
+            "себя", "себе", "хлеба", "небу", "страхуй", "оскорблять", "употреблять", "сукно", "херсон", "хохлома",
+            "художник", "Scunthorpe", "shiitake", "hue", "assassin", "Dickens", "pizza", "2024",
+        ];
+        for word in innocent {
+            assert!(!words.is_offensive(word), "{} should not be flagged", word);
+        }
+    }
+
+    #[tokio::test]
+    async fn applies_the_configured_action() {
+        let filter = ProfanityFilter::load(None);
+        assert_eq!(filter.apply("ну ты и сука, правда").await.as_deref(), Some("ну ты и пи-ип, правда"));
+        assert_eq!(filter.apply("Привет, чат!").await.as_deref(), Some("Привет, чат!"));
+
+        let skip = ProfanityConfig { action: ProfanityAction::SkipWord, ..ProfanityConfig::default() };
+        filter.set_config(skip).await.unwrap();
+        assert_eq!(filter.apply("what the fuck!").await.as_deref(), Some("what the !"));
+
+        let drop = ProfanityConfig {
+            action: ProfanityAction::DropMessage,
+            extra_words: vec!["кринж*".to_string(), "!кринжатина".to_string()],
+            ..ProfanityConfig::default()
+        };
+        filter.set_config(drop).await.unwrap();
+        assert_eq!(filter.apply("какой кринжище").await, None);
+        assert_eq!(filter.apply("вот это кринжатина").await.as_deref(), Some("вот это кринжатина"));
+
+        assert_eq!(filter.apply_name("Fuck_The_Chat").await, None);
+        assert_eq!(filter.apply_name("Kind_Viewer").await.as_deref(), Some("Kind_Viewer"));
+
+        filter.set_config(ProfanityConfig::default()).await.unwrap();
+        assert_eq!(filter.apply_name("suka_2000").await.as_deref(), Some("пи-ип"));
+        assert_eq!(filter.apply_name("@Блядь").await.as_deref(), Some("пи-ип"));
+        let skip = ProfanityConfig { action: ProfanityAction::SkipWord, ..ProfanityConfig::default() };
+        filter.set_config(skip).await.unwrap();
+        assert_eq!(filter.apply_name("suka_2000").await.as_deref(), Some("зритель"));
+
+        let off = ProfanityConfig { enabled: false, ..ProfanityConfig::default() };
+        filter.set_config(off).await.unwrap();
+        assert_eq!(filter.apply("сука").await.as_deref(), Some("сука"));
+    }
+}
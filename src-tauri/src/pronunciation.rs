@@ -0,0 +1,387 @@
+use regex::{NoExpand, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+
+use crate::storage::{load_json, save_json};
+
+/// Language assumed for voices that do not name one (ElevenLabs voices are multilingual)
+pub const DEFAULT_LANGUAGE: &str = "ru";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleKind {
+    /// Replace the pattern as a whole word
+    #[default]
+    Literal,
+    /// Replace every match of a regular expression; `$1` in the replacement refers to groups
+    Regex,
+}
+
+/// One user-defined replacement applied before synthesis
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PronunciationRule {
+    /// Assigned by the dictionary; ignored on add and import
+    #[serde(default)]
+    pub id: u64,
+    pub pattern: String,
+    pub replacement: String,
+    #[serde(default)]
+    pub kind: RuleKind,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Only apply for this language ("ru", "en-US"); `None` applies everywhere
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DictionaryFormat {
+    Json,
+    Csv,
+}
+
+/// A rule as imported and exported: ids are local to one install, so they are left out
+#[derive(Debug, Serialize, Deserialize)]
+struct RuleRecord {
+    pattern: String,
+    replacement: String,
+    #[serde(default, deserialize_with = "csv::invalid_option")]
+    kind: Option<RuleKind>,
+    #[serde(default, deserialize_with = "csv::invalid_option")]
+    case_sensitive: Option<bool>,
+    #[serde(default)]
+    language: Option<String>,
+}
+
+impl From<&PronunciationRule> for RuleRecord {
+    fn from(rule: &PronunciationRule) -> Self {
+        RuleRecord {
+            pattern: rule.pattern.clone(),
+            replacement: rule.replacement.clone(),
+            kind: Some(rule.kind),
+            case_sensitive: Some(rule.case_sensitive),
+            language: rule.language.clone(),
+        }
+    }
+}
+
+impl From<RuleRecord> for PronunciationRule {
+    fn from(record: RuleRecord) -> Self {
+        PronunciationRule {
+            id: 0,
+            pattern: record.pattern,
+            replacement: record.replacement,
+            kind: record.kind.unwrap_or_default(),
+            case_sensitive: record.case_sensitive.unwrap_or(false),
+            language: record.language.filter(|language| !language.trim().is_empty()),
+        }
+    }
+}
+
+/// "en-US", "en_us" and "EN" all compare as "en"
+fn primary_language(language: &str) -> String {
+    language.split(['-', '_']).next().unwrap_or("").trim().to_lowercase()
+}
+
+/// The language a voice id speaks, e.g. `espeak:en-us` or `piper:ru_RU-irina-medium`
+pub fn language_for_voice(voice: Option<&str>) -> String {
+    voice
+        .and_then(|voice| voice.strip_prefix("espeak:").or_else(|| voice.strip_prefix("piper:")))
+        .map(primary_language)
+        .filter(|language| !language.is_empty())
+        .unwrap_or_else(|| DEFAULT_LANGUAGE.to_string())
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn compile(rule: &PronunciationRule) -> Result<Regex, String> {
+    if rule.pattern.trim().is_empty() {
+        return Err("Pronunciation rule pattern is empty".to_string());
+    }
+    let pattern = match rule.kind {
+        RuleKind::Regex => rule.pattern.clone(),
+        RuleKind::Literal => {
+            // Whole words only, so a rule for "Ли" leaves "Лиса" alone
+            let start = if rule.pattern.starts_with(is_word_char) { r"\b" } else { "" };
+            let end = if rule.pattern.ends_with(is_word_char) { r"\b" } else { "" };
+            format!("{}{}{}", start, regex::escape(&rule.pattern), end)
+        }
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!rule.case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid pattern {:?}: {}", rule.pattern, e))
+}
+
+#[derive(Clone)]
+struct CompiledRule {
+    rule: PronunciationRule,
+    regex: Regex,
+    /// Primary subtag of `rule.language`
+    language: Option<String>,
+}
+
+impl CompiledRule {
+    fn new(rule: PronunciationRule) -> Result<Self, String> {
+        let regex = compile(&rule)?;
+        let language = rule.language.as_deref().map(primary_language);
+        Ok(CompiledRule { rule, regex, language })
+    }
+
+    fn apply(&self, text: &str) -> String {
+        match self.rule.kind {
+            RuleKind::Literal => self.regex.replace_all(text, NoExpand(&self.rule.replacement)).into_owned(),
+            RuleKind::Regex => self.regex.replace_all(text, self.rule.replacement.as_str()).into_owned(),
+        }
+    }
+}
+
+/// Ordered replacement rules persisted in the app data directory and applied before synthesis.
+///
+/// Rules run in order, each on the output of the previous one.
+pub struct PronunciationDictionary {
+    path: Option<PathBuf>,
+    rules: RwLock<Vec<CompiledRule>>,
+}
+
+impl PronunciationDictionary {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let stored: Vec<PronunciationRule> = path.as_deref().and_then(load_json).unwrap_or_default();
+        let rules = stored
+            .into_iter()
+            .filter_map(|rule| match CompiledRule::new(rule) {
+                Ok(compiled) => Some(compiled),
+                Err(e) => {
+                    log::warn!("Skipping pronunciation rule: {}", e);
+                    None
+                }
+            })
+            .collect();
+        PronunciationDictionary { path, rules: RwLock::new(rules) }
+    }
+
+    fn save(&self, rules: &[CompiledRule]) -> Result<(), String> {
+        match self.path {
+            Some(ref path) => save_json(path, &rules.iter().map(|compiled| &compiled.rule).collect::<Vec<_>>()),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn rules(&self) -> Vec<PronunciationRule> {
+        self.rules.read().await.iter().map(|compiled| compiled.rule.clone()).collect()
+    }
+
+    pub async fn add(&self, mut rule: PronunciationRule) -> Result<PronunciationRule, String> {
+        let mut rules = self.rules.write().await;
+        rule.id = next_id(&rules);
+        rules.push(CompiledRule::new(rule.clone())?);
+        if let Err(e) = self.save(&rules) {
+            rules.pop();
+            return Err(e);
+        }
+        Ok(rule)
+    }
+
+    /// Replace the rule with the same id, keeping its position
+    pub async fn update(&self, rule: PronunciationRule) -> Result<(), String> {
+        let mut rules = self.rules.write().await;
+        let index = rules
+            .iter()
+            .position(|compiled| compiled.rule.id == rule.id)
+            .ok_or_else(|| format!("Pronunciation rule {} not found", rule.id))?;
+        let previous = std::mem::replace(&mut rules[index], CompiledRule::new(rule)?);
+        if let Err(e) = self.save(&rules) {
+            rules[index] = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub async fn remove(&self, id: u64) -> Result<(), String> {
+        let mut rules = self.rules.write().await;
+        let index = rules
+            .iter()
+            .position(|compiled| compiled.rule.id == id)
+            .ok_or_else(|| format!("Pronunciation rule {} not found", id))?;
+        let removed = rules.remove(index);
+        if let Err(e) = self.save(&rules) {
+            rules.insert(index, removed);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Add rules from an export, or swap the whole dictionary when `replace` is set.
+    ///
+    /// Nothing is imported if any rule is invalid. Returns the number of rules imported.
+    pub async fn import(&self, data: &str, format: DictionaryFormat, replace: bool) -> Result<usize, String> {
+        let records = parse_records(data, format)?;
+        let mut imported = Vec::with_capacity(records.len());
+        for (index, record) in records.into_iter().enumerate() {
+            let rule = CompiledRule::new(record.into()).map_err(|e| format!("Rule {}: {}", index + 1, e))?;
+            imported.push(rule);
+        }
+        let count = imported.len();
+
+        let mut rules = self.rules.write().await;
+        let mut updated = if replace { Vec::new() } else { rules.clone() };
+        let first_id = next_id(&updated);
+        for (offset, mut compiled) in (0..).zip(imported) {
+            compiled.rule.id = first_id + offset;
+            updated.push(compiled);
+        }
+        self.save(&updated)?;
+        *rules = updated;
+        Ok(count)
+    }
+
+    pub async fn export(&self, format: DictionaryFormat) -> Result<String, String> {
+        let rules = self.rules.read().await;
+        let records: Vec<RuleRecord> = rules.iter().map(|compiled| RuleRecord::from(&compiled.rule)).collect();
+        match format {
+            DictionaryFormat::Json => {
+                serde_json::to_string_pretty(&records).map_err(|e| format!("Failed to export dictionary: {}", e))
+            }
+            DictionaryFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                for record in &records {
+                    writer.serialize(record).map_err(|e| format!("Failed to export dictionary: {}", e))?;
+                }
+                let bytes = writer.into_inner().map_err(|e| format!("Failed to export dictionary: {}", e))?;
+                String::from_utf8(bytes).map_err(|e| format!("Failed to export dictionary: {}", e))
+            }
+        }
+    }
+
+    /// Run every rule for `language` over `text`
+    pub async fn apply(&self, text: &str, language: &str) -> String {
+        let language = primary_language(language);
+        let rules = self.rules.read().await;
+        rules
+            .iter()
+            .filter(|compiled| compiled.language.as_ref().map_or(true, |rule_language| *rule_language == language))
+            .fold(text.to_string(), |text, compiled| compiled.apply(&text))
+    }
+}
+
+fn next_id(rules: &[CompiledRule]) -> u64 {
+    rules.iter().map(|compiled| compiled.rule.id).max().unwrap_or(0) + 1
+}
+
+fn parse_records(data: &str, format: DictionaryFormat) -> Result<Vec<RuleRecord>, String> {
+    match format {
+        DictionaryFormat::Json => serde_json::from_str(data).map_err(|e| format!("Invalid dictionary JSON: {}", e)),
+        DictionaryFormat::Csv => csv::ReaderBuilder::new()
+            .trim(csv::Trim::Headers)
+            .from_reader(data.as_bytes())
+            .deserialize()
+            .enumerate()
+            .map(|(index, record)| record.map_err(|e| format!("Invalid dictionary CSV at row {}: {}", index + 1, e)))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct FixtureCase {
+        text: String,
+        voice: Option<String>,
+        expected: String,
+    }
+
+    #[derive(Deserialize)]
+    struct Fixture {
+        cases: Vec<FixtureCase>,
+    }
+
+    fn rule(pattern: &str, replacement: &str) -> PronunciationRule {
+        PronunciationRule {
+            id: 0,
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            kind: RuleKind::Literal,
+            case_sensitive: false,
+            language: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn fixture_cases() {
+        let dictionary = PronunciationDictionary::load(None);
+        let imported = dictionary
+            .import(include_str!("../fixtures/pronunciation.csv"), DictionaryFormat::Csv, false)
+            .await
+            .unwrap();
+        assert_eq!(imported, dictionary.rules().await.len());
+
+        let fixture: Fixture = serde_json::from_str(include_str!("../fixtures/pronunciation_cases.json")).unwrap();
+        for case in fixture.cases {
+            let language = language_for_voice(case.voice.as_deref());
+            assert_eq!(dictionary.apply(&case.text, &language).await, case.expected, "{:?}", case.text);
+        }
+    }
+
+    #[tokio::test]
+    async fn manages_and_persists_rules() {
+        let dir = std::env::temp_dir().join(format!("streamtts-pronunciation-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = crate::storage::data_file(&dir, "pronunciation.json");
+
+        let dictionary = PronunciationDictionary::load(Some(path.clone()));
+        let first = dictionary.add(rule("GG", "гуд гейм")).await.unwrap();
+        let second = dictionary.add(rule("xqc", "иксквк")).await.unwrap();
+        assert_eq!((first.id, second.id), (1, 2));
+
+        let invalid = PronunciationRule { kind: RuleKind::Regex, ..rule("(", "x") };
+        assert!(dictionary.add(invalid).await.is_err());
+        assert!(dictionary.add(rule("  ", "x")).await.is_err());
+
+        dictionary.update(PronunciationRule { id: 2, ..rule("xQc", "икс кью си") }).await.unwrap();
+        dictionary.remove(1).await.unwrap();
+        assert!(dictionary.remove(1).await.is_err());
+
+        let reloaded = PronunciationDictionary::load(Some(path));
+        let rules = reloaded.rules().await;
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].replacement, "икс кью си");
+        assert_eq!(reloaded.add(rule("lol", "лол")).await.unwrap().id, 3);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn import_and_export_round_trip() {
+        let dictionary = PronunciationDictionary::load(None);
+        dictionary
+            .add(PronunciationRule { language: Some("ru".to_string()), case_sensitive: true, ..rule("Dota, 2", "дота \"два\"") })
+            .await
+            .unwrap();
+        dictionary.add(PronunciationRule { kind: RuleKind::Regex, ..rule(r"(\d+)k", "$1 тысяч") }).await.unwrap();
+
+        for format in [DictionaryFormat::Json, DictionaryFormat::Csv] {
+            let exported = dictionary.export(format).await.unwrap();
+            let copy = PronunciationDictionary::load(None);
+            assert_eq!(copy.import(&exported, format, false).await.unwrap(), 2);
+            assert_eq!(copy.rules().await, dictionary.rules().await, "{:?}", format);
+        }
+
+        // A bad rule rejects the whole import
+        let bad = "pattern,replacement,kind\nok,fine,literal\n[,broken,regex\n";
+        let error = dictionary.import(bad, DictionaryFormat::Csv, true).await.unwrap_err();
+        assert!(error.starts_with("Rule 2:"), "{}", error);
+        assert_eq!(dictionary.rules().await.len(), 2);
+
+        // Optional columns may be left out; replacing renumbers from 1
+        let minimal = "pattern,replacement\nNaVi,нави\n";
+        assert_eq!(dictionary.import(minimal, DictionaryFormat::Csv, true).await.unwrap(), 1);
+        let rules = dictionary.rules().await;
+        assert_eq!((rules.len(), rules[0].id, rules[0].kind), (1, 1, RuleKind::Literal));
+        assert!(dictionary.import("not json", DictionaryFormat::Json, false).await.is_err());
+    }
+}
@@ -0,0 +1,248 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::storage::JsonStore;
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+/// A long message using this share of distinct words or fewer is one phrase pasted over and over
+const REPETITIVE_WORD_RATIO: f32 = 0.3;
+
+/// Limits on how much chat gets read; a zero turns that limit off
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpamGuardConfig {
+    /// Seconds between two read messages of one viewer
+    pub user_cooldown_secs: u64,
+    /// Read messages per minute across all viewers
+    pub global_per_minute: u32,
+    /// How far back duplicates and copy-pastas are looked for
+    pub duplicate_window_secs: u64,
+    /// From 0 to 1; messages at least this similar count as the same
+    pub similarity_threshold: f32,
+    /// The same text from this many viewers is a copy-pasta
+    pub copypasta_min_users: usize,
+    /// Messages this long are checked for one phrase repeated inside them
+    pub copypasta_min_chars: usize,
+}
+
+impl Default for SpamGuardConfig {
+    fn default() -> Self {
+        SpamGuardConfig {
+            user_cooldown_secs: 5,
+            global_per_minute: 20,
+            duplicate_window_secs: 60,
+            similarity_threshold: 0.85,
+            copypasta_min_users: 3,
+            copypasta_min_chars: 60,
+        }
+    }
+}
+
+pub type SpamGuardStore = JsonStore<SpamGuardConfig>;
+
+/// Why a message was not read
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum SuppressReason {
+    UserCooldown { retry_after_secs: u64 },
+    GlobalRateLimit,
+    Duplicate,
+    CopyPasta,
+    /// Dropped by the profanity filter
+    Profanity,
+    /// A moderator muted the viewer with `!ttsmute`
+    Muted { until: DateTime<Utc> },
+}
+
+/// Lowercase letters and digits with single spaces, repeated letters squeezed, so "ПРИВЕЕЕТ!!" equals "привет"
+fn normalize(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut last = None;
+    for c in text.chars().flat_map(char::to_lowercase) {
+        let c = if c.is_alphanumeric() { c } else { ' ' };
+        if Some(c) == last || (c == ' ' && normalized.is_empty()) {
+            continue;
+        }
+        normalized.push(c);
+        last = Some(c);
+    }
+    normalized.trim_end().to_string()
+}
+
+fn trigrams(text: &str) -> HashSet<[char; 3]> {
+    let chars: Vec<char> = text.chars().collect();
+    chars.windows(3).map(|w| [w[0], w[1], w[2]]).collect()
+}
+
+/// Dice coefficient over character trigrams of two normalized texts
+fn similarity(a: &str, b: &str) -> f32 {
+    if a == b {
+        return 1.0;
+    }
+    let (a, b) = (trigrams(a), trigrams(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(&b).count() as f32 / (a.len() + b.len()) as f32
+}
+
+fn is_repetitive(normalized: &str, min_chars: usize) -> bool {
+    if min_chars == 0 || normalized.chars().count() < min_chars {
+        return false;
+    }
+    let words: Vec<&str> = normalized.split(' ').collect();
+    let distinct: HashSet<&&str> = words.iter().collect();
+    (distinct.len() as f32) / (words.len() as f32) <= REPETITIVE_WORD_RATIO
+}
+
+struct SeenMessage {
+    at: Instant,
+    viewer: String,
+    normalized: String,
+    read: bool,
+}
+
+#[derive(Default)]
+struct GuardState {
+    /// Every message that reached the guard within the duplicate window
+    seen: VecDeque<SeenMessage>,
+    /// Read messages within the last minute
+    reads: VecDeque<Instant>,
+    last_read: HashMap<String, Instant>,
+}
+
+/// The first limit `normalized` breaks, checked before the message is recorded
+fn verdict(
+    config: &SpamGuardConfig,
+    state: &GuardState,
+    viewer: &str,
+    normalized: &str,
+    now: Instant,
+) -> Result<(), SuppressReason> {
+    if let Some(last) = state.last_read.get(viewer) {
+        let wait = Duration::from_secs(config.user_cooldown_secs).saturating_sub(now.saturating_duration_since(*last));
+        return Err(SuppressReason::UserCooldown { retry_after_secs: wait.as_secs_f32().ceil() as u64 });
+    }
+
+    let similar: Vec<&SeenMessage> = state
+        .seen
+        .iter()
+        .filter(|seen| similarity(&seen.normalized, normalized) >= config.similarity_threshold)
+        .collect();
+    let mut viewers: HashSet<&str> = similar.iter().map(|seen| seen.viewer.as_str()).collect();
+    viewers.insert(viewer);
+    if (config.copypasta_min_users > 0 && viewers.len() >= config.copypasta_min_users)
+        || is_repetitive(normalized, config.copypasta_min_chars)
+    {
+        return Err(SuppressReason::CopyPasta);
+    }
+    if similar.iter().any(|seen| seen.read) {
+        return Err(SuppressReason::Duplicate);
+    }
+
+    if config.global_per_minute > 0 && state.reads.len() >= config.global_per_minute as usize {
+        return Err(SuppressReason::GlobalRateLimit);
+    }
+    Ok(())
+}
+
+/// Keeps any one viewer, or the whole chat, from flooding the speech queue
+pub struct SpamGuard {
+    store: Arc<SpamGuardStore>,
+    state: Mutex<GuardState>,
+}
+
+impl SpamGuard {
+    pub fn new(store: Arc<SpamGuardStore>) -> Self {
+        SpamGuard { store, state: Mutex::new(GuardState::default()) }
+    }
+
+    pub async fn config(&self) -> SpamGuardConfig {
+        self.store.get().await.unwrap_or_default()
+    }
+
+    pub async fn set_config(&self, config: SpamGuardConfig) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&config.similarity_threshold) {
+            return Err("Similarity threshold must be between 0 and 1".to_string());
+        }
+        self.store.set(config).await
+    }
+
+    /// Record a message about to be read; `Err` says why it should not be
+    pub async fn check(&self, platform: &str, user_name: &str, text: &str) -> Result<(), SuppressReason> {
+        self.check_at(platform, user_name, text, Instant::now()).await
+    }
+
+    async fn check_at(&self, platform: &str, user_name: &str, text: &str, now: Instant) -> Result<(), SuppressReason> {
+        let config = self.config().await;
+        let viewer = format!("{}:{}", platform, user_name.to_lowercase());
+        let normalized = normalize(text);
+        let cooldown = Duration::from_secs(config.user_cooldown_secs);
+        let window = Duration::from_secs(config.duplicate_window_secs);
+
+        let mut state = self.state.lock().await;
+        let recent = |at: Instant, span: Duration| now.saturating_duration_since(at) < span;
+        state.seen.retain(|seen| recent(seen.at, window));
+        state.reads.retain(|at| recent(*at, RATE_WINDOW));
+        state.last_read.retain(|_, at| recent(*at, cooldown));
+
+        let verdict = verdict(&config, &state, &viewer, &normalized, now);
+        state.seen.push_back(SeenMessage { at: now, viewer: viewer.clone(), normalized, read: verdict.is_ok() });
+        if verdict.is_ok() {
+            state.reads.push_back(now);
+            state.last_read.insert(viewer, now);
+        }
+        verdict
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn guard(config: SpamGuardConfig) -> SpamGuard {
+        let guard = SpamGuard::new(Arc::new(SpamGuardStore::load(None)));
+        guard.set_config(config).await.unwrap();
+        guard
+    }
+
+    #[test]
+    fn near_duplicates_are_similar() {
+        assert_eq!(normalize("  ПРИВЕЕЕТ,   чат!!! "), "привет чат");
+        assert!(similarity(&normalize("Привет всем в чате"), &normalize("привет всем в чатеее!!")) >= 0.85);
+        assert!(similarity(&normalize("привет всем в чате"), &normalize("как дела у стримера")) < 0.3);
+        assert!(is_repetitive(&normalize(&"буп ".repeat(30)), 60));
+        assert!(!is_repetitive(&normalize("буп буп буп"), 60));
+    }
+
+    #[tokio::test]
+    async fn suppresses_with_reason_codes() {
+        let guard = guard(SpamGuardConfig { global_per_minute: 4, ..SpamGuardConfig::default() }).await;
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        let cases = [
+            (0, "alice", "привет всем", Ok(())),
+            (2, "alice", "как дела", Err(SuppressReason::UserCooldown { retry_after_secs: 3 })),
+            (3, "bob", "ПРИВЕТ ВСЕМ!!!", Err(SuppressReason::Duplicate)),
+            (4, "carol", "привет всем", Err(SuppressReason::CopyPasta)),
+            (5, "dave", &"буп ".repeat(30), Err(SuppressReason::CopyPasta)),
+            (6, "alice", "как дела", Ok(())),
+            (7, "bob", "что за игра", Ok(())),
+            (8, "dave", "какой сегодня план", Ok(())),
+            (9, "erin", "сколько часов стрим", Err(SuppressReason::GlobalRateLimit)),
+            // The first read has left the rate window
+            (61, "erin", "сколько часов стрим", Ok(())),
+            // Every "привет всем" has left the duplicate window
+            (68, "frank", "привет всем", Ok(())),
+        ];
+        for (secs, user, text, expected) in cases {
+            assert_eq!(guard.check_at("twitch", user, text, at(secs)).await, expected, "{} at {}s", text, secs);
+        }
+
+        assert!(guard.set_config(SpamGuardConfig { similarity_threshold: 1.5, ..SpamGuardConfig::default() }).await.is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+
+use crate::alerts::AlertPayload;
+
+/// Gap between the viewer's name and what they wrote
+const USERNAME_PAUSE_MS: u32 = 400;
+/// Gap between an alert and the message attached to it
+const MESSAGE_PAUSE_MS: u32 = 600;
+/// Attached messages longer than this are read more slowly
+const LONG_MESSAGE_CHARS: usize = 150;
+
+/// How much SSML a provider understands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SsmlSupport {
+    /// Plain text only
+    None,
+    /// Plain text with inline `<break>` tags (ElevenLabs)
+    Breaks,
+    /// A full `<speak>` document (espeak-ng)
+    Full,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SpeechPart {
+    Text {
+        text: String,
+    },
+    Pause {
+        millis: u32,
+    },
+    /// `value` is the written form, e.g. "5.00 USD"; every engine reads `spoken`, which is the part rewritten
+    /// before synthesis and which espeak-ng cannot produce itself for currencies
+    SayAs {
+        interpret_as: String,
+        value: String,
+        spoken: String,
+    },
+}
+
+/// Rate and pitch for a whole utterance, as SSML values such as "90%" or "+2st"
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Prosody {
+    #[serde(default)]
+    pub rate: Option<String>,
+    #[serde(default)]
+    pub pitch: Option<String>,
+}
+
+impl Prosody {
+    fn is_default(&self) -> bool {
+        self.rate.is_none() && self.pitch.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageClass {
+    Chat,
+    Alert,
+    /// A paid alert carrying a long message
+    LongDonation,
+}
+
+impl MessageClass {
+    pub fn prosody(self) -> Prosody {
+        let (rate, pitch) = match self {
+            MessageClass::Chat => (None, None),
+            MessageClass::Alert => (None, Some("+2st")),
+            MessageClass::LongDonation => (Some("85%"), Some("+2st")),
+        };
+        Prosody { rate: rate.map(str::to_string), pitch: pitch.map(str::to_string) }
+    }
+}
+
+/// An utterance built from parts, rendered as SSML or plain text depending on the provider
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Speech {
+    pub parts: Vec<SpeechPart>,
+    #[serde(default)]
+    pub prosody: Prosody,
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Inline-break engines take plain text with tags in it, so viewers' angle brackets must not reach them
+fn strip_markup(text: &str) -> String {
+    text.replace(['<', '>'], " ").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl Speech {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn text(mut self, text: &str) -> Self {
+        if !text.trim().is_empty() {
+            self.parts.push(SpeechPart::Text { text: text.to_string() });
+        }
+        self
+    }
+
+    pub fn pause(mut self, millis: u32) -> Self {
+        self.parts.push(SpeechPart::Pause { millis });
+        self
+    }
+
+    pub fn say_as(mut self, interpret_as: &str, value: &str, spoken: &str) -> Self {
+        self.parts.push(SpeechPart::SayAs {
+            interpret_as: interpret_as.to_string(),
+            value: value.to_string(),
+            spoken: spoken.to_string(),
+        });
+        self
+    }
+
+    pub fn prosody(mut self, prosody: Prosody) -> Self {
+        self.prosody = prosody;
+        self
+    }
+
+    /// Everything that will be read aloud, for rewriting before synthesis
+    pub fn spoken_text_mut(&mut self) -> Vec<&mut String> {
+        self.parts
+            .iter_mut()
+            .filter_map(|part| match part {
+                SpeechPart::Text { text } => Some(text),
+                SpeechPart::SayAs { spoken, .. } => Some(spoken),
+                SpeechPart::Pause { .. } => None,
+            })
+            .collect()
+    }
+
+    pub fn plain_text(&self) -> String {
+        self.render(SsmlSupport::None)
+    }
+
+    pub fn render(&self, support: SsmlSupport) -> String {
+        let mut pieces = Vec::with_capacity(self.parts.len());
+        for part in &self.parts {
+            let piece = match (part, support) {
+                (SpeechPart::Pause { .. }, SsmlSupport::None) => continue,
+                (SpeechPart::Pause { millis }, SsmlSupport::Breaks) => {
+                    format!("<break time=\"{:.1}s\" />", *millis as f32 / 1000.0)
+                }
+                (SpeechPart::Pause { millis }, SsmlSupport::Full) => format!("<break time=\"{}ms\"/>", millis),
+                (SpeechPart::Text { text }, SsmlSupport::Full) => escape_xml(text),
+                (SpeechPart::Text { text }, SsmlSupport::Breaks) => strip_markup(text),
+                (SpeechPart::Text { text }, SsmlSupport::None) => text.clone(),
+                (SpeechPart::SayAs { spoken, .. }, SsmlSupport::Full) => escape_xml(spoken),
+                (SpeechPart::SayAs { spoken, .. }, SsmlSupport::Breaks) => strip_markup(spoken),
+                (SpeechPart::SayAs { spoken, .. }, SsmlSupport::None) => spoken.clone(),
+            };
+            pieces.push(piece);
+        }
+        let body = pieces.join(" ");
+
+        if support != SsmlSupport::Full {
+            return body;
+        }
+        if self.prosody.is_default() {
+            return format!("<speak>{}</speak>", body);
+        }
+        let mut attributes = String::new();
+        if let Some(ref rate) = self.prosody.rate {
+            attributes.push_str(&format!(" rate=\"{}\"", escape_xml(rate)));
+        }
+        if let Some(ref pitch) = self.prosody.pitch {
+            attributes.push_str(&format!(" pitch=\"{}\"", escape_xml(pitch)));
+        }
+        format!("<speak><prosody{}>{}</prosody></speak>", attributes, body)
+    }
+}
+
+/// "<name>, pause, <message>" for a chat message
+pub fn chat_speech(user_name: &str, text: &str) -> Speech {
+    Speech::new()
+        .text(user_name)
+        .pause(USERNAME_PAUSE_MS)
+        .text(text)
+        .prosody(MessageClass::Chat.prosody())
+}
+
+/// Paid alerts read the name and amount, anything else its message; the viewer's own message follows
+pub fn alert_speech(alert: &AlertPayload) -> Speech {
+    let user_message = alert.user_message.as_deref().unwrap_or("");
+    let class = match alert.spoken_amount {
+        Some(_) if user_message.chars().count() > LONG_MESSAGE_CHARS => MessageClass::LongDonation,
+        _ => MessageClass::Alert,
+    };
+
+    let speech = match (&alert.amount, &alert.spoken_amount) {
+        (Some(amount), Some(spoken)) => {
+            let value = match alert.currency {
+                Some(ref currency) => format!("{} {}", amount, currency),
+                None => amount.clone(),
+            };
+            Speech::new().text(&alert.user_name).pause(USERNAME_PAUSE_MS).say_as("currency", &value, spoken)
+        }
+        _ => Speech::new().text(&alert.message),
+    };
+    let speech = if user_message.is_empty() { speech } else { speech.pause(MESSAGE_PAUSE_MS).text(user_message) };
+    speech.prosody(class.prosody())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn super_chat(user_message: &str) -> AlertPayload {
+        AlertPayload {
+            platform: "youtube".to_string(),
+            alert_type: "superchat".to_string(),
+            user_name: "Viewer".to_string(),
+            message: "Viewer sent a $5.00 Super Chat!".to_string(),
+            amount: Some("5.00".to_string()),
+            currency: Some("USD".to_string()),
+            spoken_amount: Some("пять долларов".to_string()),
+            user_message: Some(user_message.to_string()).filter(|m| !m.is_empty()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn chat_renders_for_each_support_level() {
+        let speech = chat_speech("Bob", "a < b & c");
+        assert_eq!(speech.render(SsmlSupport::None), "Bob a < b & c");
+        assert_eq!(speech.render(SsmlSupport::Breaks), "Bob <break time=\"0.4s\" /> a b & c");
+        assert_eq!(speech.render(SsmlSupport::Full), "<speak>Bob <break time=\"400ms\"/> a &lt; b &amp; c</speak>");
+    }
+
+    #[test]
+    fn viewer_markup_cannot_add_breaks() {
+        let speech = chat_speech("<b>Bob</b>", "привет <break time=\"30s\" /> чат");
+        assert_eq!(
+            speech.render(SsmlSupport::Breaks),
+            "b Bob /b <break time=\"0.4s\" /> привет break time=\"30s\" / чат"
+        );
+        assert_eq!(speech.render(SsmlSupport::Breaks).matches("<break").count(), 1);
+    }
+
+    #[test]
+    fn paid_alert_reads_the_spoken_amount_with_prosody() {
+        let speech = alert_speech(&super_chat("спасибо за стрим"));
+        assert_eq!(speech.plain_text(), "Viewer пять долларов спасибо за стрим");
+        assert_eq!(
+            speech.render(SsmlSupport::Full),
+            "<speak><prosody pitch=\"+2st\">Viewer <break time=\"400ms\"/> \
+             пять долларов <break time=\"600ms\"/> спасибо за стрим</prosody></speak>"
+        );
+
+        let long = alert_speech(&super_chat(&"очень ".repeat(40)));
+        assert_eq!(long.prosody, MessageClass::LongDonation.prosody());
+        assert!(long.render(SsmlSupport::Full).starts_with("<speak><prosody rate=\"85%\" pitch=\"+2st\">"));
+    }
+
+    #[test]
+    fn unpaid_alert_reads_its_message() {
+        let raid = AlertPayload {
+            alert_type: "raid".to_string(),
+            user_name: "Raider".to_string(),
+            message: "Raider is raiding with 5 viewers!".to_string(),
+            count: Some(5),
+            ..Default::default()
+        };
+        let mut speech = alert_speech(&raid);
+        assert_eq!(speech.plain_text(), "Raider is raiding with 5 viewers!");
+
+        for text in speech.spoken_text_mut() {
+            *text = text.to_uppercase();
+        }
+        assert_eq!(speech.render(SsmlSupport::Breaks), "RAIDER IS RAIDING WITH 5 VIEWERS!");
+    }
+}
@@ -0,0 +1,86 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tokio::sync::RwLock;
+
+/// Resolve a file inside the app data directory, creating the directory if needed
+pub fn data_file(data_dir: &Path, name: &str) -> PathBuf {
+    if let Err(e) = std::fs::create_dir_all(data_dir) {
+        log::warn!("Failed to create data directory {}: {}", data_dir.display(), e);
+    }
+    data_dir.join(name)
+}
+
+/// Read a JSON file, returning `None` when it is missing or unreadable
+pub fn load_json<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            log::warn!("Failed to read {}: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    match serde_json::from_str(&contents) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            log::warn!("Ignoring corrupt {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Write a JSON file atomically so a crash never leaves a half-written file behind
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Failed to serialize {}: {}", path.display(), e))?;
+
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, json)
+        .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    std::fs::rename(&tmp, path)
+        .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+}
+
+/// A single value kept in memory and mirrored to a JSON file on every change.
+///
+/// Without a path (no app data directory, or in tests) it simply lives in memory.
+pub struct JsonStore<T> {
+    path: Option<PathBuf>,
+    current: RwLock<Option<T>>,
+}
+
+impl<T: Clone + Serialize + DeserializeOwned> JsonStore<T> {
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let current = path.as_deref().and_then(load_json::<T>);
+        JsonStore {
+            path,
+            current: RwLock::new(current),
+        }
+    }
+
+    pub async fn get(&self) -> Option<T> {
+        self.current.read().await.clone()
+    }
+
+    pub async fn set(&self, value: T) -> Result<(), String> {
+        if let Some(ref path) = self.path {
+            save_json(path, &value)?;
+        }
+        *self.current.write().await = Some(value);
+        Ok(())
+    }
+
+    pub async fn clear(&self) -> Result<(), String> {
+        *self.current.write().await = None;
+        if let Some(ref path) = self.path {
+            match std::fs::remove_file(path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("Failed to remove {}: {}", path.display(), e)),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,572 @@
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::audio_cache::{cache_key, AudioCache, CacheKeyParts};
+use crate::elevenlabs::{
+    ElevenLabsClient, ElevenLabsError, SpeechRequest, DEFAULT_MODEL_ID, DEFAULT_SIMILARITY_BOOST, DEFAULT_STABILITY,
+    DEFAULT_VOICE_ID, OUTPUT_FORMAT,
+};
+use crate::local_tts::{LocalTts, LocalTtsError, DEFAULT_LOCAL_VOICE};
+use crate::ssml::{Speech, SsmlSupport};
+use crate::storage::JsonStore;
+
+/// Per-provider limit; a hung provider must not hold up the rest of the chain
+const DEFAULT_PROVIDER_TIMEOUT: Duration = Duration::from_secs(20);
+const ELEVENLABS_VOICE_PREFIX: &str = "elevenlabs:";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SynthesisRequest {
+    pub text: String,
+    /// Provider-qualified voice id such as `elevenlabs:<id>` or `espeak:ru`.
+    /// Providers that do not own the voice fall back to their default.
+    #[serde(default)]
+    pub voice: Option<String>,
+    /// Structured version of `text`; providers that understand SSML get it rendered as markup
+    #[serde(default)]
+    pub speech: Option<Speech>,
+    /// Set by the registry when `text` holds markup rendered for the provider at hand
+    #[serde(skip)]
+    pub markup: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SynthesizedAudio {
+    pub provider: String,
+    pub mime_type: &'static str,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderVoice {
+    pub provider: String,
+    pub id: String,
+    pub name: String,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderHealth {
+    pub provider: String,
+    pub available: bool,
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TtsErrorKind {
+    /// Not configured or not installed
+    Unavailable,
+    QuotaExceeded,
+    RateLimited,
+    Timeout,
+    /// The request itself is bad; no other provider will do better
+    InvalidRequest,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TtsError {
+    pub provider: String,
+    pub kind: TtsErrorKind,
+    pub message: String,
+}
+
+impl TtsError {
+    fn new(provider: &str, kind: TtsErrorKind, message: impl fmt::Display) -> Self {
+        TtsError {
+            provider: provider.to_string(),
+            kind,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for TtsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.provider, self.message)
+    }
+}
+
+/// The voice, model and settings a provider would actually use for a request
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CacheIdentity {
+    pub voice: String,
+    pub model: String,
+    pub settings: String,
+}
+
+/// A speech backend. Futures are boxed so providers can live behind `dyn` in the registry.
+pub trait TtsProvider: Send + Sync {
+    fn id(&self) -> &'static str;
+    /// Everything besides the text that shapes the audio, so cache entries never mix voices
+    fn cache_identity(&self, request: &SynthesisRequest) -> CacheIdentity {
+        CacheIdentity {
+            voice: request.voice.clone().unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+    /// How much SSML the provider understands for this request's voice
+    fn ssml_support(&self, _request: &SynthesisRequest) -> SsmlSupport {
+        SsmlSupport::None
+    }
+    fn synthesize<'a>(&'a self, request: &'a SynthesisRequest) -> BoxFuture<'a, Result<SynthesizedAudio, TtsError>>;
+    fn list_voices(&self) -> BoxFuture<'_, Result<Vec<ProviderVoice>, TtsError>>;
+    fn health(&self) -> BoxFuture<'_, ProviderHealth>;
+}
+
+impl From<ElevenLabsError> for TtsError {
+    fn from(e: ElevenLabsError) -> Self {
+        let kind = match e {
+            ElevenLabsError::MissingApiKey | ElevenLabsError::Unauthorized { .. } => TtsErrorKind::Unavailable,
+            ElevenLabsError::QuotaExceeded { .. } => TtsErrorKind::QuotaExceeded,
+            ElevenLabsError::RateLimited { .. } => TtsErrorKind::RateLimited,
+            ElevenLabsError::InvalidRequest { .. } => TtsErrorKind::InvalidRequest,
+            ElevenLabsError::Api { .. } | ElevenLabsError::Network { .. } => TtsErrorKind::Failed,
+        };
+        TtsError::new("elevenlabs", kind, e)
+    }
+}
+
+fn elevenlabs_voice(request: &SynthesisRequest) -> Option<&str> {
+    request.voice.as_deref().and_then(|v| v.strip_prefix(ELEVENLABS_VOICE_PREFIX))
+}
+
+fn local_voice(request: &SynthesisRequest) -> &str {
+    request
+        .voice
+        .as_deref()
+        .filter(|v| v.starts_with("espeak:") || v.starts_with("piper:"))
+        .unwrap_or(DEFAULT_LOCAL_VOICE)
+}
+
+impl TtsProvider for ElevenLabsClient {
+    fn id(&self) -> &'static str {
+        "elevenlabs"
+    }
+
+    fn cache_identity(&self, request: &SynthesisRequest) -> CacheIdentity {
+        CacheIdentity {
+            voice: elevenlabs_voice(request).unwrap_or(DEFAULT_VOICE_ID).to_string(),
+            model: DEFAULT_MODEL_ID.to_string(),
+            settings: format!("{}/{}/{}", DEFAULT_STABILITY, DEFAULT_SIMILARITY_BOOST, OUTPUT_FORMAT),
+        }
+    }
+
+    fn ssml_support(&self, _request: &SynthesisRequest) -> SsmlSupport {
+        SsmlSupport::Breaks
+    }
+
+    fn synthesize<'a>(&'a self, request: &'a SynthesisRequest) -> BoxFuture<'a, Result<SynthesizedAudio, TtsError>> {
+        Box::pin(async move {
+            let speech = SpeechRequest {
+                text: request.text.clone(),
+                voice_id: elevenlabs_voice(request).map(|v| v.to_string()),
+                ..Default::default()
+            };
+            let data = self.text_to_speech(&speech).await?;
+            Ok(SynthesizedAudio { provider: self.id().to_string(), mime_type: "audio/mpeg", data })
+        })
+    }
+
+    fn list_voices(&self) -> BoxFuture<'_, Result<Vec<ProviderVoice>, TtsError>> {
+        Box::pin(async move {
+            let voices = ElevenLabsClient::list_voices(self).await?;
+            Ok(voices
+                .into_iter()
+                .map(|v| ProviderVoice {
+                    provider: self.id().to_string(),
+                    id: format!("{}{}", ELEVENLABS_VOICE_PREFIX, v.voice_id),
+                    name: v.name,
+                    language: v.labels.get("language").cloned(),
+                })
+                .collect())
+        })
+    }
+
+    fn health(&self) -> BoxFuture<'_, ProviderHealth> {
+        Box::pin(async move {
+            let (available, detail) = match self.usage().await {
+                Ok(usage) if usage.character_count >= usage.character_limit => {
+                    (false, Some("Character quota used up".to_string()))
+                }
+                Ok(usage) => (
+                    true,
+                    Some(format!("{} of {} characters used", usage.character_count, usage.character_limit)),
+                ),
+                Err(e) => (false, Some(e.to_string())),
+            };
+            ProviderHealth { provider: self.id().to_string(), available, detail }
+        })
+    }
+}
+
+impl From<LocalTtsError> for TtsError {
+    fn from(e: LocalTtsError) -> Self {
+        let kind = match e {
+            LocalTtsError::BinaryNotFound { .. } | LocalTtsError::VoiceNotFound { .. } => TtsErrorKind::Unavailable,
+            LocalTtsError::InvalidRequest { .. } => TtsErrorKind::InvalidRequest,
+            LocalTtsError::Timeout { .. } => TtsErrorKind::Timeout,
+            LocalTtsError::Failed { .. } => TtsErrorKind::Failed,
+        };
+        TtsError::new("local", kind, e)
+    }
+}
+
+impl TtsProvider for LocalTts {
+    fn id(&self) -> &'static str {
+        "local"
+    }
+
+    fn cache_identity(&self, request: &SynthesisRequest) -> CacheIdentity {
+        CacheIdentity {
+            voice: local_voice(request).to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn ssml_support(&self, request: &SynthesisRequest) -> SsmlSupport {
+        if local_voice(request).starts_with("espeak:") {
+            SsmlSupport::Full
+        } else {
+            SsmlSupport::None
+        }
+    }
+
+    fn synthesize<'a>(&'a self, request: &'a SynthesisRequest) -> BoxFuture<'a, Result<SynthesizedAudio, TtsError>> {
+        Box::pin(async move {
+            let data = if request.markup {
+                self.synthesize_ssml(&request.text, local_voice(request)).await?
+            } else {
+                LocalTts::synthesize(self, &request.text, local_voice(request)).await?
+            };
+            Ok(SynthesizedAudio { provider: self.id().to_string(), mime_type: "audio/wav", data })
+        })
+    }
+
+    fn list_voices(&self) -> BoxFuture<'_, Result<Vec<ProviderVoice>, TtsError>> {
+        Box::pin(async move {
+            let voices = LocalTts::list_voices(self).await?;
+            Ok(voices
+                .into_iter()
+                .map(|v| ProviderVoice {
+                    provider: self.id().to_string(),
+                    id: v.id,
+                    name: v.name,
+                    language: Some(v.language),
+                })
+                .collect())
+        })
+    }
+
+    fn health(&self) -> BoxFuture<'_, ProviderHealth> {
+        Box::pin(async move {
+            let (available, detail) = match LocalTts::list_voices(self).await {
+                Ok(voices) if voices.is_empty() => (false, Some("Neither espeak-ng nor Piper is installed".to_string())),
+                Ok(voices) => (true, Some(format!("{} voices installed", voices.len()))),
+                Err(e) => (false, Some(e.to_string())),
+            };
+            ProviderHealth { provider: self.id().to_string(), available, detail }
+        })
+    }
+}
+
+/// The ordered provider ids to try, persisted in the app data directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TtsChainConfig {
+    pub providers: Vec<String>,
+}
+
+pub type TtsChainStore = JsonStore<TtsChainConfig>;
+
+/// Every provider failed; `attempts` holds each failure in chain order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TtsChainError {
+    pub message: String,
+    pub attempts: Vec<TtsError>,
+}
+
+impl fmt::Display for TtsChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The request with its speech rendered for `provider`, or `None` when there is only plain text
+fn render_for(provider: &dyn TtsProvider, request: &SynthesisRequest) -> Option<SynthesisRequest> {
+    let speech = request.speech.as_ref()?;
+    let support = provider.ssml_support(request);
+    Some(SynthesisRequest {
+        text: speech.render(support),
+        voice: request.voice.clone(),
+        speech: None,
+        markup: support != SsmlSupport::None,
+    })
+}
+
+/// Registered providers plus the fallback chain that decides which ones to try, in order
+pub struct TtsRegistry {
+    providers: Vec<Arc<dyn TtsProvider>>,
+    chain: TtsChainStore,
+    timeout: Duration,
+    cache: Option<Arc<AudioCache>>,
+}
+
+impl TtsRegistry {
+    pub fn new(providers: Vec<Arc<dyn TtsProvider>>, chain: TtsChainStore) -> Self {
+        Self::with_timeout(providers, chain, DEFAULT_PROVIDER_TIMEOUT)
+    }
+
+    pub fn with_timeout(providers: Vec<Arc<dyn TtsProvider>>, chain: TtsChainStore, timeout: Duration) -> Self {
+        TtsRegistry { providers, chain, timeout, cache: None }
+    }
+
+    /// Serve repeated requests from `cache` instead of synthesizing (and paying) again
+    pub fn with_cache(mut self, cache: Arc<AudioCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    fn cache_key_for(&self, provider: &dyn TtsProvider, request: &SynthesisRequest) -> String {
+        let identity = provider.cache_identity(request);
+        cache_key(&CacheKeyParts {
+            provider: provider.id(),
+            voice: &identity.voice,
+            model: &identity.model,
+            settings: &identity.settings,
+            text: &request.text,
+        })
+    }
+
+    fn provider(&self, id: &str) -> Option<&Arc<dyn TtsProvider>> {
+        self.providers.iter().find(|p| p.id() == id)
+    }
+
+    /// The configured chain, or every provider in registration order when none is set
+    pub async fn chain(&self) -> Vec<String> {
+        match self.chain.get().await {
+            Some(config) => config.providers,
+            None => self.providers.iter().map(|p| p.id().to_string()).collect(),
+        }
+    }
+
+    pub async fn set_chain(&self, providers: Vec<String>) -> Result<(), String> {
+        if providers.is_empty() {
+            return Err("The fallback chain needs at least one provider".to_string());
+        }
+        if let Some(unknown) = providers.iter().find(|id| self.provider(id).is_none()) {
+            return Err(format!("Unknown TTS provider: {}", unknown));
+        }
+        self.chain.set(TtsChainConfig { providers }).await
+    }
+
+    /// Try each provider in the chain until one produces audio
+    pub async fn synthesize(&self, request: &SynthesisRequest) -> Result<SynthesizedAudio, TtsChainError> {
+        let mut attempts = Vec::new();
+
+        for id in self.chain().await {
+            let Some(provider) = self.provider(&id) else {
+                continue;
+            };
+
+            let rendered = render_for(provider.as_ref(), request);
+            let request = rendered.as_ref().unwrap_or(request);
+
+            let key = self.cache.as_ref().map(|_| self.cache_key_for(provider.as_ref(), request));
+            if let (Some(cache), Some(key)) = (&self.cache, &key) {
+                if let Some((data, mime_type)) = cache.get(key).await {
+                    return Ok(SynthesizedAudio { provider: id, mime_type, data });
+                }
+            }
+
+            let result = tokio::time::timeout(self.timeout, provider.synthesize(request))
+                .await
+                .unwrap_or_else(|_| {
+                    Err(TtsError::new(&id, TtsErrorKind::Timeout, format!("No audio after {:?}", self.timeout)))
+                });
+
+            match result {
+                Ok(audio) => {
+                    if let (Some(cache), Some(key)) = (&self.cache, &key) {
+                        cache.put(key, &audio.data, audio.mime_type).await;
+                    }
+                    if !attempts.is_empty() {
+                        log::info!("Synthesized with fallback provider {} after {} failure(s)", id, attempts.len());
+                    }
+                    return Ok(audio);
+                }
+                Err(e) if e.kind == TtsErrorKind::InvalidRequest => {
+                    attempts.push(e);
+                    break;
+                }
+                Err(e) => {
+                    log::warn!("TTS provider failed, trying the next one: {}", e);
+                    attempts.push(e);
+                }
+            }
+        }
+
+        let message = match attempts.last() {
+            Some(last) => format!("All TTS providers failed, last error: {}", last),
+            None => "No TTS provider configured".to_string(),
+        };
+        Err(TtsChainError { message, attempts })
+    }
+
+    /// Voices of every registered provider; providers that cannot list voices are skipped
+    pub async fn list_voices(&self) -> Vec<ProviderVoice> {
+        let mut voices = Vec::new();
+        for provider in &self.providers {
+            match provider.list_voices().await {
+                Ok(list) => voices.extend(list),
+                Err(e) => log::info!("Skipping voices of {}: {}", provider.id(), e),
+            }
+        }
+        voices
+    }
+
+    pub async fn health(&self) -> Vec<ProviderHealth> {
+        let mut health = Vec::new();
+        for provider in &self.providers {
+            health.push(provider.health().await);
+        }
+        health
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct FakeProvider {
+        id: &'static str,
+        result: Result<(), TtsErrorKind>,
+        delay: Duration,
+        calls: AtomicUsize,
+    }
+
+    impl FakeProvider {
+        fn new(id: &'static str, result: Result<(), TtsErrorKind>) -> Arc<Self> {
+            Arc::new(FakeProvider { id, result, delay: Duration::ZERO, calls: AtomicUsize::new(0) })
+        }
+    }
+
+    impl TtsProvider for FakeProvider {
+        fn id(&self) -> &'static str {
+            self.id
+        }
+
+        fn synthesize<'a>(&'a self, request: &'a SynthesisRequest) -> BoxFuture<'a, Result<SynthesizedAudio, TtsError>> {
+            Box::pin(async move {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(self.delay).await;
+                match self.result {
+                    Ok(()) => Ok(SynthesizedAudio {
+                        provider: self.id.to_string(),
+                        mime_type: "audio/wav",
+                        data: request.text.as_bytes().to_vec(),
+                    }),
+                    Err(kind) => Err(TtsError::new(self.id, kind, "fake failure")),
+                }
+            })
+        }
+
+        fn list_voices(&self) -> BoxFuture<'_, Result<Vec<ProviderVoice>, TtsError>> {
+            Box::pin(async move { Ok(Vec::new()) })
+        }
+
+        fn health(&self) -> BoxFuture<'_, ProviderHealth> {
+            Box::pin(async move { ProviderHealth { provider: self.id.to_string(), available: self.result.is_ok(), detail: None } })
+        }
+    }
+
+    fn request() -> SynthesisRequest {
+        SynthesisRequest { text: "привет".to_string(), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn falls_back_when_quota_is_exceeded() {
+        let cloud = FakeProvider::new("cloud", Err(TtsErrorKind::QuotaExceeded));
+        let local = FakeProvider::new("local", Ok(()));
+        let registry = TtsRegistry::new(vec![cloud.clone(), local.clone()], TtsChainStore::load(None));
+
+        let audio = registry.synthesize(&request()).await.unwrap();
+        assert_eq!(audio.provider, "local");
+        assert_eq!(audio.data, "привет".as_bytes());
+        assert_eq!(cloud.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn falls_back_when_a_provider_hangs() {
+        let slow = Arc::new(FakeProvider {
+            id: "slow",
+            result: Ok(()),
+            delay: Duration::from_secs(10),
+            calls: AtomicUsize::new(0),
+        });
+        let local = FakeProvider::new("local", Ok(()));
+        let registry = TtsRegistry::with_timeout(vec![slow, local], TtsChainStore::load(None), Duration::from_millis(50));
+
+        assert_eq!(registry.synthesize(&request()).await.unwrap().provider, "local");
+    }
+
+    #[tokio::test]
+    async fn chain_order_and_invalid_requests() {
+        let first = FakeProvider::new("first", Ok(()));
+        let second = FakeProvider::new("second", Err(TtsErrorKind::InvalidRequest));
+        let registry = TtsRegistry::new(vec![first.clone(), second.clone()], TtsChainStore::load(None));
+
+        assert!(registry.set_chain(vec!["nope".to_string()]).await.is_err());
+        registry.set_chain(vec!["second".to_string(), "first".to_string()]).await.unwrap();
+
+        // A bad request is not retried elsewhere
+        let error = registry.synthesize(&request()).await.unwrap_err();
+        assert_eq!(error.attempts.len(), 1);
+        assert_eq!(error.attempts[0].kind, TtsErrorKind::InvalidRequest);
+        assert_eq!(first.calls.load(Ordering::SeqCst), 0);
+
+        let health = registry.health().await;
+        assert!(health[0].available);
+        assert!(!health[1].available);
+    }
+
+    #[tokio::test]
+    async fn speech_degrades_to_plain_text() {
+        let plain = FakeProvider::new("plain", Ok(()));
+        let registry = TtsRegistry::new(vec![plain], TtsChainStore::load(None));
+        let request = SynthesisRequest {
+            text: "Bob привет".to_string(),
+            speech: Some(crate::ssml::chat_speech("Bob", "привет")),
+            ..Default::default()
+        };
+        assert_eq!(registry.synthesize(&request).await.unwrap().data, "Bob привет".as_bytes());
+    }
+
+    #[tokio::test]
+    async fn cache_skips_the_provider_on_repeat() {
+        let dir = std::env::temp_dir().join(format!("streamtts-tts-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let cloud = FakeProvider::new("cloud", Ok(()));
+        let registry = TtsRegistry::new(vec![cloud.clone()], TtsChainStore::load(None))
+            .with_cache(Arc::new(AudioCache::open(dir.clone(), 1024)));
+
+        let first = registry.synthesize(&request()).await.unwrap();
+        let again = registry
+            .synthesize(&SynthesisRequest { text: " привет ".to_string(), ..Default::default() })
+            .await
+            .unwrap();
+        assert_eq!(first.data, again.data);
+        assert_eq!(cloud.calls.load(Ordering::SeqCst), 1);
+
+        // A different voice is a different entry
+        registry
+            .synthesize(&SynthesisRequest { text: "привет".to_string(), voice: Some("other".to_string()), ..Default::default() })
+            .await
+            .unwrap();
+        assert_eq!(cloud.calls.load(Ordering::SeqCst), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,371 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use tokio::sync::broadcast;
+
+use crate::ssml::Speech;
+
+const DEFAULT_MAX_LENGTH: usize = 50;
+
+/// Higher priorities are always spoken first; alerts jump ahead of chat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueuePriority {
+    Chat,
+    Alert,
+}
+
+/// What happens to a new item when the queue is already full of items of the same priority.
+///
+/// A higher priority item always pushes out a lower priority one, whatever the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropPolicy {
+    /// Make room by dropping the oldest waiting item
+    DropOldest,
+    /// Keep what is waiting and reject the new item
+    DropNewest,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueItem {
+    pub id: u64,
+    pub platform: String,
+    pub user_name: String,
+    pub text: String,
+    pub priority: QueuePriority,
+    pub enqueued_at: DateTime<Utc>,
+    /// How to read the item aloud; pass it back with the synthesis request
+    pub speech: Option<Speech>,
+    /// Voice assigned to the viewer; `None` uses the default
+    pub voice: Option<String>,
+}
+
+/// Input for [`TtsQueue::enqueue`]; the queue assigns the id and timestamp
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewQueueItem {
+    pub platform: String,
+    pub user_name: String,
+    pub text: String,
+    pub priority: QueuePriority,
+    #[serde(default)]
+    pub speech: Option<Speech>,
+    #[serde(default)]
+    pub voice: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    Completed,
+    Skipped,
+    Cleared,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueSnapshot {
+    pub current: Option<QueueItem>,
+    pub pending: Vec<QueueItem>,
+    pub paused: bool,
+    pub max_length: usize,
+    pub drop_policy: DropPolicy,
+}
+
+#[derive(Debug, Clone)]
+pub enum QueueEvent {
+    Updated(QueueSnapshot),
+    ItemStarted(QueueItem),
+    ItemFinished { item: QueueItem, reason: FinishReason },
+}
+
+/// The authoritative speech queue.
+///
+/// Playback happens elsewhere: a player listens for `ItemStarted`, speaks the item and
+/// reports back through [`TtsQueue::finish`], which starts the next one.
+pub struct TtsQueue {
+    pending: VecDeque<QueueItem>,
+    current: Option<QueueItem>,
+    paused: bool,
+    max_length: usize,
+    drop_policy: DropPolicy,
+    next_id: u64,
+    events: broadcast::Sender<QueueEvent>,
+}
+
+impl TtsQueue {
+    pub fn new(events: broadcast::Sender<QueueEvent>) -> Self {
+        TtsQueue {
+            pending: VecDeque::new(),
+            current: None,
+            paused: false,
+            max_length: DEFAULT_MAX_LENGTH,
+            drop_policy: DropPolicy::DropOldest,
+            next_id: 1,
+            events,
+        }
+    }
+
+    /// Queue an item, returning its id, or `None` when the drop policy rejected it
+    pub fn enqueue(&mut self, new: NewQueueItem) -> Option<u64> {
+        if self.pending.len() >= self.max_length && !self.make_room(new.priority) {
+            log::info!("TTS queue full, dropped message from {}", new.user_name);
+            return None;
+        }
+
+        let item = QueueItem {
+            id: self.next_id,
+            platform: new.platform,
+            user_name: new.user_name,
+            text: new.text,
+            priority: new.priority,
+            enqueued_at: Utc::now(),
+            speech: new.speech,
+            voice: new.voice,
+        };
+        self.next_id += 1;
+        let id = item.id;
+
+        // Stable insert: after every item of the same or higher priority
+        let position = self
+            .pending
+            .iter()
+            .position(|queued| queued.priority < item.priority)
+            .unwrap_or(self.pending.len());
+        self.pending.insert(position, item);
+
+        self.advance();
+        self.publish();
+        Some(id)
+    }
+
+    /// Evict one waiting item so an item of `priority` fits; `false` when nothing may go
+    fn make_room(&mut self, priority: QueuePriority) -> bool {
+        let Some(lowest) = self.pending.iter().map(|item| item.priority).min() else {
+            return false;
+        };
+
+        let victim = if lowest < priority {
+            // Newest of the lowest priority, so the oldest chat still gets its turn
+            self.pending.iter().rposition(|item| item.priority == lowest)
+        } else if lowest == priority && self.drop_policy == DropPolicy::DropOldest {
+            self.pending.iter().position(|item| item.priority == lowest)
+        } else {
+            None
+        };
+
+        match victim.and_then(|index| self.pending.remove(index)) {
+            Some(dropped) => {
+                log::info!("TTS queue full, dropped message {} from {}", dropped.id, dropped.user_name);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop the current item; the next one starts unless the queue is paused
+    pub fn skip(&mut self) -> Option<QueueItem> {
+        let skipped = self.finish_current(FinishReason::Skipped);
+        self.advance();
+        self.publish();
+        skipped
+    }
+
+    /// Report that the player is done with item `id`
+    pub fn finish(&mut self, id: u64, reason: FinishReason) -> Result<(), String> {
+        match self.current {
+            Some(ref current) if current.id == id => {}
+            _ => return Err(format!("Queue item {} is not playing", id)),
+        }
+        self.finish_current(reason);
+        self.advance();
+        self.publish();
+        Ok(())
+    }
+
+    /// Stop starting new items; the player pauses whatever is playing
+    pub fn pause(&mut self) {
+        if !self.paused {
+            self.paused = true;
+            self.publish();
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.paused {
+            self.paused = false;
+            self.advance();
+            self.publish();
+        }
+    }
+
+    /// Drop everything waiting and stop the current item
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.finish_current(FinishReason::Cleared);
+        self.publish();
+    }
+
+    pub fn set_limits(&mut self, max_length: usize, drop_policy: DropPolicy) {
+        self.max_length = max_length.max(1);
+        self.drop_policy = drop_policy;
+        // Shrinking evicts what a full queue would, lowest priority first
+        while self.pending.len() > self.max_length {
+            let lowest = self.pending.iter().map(|item| item.priority).min().unwrap_or(QueuePriority::Chat);
+            if !self.make_room(lowest) {
+                if let Some(dropped) = self.pending.pop_back() {
+                    log::info!("TTS queue shrunk, dropped message {} from {}", dropped.id, dropped.user_name);
+                }
+            }
+        }
+        self.publish();
+    }
+
+    pub fn snapshot(&self) -> QueueSnapshot {
+        QueueSnapshot {
+            current: self.current.clone(),
+            pending: self.pending.iter().cloned().collect(),
+            paused: self.paused,
+            max_length: self.max_length,
+            drop_policy: self.drop_policy,
+        }
+    }
+
+    fn finish_current(&mut self, reason: FinishReason) -> Option<QueueItem> {
+        let item = self.current.take()?;
+        let _ = self.events.send(QueueEvent::ItemFinished { item: item.clone(), reason });
+        Some(item)
+    }
+
+    fn advance(&mut self) {
+        if self.paused || self.current.is_some() {
+            return;
+        }
+        if let Some(next) = self.pending.pop_front() {
+            let _ = self.events.send(QueueEvent::ItemStarted(next.clone()));
+            self.current = Some(next);
+        }
+    }
+
+    fn publish(&self) {
+        let _ = self.events.send(QueueEvent::Updated(self.snapshot()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(user_name: &str, priority: QueuePriority) -> NewQueueItem {
+        NewQueueItem {
+            platform: "twitch".to_string(),
+            user_name: user_name.to_string(),
+            text: format!("hello from {}", user_name),
+            priority,
+            speech: None,
+            voice: None,
+        }
+    }
+
+    fn pending_users(queue: &TtsQueue) -> Vec<String> {
+        queue.snapshot().pending.into_iter().map(|i| i.user_name).collect()
+    }
+
+    fn started(receiver: &mut broadcast::Receiver<QueueEvent>) -> Vec<String> {
+        let mut users = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            if let QueueEvent::ItemStarted(item) = event {
+                users.push(item.user_name);
+            }
+        }
+        users
+    }
+
+    #[test]
+    fn alerts_jump_ahead_of_chat() {
+        let (events, mut receiver) = broadcast::channel(64);
+        let mut queue = TtsQueue::new(events);
+        queue.enqueue(item("a", QueuePriority::Chat));
+        queue.enqueue(item("b", QueuePriority::Chat));
+        queue.enqueue(item("alert1", QueuePriority::Alert));
+        queue.enqueue(item("alert2", QueuePriority::Alert));
+
+        // "a" started immediately; the rest wait in priority order
+        assert_eq!(started(&mut receiver), vec!["a"]);
+        assert_eq!(pending_users(&queue), vec!["alert1", "alert2", "b"]);
+
+        let current = queue.snapshot().current.unwrap().id;
+        queue.finish(current, FinishReason::Completed).unwrap();
+        assert_eq!(started(&mut receiver), vec!["alert1"]);
+        assert!(queue.finish(current, FinishReason::Completed).is_err());
+    }
+
+    #[test]
+    fn pause_holds_the_next_item_until_resume() {
+        let (events, mut receiver) = broadcast::channel(64);
+        let mut queue = TtsQueue::new(events);
+        queue.enqueue(item("a", QueuePriority::Chat));
+        queue.enqueue(item("b", QueuePriority::Chat));
+        queue.pause();
+
+        let skipped = queue.skip().unwrap();
+        assert_eq!(skipped.user_name, "a");
+        assert!(queue.snapshot().current.is_none());
+        assert_eq!(started(&mut receiver), vec!["a"]);
+
+        queue.resume();
+        assert_eq!(started(&mut receiver), vec!["b"]);
+    }
+
+    #[test]
+    fn drop_policies_when_full() {
+        let (events, _receiver) = broadcast::channel(64);
+        let mut queue = TtsQueue::new(events);
+        queue.pause();
+        for user in ["a", "b", "c", "d"] {
+            queue.enqueue(item(user, QueuePriority::Chat));
+        }
+        // Shrinking follows the policy given with the new length
+        queue.set_limits(2, DropPolicy::DropOldest);
+        assert_eq!(pending_users(&queue), vec!["c", "d"]);
+        queue.enqueue(item("e", QueuePriority::Chat));
+        assert_eq!(pending_users(&queue), vec!["d", "e"]);
+
+        queue.set_limits(2, DropPolicy::DropNewest);
+        assert!(queue.enqueue(item("f", QueuePriority::Chat)).is_none());
+        assert_eq!(pending_users(&queue), vec!["d", "e"]);
+
+        // Alerts always make room by pushing out the newest chat
+        assert!(queue.enqueue(item("alert", QueuePriority::Alert)).is_some());
+        assert_eq!(pending_users(&queue), vec!["alert", "d"]);
+        queue.enqueue(item("alert2", QueuePriority::Alert));
+        assert!(queue.enqueue(item("g", QueuePriority::Chat)).is_none());
+        assert_eq!(pending_users(&queue), vec!["alert", "alert2"]);
+
+        queue.set_limits(4, DropPolicy::DropNewest);
+        queue.enqueue(item("h", QueuePriority::Chat));
+        queue.enqueue(item("i", QueuePriority::Chat));
+        queue.set_limits(3, DropPolicy::DropNewest);
+        assert_eq!(pending_users(&queue), vec!["alert", "alert2", "h"]);
+        // Chat goes before any alert does
+        queue.set_limits(1, DropPolicy::DropOldest);
+        assert_eq!(pending_users(&queue), vec!["alert2"]);
+    }
+
+    #[test]
+    fn clear_stops_everything() {
+        let (events, mut receiver) = broadcast::channel(64);
+        let mut queue = TtsQueue::new(events);
+        queue.enqueue(item("a", QueuePriority::Chat));
+        queue.enqueue(item("b", QueuePriority::Chat));
+        queue.clear();
+
+        let snapshot = queue.snapshot();
+        assert!(snapshot.current.is_none());
+        assert!(snapshot.pending.is_empty());
+        let cleared = std::iter::from_fn(|| receiver.try_recv().ok()).any(|event| {
+            matches!(event, QueueEvent::ItemFinished { ref item, reason: FinishReason::Cleared } if item.user_name == "a")
+        });
+        assert!(cleared);
+    }
+}
@@ -0,0 +1,275 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::storage::JsonStore;
+
+const DEFAULT_HELIX_BASE_URL: &str = "https://api.twitch.tv/helix";
+const DEFAULT_ID_BASE_URL: &str = "https://id.twitch.tv";
+
+fn get_twitch_helix_base_url() -> String {
+    std::env::var("TWITCH_HELIX_BASE_URL")
+        .unwrap_or_else(|_| DEFAULT_HELIX_BASE_URL.to_string())
+}
+
+fn get_twitch_id_base_url() -> String {
+    std::env::var("TWITCH_ID_BASE_URL")
+        .unwrap_or_else(|_| DEFAULT_ID_BASE_URL.to_string())
+}
+
+/// A validated Twitch user token together with the identity it belongs to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwitchCredentials {
+    pub access_token: String,
+    pub client_id: String,
+    pub user_id: String,
+    pub login: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ValidateResponse {
+    client_id: String,
+    login: String,
+    user_id: String,
+    #[serde(default)]
+    scopes: Vec<String>,
+}
+
+/// Subscription object as returned by `GET/POST /eventsub/subscriptions`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HelixSubscription {
+    pub id: String,
+    pub status: String,
+    pub r#type: String,
+    pub version: String,
+    pub condition: serde_json::Value,
+    #[serde(default)]
+    pub transport: serde_json::Value,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub cost: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct HelixSubscriptionList {
+    data: Vec<HelixSubscription>,
+    #[serde(default)]
+    pagination: HelixPagination,
+}
+
+#[derive(Debug, Deserialize)]
+struct HelixFollower {
+    followed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HelixFollowerList {
+    data: Vec<HelixFollower>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct HelixPagination {
+    cursor: Option<String>,
+}
+
+/// Minimal Helix client for the EventSub subscription endpoints
+#[derive(Clone)]
+pub struct HelixClient {
+    http: reqwest::Client,
+    helix_base_url: String,
+    id_base_url: String,
+}
+
+impl HelixClient {
+    pub fn new() -> Self {
+        Self::with_base_urls(get_twitch_helix_base_url(), get_twitch_id_base_url())
+    }
+
+    pub fn with_base_urls(helix_base_url: String, id_base_url: String) -> Self {
+        HelixClient {
+            http: reqwest::Client::new(),
+            helix_base_url: helix_base_url.trim_end_matches('/').to_string(),
+            id_base_url: id_base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Resolve the client id and user behind a token via `oauth2/validate`
+    pub async fn validate_token(&self, access_token: &str) -> Result<TwitchCredentials, String> {
+        let response = self
+            .http
+            .get(format!("{}/oauth2/validate", self.id_base_url))
+            .header("Authorization", format!("OAuth {}", access_token))
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach Twitch: {}", e))?;
+
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return Err("Twitch token is invalid or expired".to_string());
+        }
+        if !response.status().is_success() {
+            return Err(format!("Twitch token validation failed with status {}", response.status()));
+        }
+
+        let validated: ValidateResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid token validation response: {}", e))?;
+
+        Ok(TwitchCredentials {
+            access_token: access_token.to_string(),
+            client_id: validated.client_id,
+            user_id: validated.user_id,
+            login: validated.login,
+            scopes: validated.scopes,
+        })
+    }
+
+    pub async fn create_subscription(
+        &self,
+        credentials: &TwitchCredentials,
+        subscription_type: &str,
+        version: &str,
+        condition: serde_json::Value,
+        transport: serde_json::Value,
+    ) -> Result<HelixSubscription, String> {
+        let body = serde_json::json!({
+            "type": subscription_type,
+            "version": version,
+            "condition": condition,
+            "transport": transport,
+        });
+
+        let response = self
+            .http
+            .post(format!("{}/eventsub/subscriptions", self.helix_base_url))
+            .bearer_auth(&credentials.access_token)
+            .header("Client-Id", &credentials.client_id)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach Twitch: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let message = helix_error_message(response).await;
+            return Err(format!("Creating {} subscription failed ({}): {}", subscription_type, status, message));
+        }
+
+        let mut created: HelixSubscriptionList = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid subscription response: {}", e))?;
+
+        created
+            .data
+            .pop()
+            .ok_or_else(|| "Twitch returned no subscription".to_string())
+    }
+
+    /// List every subscription owned by the token's client, following pagination
+    pub async fn list_subscriptions(&self, credentials: &TwitchCredentials) -> Result<Vec<HelixSubscription>, String> {
+        let mut subscriptions = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let mut request = self
+                .http
+                .get(format!("{}/eventsub/subscriptions", self.helix_base_url))
+                .bearer_auth(&credentials.access_token)
+                .header("Client-Id", &credentials.client_id);
+            if let Some(ref after) = cursor {
+                request = request.query(&[("after", after)]);
+            }
+
+            let response = request
+                .send()
+                .await
+                .map_err(|e| format!("Failed to reach Twitch: {}", e))?;
+
+            let status = response.status();
+            if !status.is_success() {
+                let message = helix_error_message(response).await;
+                return Err(format!("Listing subscriptions failed ({}): {}", status, message));
+            }
+
+            let page: HelixSubscriptionList = response
+                .json()
+                .await
+                .map_err(|e| format!("Invalid subscription list response: {}", e))?;
+
+            subscriptions.extend(page.data);
+            match page.pagination.cursor {
+                Some(next) if !next.is_empty() => cursor = Some(next),
+                _ => break,
+            }
+        }
+
+        Ok(subscriptions)
+    }
+
+    pub async fn delete_subscription(&self, credentials: &TwitchCredentials, id: &str) -> Result<(), String> {
+        let response = self
+            .http
+            .delete(format!("{}/eventsub/subscriptions", self.helix_base_url))
+            .bearer_auth(&credentials.access_token)
+            .header("Client-Id", &credentials.client_id)
+            .query(&[("id", id)])
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach Twitch: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let message = helix_error_message(response).await;
+            return Err(format!("Deleting subscription failed ({}): {}", status, message));
+        }
+        Ok(())
+    }
+
+    /// When `user_id` followed the token's channel, `None` if they do not follow it.
+    ///
+    /// Needs the `moderator:read:followers` scope.
+    pub async fn get_followed_at(&self, credentials: &TwitchCredentials, user_id: &str) -> Result<Option<DateTime<Utc>>, String> {
+        let response = self
+            .http
+            .get(format!("{}/channels/followers", self.helix_base_url))
+            .bearer_auth(&credentials.access_token)
+            .header("Client-Id", &credentials.client_id)
+            .query(&[("broadcaster_id", credentials.user_id.as_str()), ("user_id", user_id)])
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach Twitch: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let message = helix_error_message(response).await;
+            return Err(format!("Follower lookup failed ({}): {}", status, message));
+        }
+
+        let followers: HelixFollowerList = response
+            .json()
+            .await
+            .map_err(|e| format!("Invalid follower response: {}", e))?;
+        Ok(followers.data.into_iter().next().map(|f| f.followed_at))
+    }
+}
+
+impl Default for HelixClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Helix errors look like `{"error": "Conflict", "status": 409, "message": "subscription already exists"}`
+async fn helix_error_message(response: reqwest::Response) -> String {
+    let body = response.text().await.unwrap_or_default();
+    serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|v| v["message"].as_str().map(|m| m.to_string()))
+        .unwrap_or(body)
+}
+
+/// Twitch credentials persisted in the app data directory
+pub type TwitchCredentialStore = JsonStore<TwitchCredentials>;
@@ -0,0 +1,544 @@
+use chrono::{TimeZone, Utc};
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::chat::{ChatBadge, ChatEmote, ChatMessage, ChatRoles};
+use crate::twitch_api::TwitchCredentialStore;
+
+const DEFAULT_TWITCH_IRC_WS_URL: &str = "wss://irc-ws.chat.twitch.tv:443";
+/// Twitch pings roughly every five minutes; silence past this means the socket is dead
+const READ_TIMEOUT: Duration = Duration::from_secs(6 * 60);
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+fn get_twitch_irc_ws_url() -> String {
+    std::env::var("TWITCH_IRC_WS_URL")
+        .unwrap_or_else(|_| DEFAULT_TWITCH_IRC_WS_URL.to_string())
+}
+
+/// One parsed IRC line: `@tags :prefix COMMAND params :trailing`
+#[derive(Debug, Default, PartialEq)]
+pub struct IrcMessage {
+    pub tags: HashMap<String, String>,
+    pub prefix: Option<String>,
+    pub command: String,
+    pub params: Vec<String>,
+}
+
+impl IrcMessage {
+    pub fn parse(line: &str) -> Option<IrcMessage> {
+        let mut rest = line.trim_end_matches(['\r', '\n']);
+        let mut message = IrcMessage::default();
+
+        if let Some(tagged) = rest.strip_prefix('@') {
+            let (tags, remainder) = tagged.split_once(' ')?;
+            for tag in tags.split(';') {
+                let (key, value) = tag.split_once('=').unwrap_or((tag, ""));
+                message.tags.insert(key.to_string(), unescape_tag_value(value));
+            }
+            rest = remainder.trim_start();
+        }
+
+        if let Some(prefixed) = rest.strip_prefix(':') {
+            let (prefix, remainder) = prefixed.split_once(' ')?;
+            message.prefix = Some(prefix.to_string());
+            rest = remainder.trim_start();
+        }
+
+        let (middle, trailing) = match rest.split_once(" :") {
+            Some((middle, trailing)) => (middle, Some(trailing)),
+            None => (rest, None),
+        };
+
+        let mut parts = middle.split(' ').filter(|p| !p.is_empty());
+        message.command = parts.next()?.to_string();
+        message.params = parts.map(|p| p.to_string()).collect();
+        if let Some(trailing) = trailing {
+            message.params.push(trailing.to_string());
+        }
+
+        Some(message)
+    }
+
+    fn tag(&self, key: &str) -> Option<&str> {
+        self.tags.get(key).map(|v| v.as_str()).filter(|v| !v.is_empty())
+    }
+
+    /// Nick part of `nick!user@host`
+    fn nick(&self) -> Option<&str> {
+        self.prefix.as_deref().map(|p| p.split('!').next().unwrap_or(p))
+    }
+}
+
+/// Undo IRCv3 tag escaping (`\s`, `\:`, `\\`, `\r`, `\n`)
+fn unescape_tag_value(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some(':') => out.push(';'),
+            Some('\\') => out.push('\\'),
+            Some('r') => out.push('\r'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+/// `broadcaster/1,subscriber/12`
+fn parse_badges(value: Option<&str>) -> Vec<ChatBadge> {
+    value
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|badge| {
+            let (name, version) = badge.split_once('/')?;
+            Some(ChatBadge {
+                name: name.to_string(),
+                version: version.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Roles from the badges plus the `mod`, `vip` and `subscriber` tags
+fn roles(message: &IrcMessage, badges: &[ChatBadge]) -> ChatRoles {
+    let has_badge = |names: &[&str]| badges.iter().any(|b| names.contains(&b.name.as_str()));
+    ChatRoles {
+        broadcaster: has_badge(&["broadcaster"]),
+        moderator: message.tag("mod") == Some("1") || has_badge(&["moderator"]),
+        vip: message.tag("vip").is_some() || has_badge(&["vip"]),
+        subscriber: message.tag("subscriber") == Some("1") || has_badge(&["subscriber", "founder"]),
+        followed_at: None,
+    }
+}
+
+/// `25:0-4,12-16/1902:6-10`
+fn parse_emotes(value: Option<&str>) -> Vec<ChatEmote> {
+    let mut emotes: Vec<ChatEmote> = value
+        .unwrap_or("")
+        .split('/')
+        .filter_map(|emote| emote.split_once(':'))
+        .flat_map(|(id, ranges)| {
+            ranges.split(',').filter_map(move |range| {
+                let (start, end) = range.split_once('-')?;
+                Some(ChatEmote {
+                    id: id.to_string(),
+                    start: start.parse().ok()?,
+                    end: end.parse().ok()?,
+                })
+            })
+        })
+        .collect();
+    emotes.sort_by_key(|e| e.start);
+    emotes
+}
+
+/// Turn a tagged PRIVMSG into a chat message; other commands yield `None`
+pub fn parse_privmsg(message: &IrcMessage) -> Option<ChatMessage> {
+    if message.command != "PRIVMSG" || message.params.len() < 2 {
+        return None;
+    }
+
+    let channel = message.params[0].trim_start_matches('#').to_string();
+    let raw_text = &message.params[1];
+    let (text, is_action) = match raw_text
+        .strip_prefix("\u{1}ACTION ")
+        .map(|t| t.trim_end_matches('\u{1}'))
+    {
+        Some(action) => (action.to_string(), true),
+        None => (raw_text.to_string(), false),
+    };
+
+    let user_name = message.nick().unwrap_or("").to_string();
+    let display_name = message
+        .tag("display-name")
+        .map(|d| d.to_string())
+        .unwrap_or_else(|| user_name.clone());
+
+    let badges = parse_badges(message.tag("badges"));
+
+    Some(ChatMessage {
+        platform: "twitch".to_string(),
+        id: message.tag("id").unwrap_or("").to_string(),
+        channel,
+        user_id: message.tag("user-id").unwrap_or("").to_string(),
+        user_name,
+        display_name,
+        text,
+        color: message.tag("color").map(|c| c.to_string()),
+        roles: roles(message, &badges),
+        badges,
+        emotes: parse_emotes(message.tag("emotes")),
+        bits: message.tag("bits").and_then(|b| b.parse().ok()),
+        msg_id: message.tag("msg-id").map(|m| m.to_string()),
+        is_action,
+        timestamp: message
+            .tag("tmi-sent-ts")
+            .and_then(|ts| ts.parse::<i64>().ok())
+            .and_then(|ms| Utc.timestamp_millis_opt(ms).single()),
+    })
+}
+
+enum ChatSessionEnd {
+    /// The server sent RECONNECT; reconnect right away
+    Reconnect,
+    Lost { joined: bool },
+    AuthFailed,
+}
+
+/// Twitch chat over IRC-on-WebSocket, running in the backend so it survives webview reloads
+pub struct TwitchChatClient {
+    url: String,
+    sender: broadcast::Sender<ChatMessage>,
+    credentials: Arc<TwitchCredentialStore>,
+    task: Mutex<Option<(String, JoinHandle<()>)>>,
+}
+
+impl TwitchChatClient {
+    pub fn new(sender: broadcast::Sender<ChatMessage>, credentials: Arc<TwitchCredentialStore>) -> Self {
+        Self::with_url(get_twitch_irc_ws_url(), sender, credentials)
+    }
+
+    pub fn with_url(
+        url: String,
+        sender: broadcast::Sender<ChatMessage>,
+        credentials: Arc<TwitchCredentialStore>,
+    ) -> Self {
+        TwitchChatClient {
+            url,
+            sender,
+            credentials,
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Join a channel, replacing any previous connection
+    pub async fn start(&self, channel: &str) -> Result<(), String> {
+        let channel = channel.trim().trim_start_matches('#').to_lowercase();
+        if channel.is_empty() || !channel.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err("Invalid Twitch channel name".to_string());
+        }
+
+        let mut task = self.task.lock().await;
+        if let Some((current, handle)) = task.as_ref() {
+            if *current == channel && !handle.is_finished() {
+                return Ok(());
+            }
+        }
+        if let Some((_, handle)) = task.take() {
+            handle.abort();
+        }
+
+        log::info!("Connecting to Twitch chat for #{}", channel);
+        let handle = tokio::spawn(run_chat_loop(
+            self.url.clone(),
+            channel.clone(),
+            self.credentials.clone(),
+            self.sender.clone(),
+        ));
+        *task = Some((channel, handle));
+        Ok(())
+    }
+
+    pub async fn stop(&self) {
+        if let Some((channel, handle)) = self.task.lock().await.take() {
+            handle.abort();
+            log::info!("Disconnected from Twitch chat for #{}", channel);
+        }
+    }
+}
+
+async fn run_chat_loop(
+    url: String,
+    channel: String,
+    credentials: Arc<TwitchCredentialStore>,
+    sender: broadcast::Sender<ChatMessage>,
+) {
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        match run_chat_session(&url, &channel, &credentials, &sender).await {
+            ChatSessionEnd::Reconnect => {
+                log::info!("Twitch chat asked us to reconnect");
+                backoff = INITIAL_BACKOFF;
+            }
+            ChatSessionEnd::Lost { joined } => {
+                if joined {
+                    backoff = INITIAL_BACKOFF;
+                }
+                log::warn!("Twitch chat connection lost, reconnecting in {:?}", backoff);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            ChatSessionEnd::AuthFailed => {
+                log::error!("Twitch chat login failed, not reconnecting");
+                return;
+            }
+        }
+    }
+}
+
+/// Anonymous `justinfan` logins can read chat without a token
+fn anonymous_nick() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    format!("justinfan{}", 10000 + nanos % 90000)
+}
+
+async fn run_chat_session(
+    url: &str,
+    channel: &str,
+    credentials: &TwitchCredentialStore,
+    sender: &broadcast::Sender<ChatMessage>,
+) -> ChatSessionEnd {
+    let (mut stream, _) = match connect_async(url).await {
+        Ok(connected) => connected,
+        Err(e) => {
+            log::warn!("Twitch chat connect failed: {}", e);
+            return ChatSessionEnd::Lost { joined: false };
+        }
+    };
+
+    let (pass, nick) = match credentials.get().await {
+        Some(creds) if creds.scopes.iter().any(|s| s == "chat:read") => {
+            (format!("oauth:{}", creds.access_token), creds.login)
+        }
+        _ => ("SCHMOOPIIE".to_string(), anonymous_nick()),
+    };
+
+    let handshake = [
+        "CAP REQ :twitch.tv/tags twitch.tv/commands".to_string(),
+        format!("PASS {}", pass),
+        format!("NICK {}", nick),
+        format!("JOIN #{}", channel),
+    ];
+    for line in handshake {
+        if let Err(e) = stream.send(Message::text(line)).await {
+            log::warn!("Twitch chat handshake failed: {}", e);
+            return ChatSessionEnd::Lost { joined: false };
+        }
+    }
+
+    let mut joined = false;
+    loop {
+        let frame = match tokio::time::timeout(READ_TIMEOUT, stream.next()).await {
+            Ok(Some(Ok(frame))) => frame,
+            Ok(Some(Err(e))) => {
+                log::warn!("Twitch chat read error: {}", e);
+                return ChatSessionEnd::Lost { joined };
+            }
+            Ok(None) => return ChatSessionEnd::Lost { joined },
+            Err(_) => {
+                log::warn!("Twitch chat went silent, reconnecting");
+                let _ = stream.close(None).await;
+                return ChatSessionEnd::Lost { joined };
+            }
+        };
+
+        let text = match frame {
+            Message::Text(text) => text,
+            Message::Close(_) => return ChatSessionEnd::Lost { joined },
+            _ => continue,
+        };
+
+        // A single frame may carry several CRLF separated lines
+        for line in text.as_str().split("\r\n").filter(|l| !l.is_empty()) {
+            let Some(message) = IrcMessage::parse(line) else {
+                continue;
+            };
+
+            match message.command.as_str() {
+                "PING" => {
+                    let token = message.params.last().cloned().unwrap_or_else(|| "tmi.twitch.tv".to_string());
+                    if stream.send(Message::text(format!("PONG :{}", token))).await.is_err() {
+                        return ChatSessionEnd::Lost { joined };
+                    }
+                }
+                "RECONNECT" => {
+                    let _ = stream.close(None).await;
+                    return ChatSessionEnd::Reconnect;
+                }
+                "NOTICE" => {
+                    let notice = message.params.last().map(|s| s.as_str()).unwrap_or("");
+                    if notice.contains("Login authentication failed") || notice.contains("Improperly formatted auth") {
+                        return ChatSessionEnd::AuthFailed;
+                    }
+                    log::info!("Twitch chat notice: {}", notice);
+                }
+                "JOIN" if message.nick() == Some(nick.as_str()) => {
+                    log::info!("Joined Twitch chat #{}", channel);
+                    joined = true;
+                }
+                "PRIVMSG" => {
+                    if let Some(chat) = parse_privmsg(&message) {
+                        let _ = sender.send(chat);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::accept_async;
+
+    const PRIVMSG: &str = "@badge-info=subscriber/8;badges=broadcaster/1,subscriber/6;bits=100;color=#0D4200;display-name=Ronni;emotes=25:0-4,12-16/1902:6-10;id=b34ccfc7-4977-403a-8a94-33c6bac34fb8;mod=0;msg-id=highlighted-message;room-id=1337;subscriber=1;tmi-sent-ts=1507246572675;turbo=1;user-id=1337;user-type=global_mod :ronni!ronni@ronni.tmi.twitch.tv PRIVMSG #ronni :Kappa Keepo Kappa";
+
+    #[test]
+    fn parses_privmsg_tags() {
+        let message = IrcMessage::parse(PRIVMSG).unwrap();
+        let chat = parse_privmsg(&message).unwrap();
+        assert_eq!(chat.platform, "twitch");
+        assert_eq!(chat.channel, "ronni");
+        assert_eq!(chat.user_name, "ronni");
+        assert_eq!(chat.display_name, "Ronni");
+        assert_eq!(chat.user_id, "1337");
+        assert_eq!(chat.text, "Kappa Keepo Kappa");
+        assert_eq!(chat.color.as_deref(), Some("#0D4200"));
+        assert_eq!(chat.bits, Some(100));
+        assert_eq!(chat.msg_id.as_deref(), Some("highlighted-message"));
+        assert_eq!(chat.id, "b34ccfc7-4977-403a-8a94-33c6bac34fb8");
+        assert_eq!(
+            chat.badges,
+            vec![
+                ChatBadge { name: "broadcaster".to_string(), version: "1".to_string() },
+                ChatBadge { name: "subscriber".to_string(), version: "6".to_string() },
+            ]
+        );
+        assert_eq!(
+            chat.emotes,
+            vec![
+                ChatEmote { id: "25".to_string(), start: 0, end: 4 },
+                ChatEmote { id: "1902".to_string(), start: 6, end: 10 },
+                ChatEmote { id: "25".to_string(), start: 12, end: 16 },
+            ]
+        );
+        assert_eq!(
+            chat.roles,
+            ChatRoles { broadcaster: true, subscriber: true, ..Default::default() }
+        );
+        assert_eq!(chat.timestamp.unwrap().timestamp_millis(), 1507246572675);
+        assert!(!chat.is_action);
+    }
+
+    #[test]
+    fn parses_action_and_escaped_tags() {
+        let line = "@display-name=;system-msg=Hello\\sworld\\:\\\\ :viewer!viewer@viewer.tmi.twitch.tv PRIVMSG #chan :\u{1}ACTION waves\u{1}";
+        let message = IrcMessage::parse(line).unwrap();
+        assert_eq!(message.tags["system-msg"], "Hello world;\\");
+        let chat = parse_privmsg(&message).unwrap();
+        assert!(chat.is_action);
+        assert_eq!(chat.text, "waves");
+        // Empty display-name falls back to the login
+        assert_eq!(chat.display_name, "viewer");
+        assert!(chat.badges.is_empty());
+        assert_eq!(chat.roles, ChatRoles::default());
+    }
+
+    #[test]
+    fn parses_untagged_commands() {
+        let ping = IrcMessage::parse("PING :tmi.twitch.tv").unwrap();
+        assert_eq!(ping.command, "PING");
+        assert_eq!(ping.params, vec!["tmi.twitch.tv"]);
+
+        let reconnect = IrcMessage::parse(":tmi.twitch.tv RECONNECT").unwrap();
+        assert_eq!(reconnect.command, "RECONNECT");
+        assert_eq!(reconnect.prefix.as_deref(), Some("tmi.twitch.tv"));
+        assert!(parse_privmsg(&reconnect).is_none());
+    }
+
+    /// Fake IRC server: each accepted connection runs the next script.
+    /// Every script waits for JOIN, then sends its lines.
+    async fn fake_irc(scripts: Vec<Vec<String>>) -> (String, tokio::sync::mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (received_tx, received_rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            for script in scripts {
+                let (tcp, _) = listener.accept().await.unwrap();
+                let mut ws = accept_async(tcp).await.unwrap();
+                while let Some(Ok(Message::Text(line))) = ws.next().await {
+                    let line = line.as_str().to_string();
+                    let is_join = line.starts_with("JOIN");
+                    received_tx.send(line).unwrap();
+                    if is_join {
+                        break;
+                    }
+                }
+                for line in script {
+                    ws.send(Message::text(line)).await.unwrap();
+                }
+                // Report anything the client sends back, e.g. PONG
+                while let Some(Ok(frame)) = ws.next().await {
+                    if let Message::Text(line) = frame {
+                        received_tx.send(line.as_str().to_string()).unwrap();
+                    }
+                }
+            }
+        });
+        (url, received_rx)
+    }
+
+    async fn next_line(rx: &mut tokio::sync::mpsc::UnboundedReceiver<String>) -> String {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn client_handshakes_answers_ping_and_follows_reconnect() {
+        let (url, mut received) = fake_irc(vec![
+            vec![
+                format!("{}\r\nPING :tmi.twitch.tv\r\n", PRIVMSG),
+                ":tmi.twitch.tv RECONNECT\r\n".to_string(),
+            ],
+            vec!["@id=second;display-name=Two :two!two@two.tmi.twitch.tv PRIVMSG #ronni :after reconnect\r\n".to_string()],
+        ])
+        .await;
+
+        let (sender, mut chat) = broadcast::channel(16);
+        let client = TwitchChatClient::with_url(url, sender, Arc::new(TwitchCredentialStore::load(None)));
+        client.start("#Ronni").await.unwrap();
+
+        assert_eq!(next_line(&mut received).await, "CAP REQ :twitch.tv/tags twitch.tv/commands");
+        assert_eq!(next_line(&mut received).await, "PASS SCHMOOPIIE");
+        assert!(next_line(&mut received).await.starts_with("NICK justinfan"));
+        assert_eq!(next_line(&mut received).await, "JOIN #ronni");
+
+        let first = tokio::time::timeout(Duration::from_secs(5), chat.recv()).await.unwrap().unwrap();
+        assert_eq!(first.display_name, "Ronni");
+        assert_eq!(next_line(&mut received).await, "PONG :tmi.twitch.tv");
+
+        // After RECONNECT the client logs in again on a fresh connection
+        assert_eq!(next_line(&mut received).await, "CAP REQ :twitch.tv/tags twitch.tv/commands");
+        let second = tokio::time::timeout(Duration::from_secs(5), chat.recv()).await.unwrap().unwrap();
+        assert_eq!(second.text, "after reconnect");
+        assert_eq!(second.id, "second");
+
+        client.stop().await;
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_channel_names() {
+        let (sender, _) = broadcast::channel(1);
+        let client = TwitchChatClient::with_url("ws://127.0.0.1:9".to_string(), sender, Arc::new(TwitchCredentialStore::load(None)));
+        assert!(client.start("").await.is_err());
+        assert!(client.start("bad channel").await.is_err());
+    }
+}
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::storage::JsonStore;
+
+const VOICE_COMMANDS: &[&str] = &["!voice", "!голос"];
+/// Arguments of `!voice` that go back to the automatic voice
+const RESET_ARGUMENTS: &[&str] = &["reset", "default", "сброс"];
+
+/// A voice viewers may pick by name with `!voice <name>`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VoiceChoice {
+    pub name: String,
+    /// Provider-qualified voice id, e.g. `espeak:ru` or `elevenlabs:<id>`
+    pub voice: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ViewerVoiceConfig {
+    /// Voices handed out automatically; each viewer always lands on the same one
+    #[serde(default)]
+    pub pool: Vec<String>,
+    #[serde(default)]
+    pub choices: Vec<VoiceChoice>,
+    /// Voice per `platform:user_name`, set by the streamer or by the viewer's `!voice`
+    #[serde(default)]
+    pub overrides: HashMap<String, String>,
+}
+
+pub type ViewerVoiceStore = JsonStore<ViewerVoiceConfig>;
+
+/// What a `!voice` command did, reported to the frontend
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum VoiceCommandOutcome {
+    Selected { choice: String },
+    Reset,
+    /// No or unknown name; `choices` lists what may be picked
+    Unknown { requested: String, choices: Vec<String> },
+}
+
+fn viewer_key(platform: &str, user_name: &str) -> String {
+    format!("{}:{}", platform, user_name.to_lowercase())
+}
+
+/// Stable across restarts and builds, unlike the std hasher
+fn pool_index(key: &str, pool_len: usize) -> usize {
+    let digest = Sha256::digest(key.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    (u64::from_be_bytes(bytes) % pool_len as u64) as usize
+}
+
+/// Arguments of a voice command, or `None` when `text` is ordinary chat
+fn parse_voice_command(text: &str) -> Option<&str> {
+    let text = text.trim();
+    let (command, argument) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let command = command.to_lowercase();
+    VOICE_COMMANDS.contains(&command.as_str()).then(|| argument.trim())
+}
+
+pub struct ViewerVoices {
+    store: Arc<ViewerVoiceStore>,
+    /// Serializes read-modify-write so two `!voice` commands never drop each other's change
+    updates: Mutex<()>,
+}
+
+impl ViewerVoices {
+    pub fn new(store: Arc<ViewerVoiceStore>) -> Self {
+        ViewerVoices { store, updates: Mutex::new(()) }
+    }
+
+    pub async fn config(&self) -> ViewerVoiceConfig {
+        self.store.get().await.unwrap_or_default()
+    }
+
+    async fn update(&self, change: impl FnOnce(&mut ViewerVoiceConfig)) -> Result<(), String> {
+        let _guard = self.updates.lock().await;
+        let mut config = self.config().await;
+        change(&mut config);
+        self.store.set(config).await
+    }
+
+    pub async fn set_pool(&self, pool: Vec<String>) -> Result<(), String> {
+        let pool = pool.into_iter().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect();
+        self.update(|config| config.pool = pool).await
+    }
+
+    pub async fn set_choices(&self, choices: Vec<VoiceChoice>) -> Result<(), String> {
+        if let Some(blank) = choices.iter().find(|c| c.name.trim().is_empty() || c.voice.trim().is_empty()) {
+            return Err(format!("Voice choice {:?} needs a name and a voice", blank.name));
+        }
+        self.update(|config| config.choices = choices).await
+    }
+
+    /// Pin a viewer to `voice`, or hand them back to the pool with `None`
+    pub async fn set_override(&self, platform: &str, user_name: &str, voice: Option<String>) -> Result<(), String> {
+        let key = viewer_key(platform, user_name);
+        self.update(|config| match voice.filter(|v| !v.trim().is_empty()) {
+            Some(voice) => {
+                config.overrides.insert(key, voice);
+            }
+            None => {
+                config.overrides.remove(&key);
+            }
+        })
+        .await
+    }
+
+    /// The viewer's override, else their slot in the pool; `None` leaves the default voice
+    pub async fn voice_for(&self, platform: &str, user_name: &str) -> Option<String> {
+        let config = self.config().await;
+        let key = viewer_key(platform, user_name);
+        if let Some(voice) = config.overrides.get(&key) {
+            return Some(voice.clone());
+        }
+        if config.pool.is_empty() {
+            return None;
+        }
+        Some(config.pool[pool_index(&key, config.pool.len())].clone())
+    }
+
+    /// Handle `!voice <name>` from chat; `None` when the message is not a voice command
+    pub async fn handle_command(&self, platform: &str, user_name: &str, text: &str) -> Option<Result<VoiceCommandOutcome, String>> {
+        let argument = parse_voice_command(text)?;
+        let config = self.config().await;
+
+        if RESET_ARGUMENTS.contains(&argument.to_lowercase().as_str()) {
+            return Some(self.set_override(platform, user_name, None).await.map(|()| VoiceCommandOutcome::Reset));
+        }
+
+        let Some(choice) = config.choices.iter().find(|c| c.name.to_lowercase() == argument.to_lowercase()) else {
+            return Some(Ok(VoiceCommandOutcome::Unknown {
+                requested: argument.to_string(),
+                choices: config.choices.iter().map(|c| c.name.clone()).collect(),
+            }));
+        };
+        let outcome = VoiceCommandOutcome::Selected { choice: choice.name.clone() };
+        Some(self.set_override(platform, user_name, Some(choice.voice.clone())).await.map(|()| outcome))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voices() -> ViewerVoices {
+        ViewerVoices::new(Arc::new(ViewerVoiceStore::load(None)))
+    }
+
+    #[tokio::test]
+    async fn pool_assignment_is_stable_and_spread() {
+        let viewers = voices();
+        assert_eq!(viewers.voice_for("twitch", "alice").await, None);
+
+        let pool: Vec<String> = (0..4).map(|i| format!("espeak:voice{}", i)).collect();
+        viewers.set_pool(pool.clone()).await.unwrap();
+
+        let first = viewers.voice_for("twitch", "Alice").await.unwrap();
+        assert_eq!(viewers.voice_for("twitch", "alice").await.unwrap(), first);
+        // Fixed hash, so this survives restarts and upgrades
+        assert_eq!(pool_index("twitch:alice", 1000), 802);
+
+        let mut used = std::collections::HashSet::new();
+        for i in 0..40 {
+            used.insert(viewers.voice_for("twitch", &format!("viewer{}", i)).await.unwrap());
+        }
+        assert_eq!(used.len(), pool.len());
+    }
+
+    #[tokio::test]
+    async fn overrides_and_voice_command() {
+        let viewers = voices();
+        viewers.set_pool(vec!["espeak:ru".to_string()]).await.unwrap();
+        viewers
+            .set_choices(vec![VoiceChoice { name: "Robot".to_string(), voice: "espeak:en".to_string() }])
+            .await
+            .unwrap();
+
+        assert!(viewers.handle_command("twitch", "bob", "hello !voice robot").await.is_none());
+        assert_eq!(
+            viewers.handle_command("twitch", "bob", "!VOICE robot").await,
+            Some(Ok(VoiceCommandOutcome::Selected { choice: "Robot".to_string() }))
+        );
+        assert_eq!(viewers.voice_for("twitch", "bob").await.as_deref(), Some("espeak:en"));
+        // Same name on another platform is another viewer
+        assert_eq!(viewers.voice_for("youtube", "bob").await.as_deref(), Some("espeak:ru"));
+
+        assert_eq!(
+            viewers.handle_command("twitch", "bob", "!голос пират").await,
+            Some(Ok(VoiceCommandOutcome::Unknown { requested: "пират".to_string(), choices: vec!["Robot".to_string()] }))
+        );
+        assert_eq!(viewers.handle_command("twitch", "bob", "!voice сброс").await, Some(Ok(VoiceCommandOutcome::Reset)));
+        assert_eq!(viewers.voice_for("twitch", "bob").await.as_deref(), Some("espeak:ru"));
+
+        viewers.set_override("twitch", "Carol", Some("piper:ru_RU-irina-medium".to_string())).await.unwrap();
+        assert_eq!(viewers.voice_for("twitch", "carol").await.as_deref(), Some("piper:ru_RU-irina-medium"));
+        assert!(viewers.set_choices(vec![VoiceChoice { name: " ".to_string(), voice: "x".to_string() }]).await.is_err());
+    }
+}
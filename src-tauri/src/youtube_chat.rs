@@ -0,0 +1,670 @@
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::alerts::{process_youtube_chat_event, AlertPayload};
+use crate::chat::{ChatBadge, ChatMessage, ChatRoles};
+use crate::eventsub::MessageIdCache;
+use crate::oauth::{refresh_youtube_token, GoogleOAuthClient, TokenRefreshError};
+use crate::storage::JsonStore;
+
+const DEFAULT_YOUTUBE_API_BASE_URL: &str = "https://www.googleapis.com/youtube/v3";
+/// Used when a response omits `pollingIntervalMillis`
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const SEEN_MESSAGE_CAPACITY: usize = 2000;
+const SEEN_MESSAGE_TTL_SECS: u64 = 60 * 60;
+
+fn get_youtube_api_base_url() -> String {
+    std::env::var("YOUTUBE_API_BASE_URL")
+        .unwrap_or_else(|_| DEFAULT_YOUTUBE_API_BASE_URL.to_string())
+}
+
+/// YouTube OAuth tokens kept by the backend so the poller can refresh them on its own
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct YouTubeCredentials {
+    pub access_token: String,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+}
+
+/// YouTube credentials persisted in the app data directory
+pub type YouTubeCredentialStore = JsonStore<YouTubeCredentials>;
+
+#[derive(Debug)]
+enum ApiError {
+    /// The chat is gone or we are not allowed to read it; polling again will not help
+    Fatal(String),
+    Transient(String),
+}
+
+impl ApiError {
+    fn into_message(self) -> String {
+        match self {
+            ApiError::Fatal(message) | ApiError::Transient(message) => message,
+        }
+    }
+}
+
+/// Data API errors look like `{"error": {"code": 403, "message": "...", "errors": [{"reason": "liveChatEnded"}]}}`
+fn api_error_message(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| {
+            let error = &v["error"];
+            let message = error["message"].as_str()?;
+            Some(match error["errors"][0]["reason"].as_str() {
+                Some(reason) => format!("{} ({})", message, reason),
+                None => message.to_string(),
+            })
+        })
+        .unwrap_or_else(|| body.to_string())
+}
+
+/// Authorized Data API calls that refresh the access token once on a 401
+#[derive(Clone)]
+struct YouTubeApi {
+    http: reqwest::Client,
+    base_url: String,
+    oauth: GoogleOAuthClient,
+    credentials: Arc<YouTubeCredentialStore>,
+}
+
+impl YouTubeApi {
+    async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, &str)]) -> Result<T, ApiError> {
+        let credentials = self
+            .credentials
+            .get()
+            .await
+            .ok_or_else(|| ApiError::Fatal("Not connected to YouTube".to_string()))?;
+
+        let mut response = self.send(path, query, &credentials.access_token).await?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            let access_token = self.refresh(credentials).await?;
+            response = self.send(path, query, &access_token).await?;
+        }
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            let message = format!("YouTube API request failed ({}): {}", status, api_error_message(&body));
+            return Err(if status.is_client_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                ApiError::Fatal(message)
+            } else {
+                ApiError::Transient(message)
+            });
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| ApiError::Transient(format!("Invalid YouTube API response: {}", e)))
+    }
+
+    async fn send(&self, path: &str, query: &[(&str, &str)], access_token: &str) -> Result<reqwest::Response, ApiError> {
+        self.http
+            .get(format!("{}/{}", self.base_url, path))
+            .bearer_auth(access_token)
+            .query(query)
+            .send()
+            .await
+            .map_err(|e| ApiError::Transient(format!("Failed to reach YouTube: {}", e)))
+    }
+
+    async fn refresh(&self, credentials: YouTubeCredentials) -> Result<String, ApiError> {
+        let refresh_token = credentials
+            .refresh_token
+            .ok_or_else(|| ApiError::Fatal("YouTube token expired and no refresh token is stored".to_string()))?;
+
+        log::info!("YouTube access token rejected, refreshing");
+        let tokens = refresh_youtube_token(&self.http, &self.oauth, &refresh_token).await.map_err(|e| match e {
+            TokenRefreshError::Unreachable => ApiError::Transient("Failed to reach Google to refresh the YouTube token".to_string()),
+            e => ApiError::Fatal(format!("Refreshing the YouTube token failed: {:?}", e)),
+        })?;
+
+        let refreshed = YouTubeCredentials {
+            access_token: tokens.access_token.clone(),
+            // Google only sends a new refresh token when it rotates it
+            refresh_token: tokens.refresh_token.or(Some(refresh_token)),
+        };
+        if let Err(e) = self.credentials.set(refreshed).await {
+            log::warn!("Failed to store refreshed YouTube token: {}", e);
+        }
+        Ok(tokens.access_token)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemList<T> {
+    #[serde(default = "Vec::new")]
+    items: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LiveBroadcast {
+    snippet: LiveBroadcastSnippet,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LiveBroadcastSnippet {
+    live_chat_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Video {
+    live_streaming_details: Option<LiveStreamingDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LiveStreamingDetails {
+    active_live_chat_id: Option<String>,
+}
+
+/// Page returned by `liveChat/messages`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveChatMessageList {
+    #[serde(default)]
+    pub next_page_token: Option<String>,
+    #[serde(default)]
+    pub polling_interval_millis: Option<u64>,
+    /// Set once the broadcast has ended
+    #[serde(default)]
+    pub offline_at: Option<String>,
+    #[serde(default)]
+    pub items: Vec<LiveChatItem>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveChatItem {
+    pub id: String,
+    pub snippet: LiveChatSnippet,
+    #[serde(default)]
+    pub author_details: Option<LiveChatAuthor>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveChatSnippet {
+    pub r#type: String,
+    #[serde(default)]
+    pub live_chat_id: String,
+    #[serde(default)]
+    pub published_at: Option<String>,
+    #[serde(default)]
+    pub display_message: Option<String>,
+    #[serde(default)]
+    pub text_message_details: Option<TextMessageDetails>,
+    /// Type specific details for paid and membership events, read by `alerts::process_youtube_chat_event`
+    #[serde(default)]
+    pub super_chat_details: Option<serde_json::Value>,
+    #[serde(default)]
+    pub super_sticker_details: Option<serde_json::Value>,
+    #[serde(default)]
+    pub new_sponsor_details: Option<serde_json::Value>,
+    #[serde(default)]
+    pub member_milestone_chat_details: Option<serde_json::Value>,
+    #[serde(default)]
+    pub membership_gifting_details: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextMessageDetails {
+    pub message_text: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveChatAuthor {
+    pub channel_id: String,
+    pub display_name: String,
+    #[serde(default)]
+    pub is_verified: bool,
+    #[serde(default)]
+    pub is_chat_owner: bool,
+    #[serde(default)]
+    pub is_chat_sponsor: bool,
+    #[serde(default)]
+    pub is_chat_moderator: bool,
+}
+
+fn author_badges(author: &LiveChatAuthor) -> Vec<ChatBadge> {
+    [
+        (author.is_chat_owner, "broadcaster"),
+        (author.is_chat_moderator, "moderator"),
+        (author.is_chat_sponsor, "member"),
+        (author.is_verified, "verified"),
+    ]
+    .into_iter()
+    .filter(|(present, _)| *present)
+    .map(|(_, name)| ChatBadge { name: name.to_string(), version: "1".to_string() })
+    .collect()
+}
+
+/// Turn a `textMessageEvent` into a chat message; other item types are ignored
+pub fn parse_live_chat_item(item: &LiveChatItem) -> Option<ChatMessage> {
+    if item.snippet.r#type != "textMessageEvent" {
+        return None;
+    }
+
+    let text = item
+        .snippet
+        .text_message_details
+        .as_ref()
+        .map(|d| d.message_text.clone())
+        .or_else(|| item.snippet.display_message.clone())?;
+    let author = item.author_details.clone().unwrap_or_default();
+
+    Some(ChatMessage {
+        platform: "youtube".to_string(),
+        id: item.id.clone(),
+        channel: item.snippet.live_chat_id.clone(),
+        user_id: author.channel_id.clone(),
+        // YouTube display names are handles such as `@someone`
+        user_name: author.display_name.trim_start_matches('@').to_lowercase(),
+        display_name: author.display_name.clone(),
+        text,
+        badges: author_badges(&author),
+        roles: ChatRoles {
+            broadcaster: author.is_chat_owner,
+            moderator: author.is_chat_moderator,
+            subscriber: author.is_chat_sponsor,
+            ..Default::default()
+        },
+        timestamp: item
+            .snippet
+            .published_at
+            .as_deref()
+            .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+            .map(|ts| ts.with_timezone(&Utc)),
+        ..Default::default()
+    })
+}
+
+/// Polls the live chat of the active broadcast.
+///
+/// Text messages go to the chat channel, Super Chats and membership events to the alert channel.
+pub struct YouTubeChatPoller {
+    api: YouTubeApi,
+    sender: broadcast::Sender<ChatMessage>,
+    alert_sender: broadcast::Sender<AlertPayload>,
+    task: Mutex<Option<(String, JoinHandle<()>)>>,
+}
+
+impl YouTubeChatPoller {
+    pub fn new(
+        sender: broadcast::Sender<ChatMessage>,
+        alert_sender: broadcast::Sender<AlertPayload>,
+        credentials: Arc<YouTubeCredentialStore>,
+    ) -> Self {
+        Self::with_endpoints(get_youtube_api_base_url(), GoogleOAuthClient::from_env(), sender, alert_sender, credentials)
+    }
+
+    pub fn with_endpoints(
+        base_url: String,
+        oauth: GoogleOAuthClient,
+        sender: broadcast::Sender<ChatMessage>,
+        alert_sender: broadcast::Sender<AlertPayload>,
+        credentials: Arc<YouTubeCredentialStore>,
+    ) -> Self {
+        YouTubeChatPoller {
+            api: YouTubeApi {
+                http: reqwest::Client::new(),
+                base_url: base_url.trim_end_matches('/').to_string(),
+                oauth,
+                credentials,
+            },
+            sender,
+            alert_sender,
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Start polling the chat of `video_id`, or of the user's active broadcast when none is given.
+    ///
+    /// Returns the resolved live chat id.
+    pub async fn start(&self, video_id: Option<&str>) -> Result<String, String> {
+        let live_chat_id = self
+            .resolve_live_chat_id(video_id.map(str::trim).filter(|id| !id.is_empty()))
+            .await?;
+
+        let mut task = self.task.lock().await;
+        if let Some((current, handle)) = task.as_ref() {
+            if *current == live_chat_id && !handle.is_finished() {
+                return Ok(live_chat_id);
+            }
+        }
+        if let Some((_, handle)) = task.take() {
+            handle.abort();
+        }
+
+        log::info!("Polling YouTube live chat {}", live_chat_id);
+        let handle = tokio::spawn(run_poll_loop(
+            self.api.clone(),
+            live_chat_id.clone(),
+            self.sender.clone(),
+            self.alert_sender.clone(),
+        ));
+        *task = Some((live_chat_id.clone(), handle));
+        Ok(live_chat_id)
+    }
+
+    pub async fn stop(&self) {
+        if let Some((live_chat_id, handle)) = self.task.lock().await.take() {
+            handle.abort();
+            log::info!("Stopped polling YouTube live chat {}", live_chat_id);
+        }
+    }
+
+    async fn resolve_live_chat_id(&self, video_id: Option<&str>) -> Result<String, String> {
+        let live_chat_id = match video_id {
+            Some(video_id) => {
+                let videos: ItemList<Video> = self
+                    .api
+                    .get("videos", &[("part", "liveStreamingDetails"), ("id", video_id)])
+                    .await
+                    .map_err(ApiError::into_message)?;
+                videos
+                    .items
+                    .into_iter()
+                    .next()
+                    .and_then(|v| v.live_streaming_details)
+                    .and_then(|d| d.active_live_chat_id)
+            }
+            None => {
+                let broadcasts: ItemList<LiveBroadcast> = self
+                    .api
+                    .get(
+                        "liveBroadcasts",
+                        &[("part", "snippet"), ("broadcastStatus", "active"), ("broadcastType", "all")],
+                    )
+                    .await
+                    .map_err(ApiError::into_message)?;
+                broadcasts.items.into_iter().find_map(|b| b.snippet.live_chat_id)
+            }
+        };
+
+        live_chat_id.ok_or_else(|| "No active YouTube live chat found".to_string())
+    }
+}
+
+async fn run_poll_loop(
+    api: YouTubeApi,
+    live_chat_id: String,
+    sender: broadcast::Sender<ChatMessage>,
+    alert_sender: broadcast::Sender<AlertPayload>,
+) {
+    let mut seen = MessageIdCache::with_limits(SEEN_MESSAGE_CAPACITY, SEEN_MESSAGE_TTL_SECS);
+    let mut page_token: Option<String> = None;
+    // The first page is recent history; remember it without reading it out
+    let mut backlog = true;
+    let mut backoff = INITIAL_BACKOFF;
+
+    loop {
+        let mut query = vec![
+            ("liveChatId", live_chat_id.as_str()),
+            ("part", "snippet,authorDetails"),
+        ];
+        if let Some(ref token) = page_token {
+            query.push(("pageToken", token.as_str()));
+        }
+
+        let page: LiveChatMessageList = match api.get("liveChat/messages", &query).await {
+            Ok(page) => page,
+            Err(ApiError::Fatal(message)) => {
+                log::error!("Stopped polling YouTube live chat: {}", message);
+                return;
+            }
+            Err(ApiError::Transient(message)) => {
+                log::warn!("YouTube live chat poll failed, retrying in {:?}: {}", backoff, message);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+        backoff = INITIAL_BACKOFF;
+
+        let now = Utc::now().timestamp().max(0) as u64;
+        for item in &page.items {
+            if !seen.check_and_record(&item.id, now) || backlog {
+                continue;
+            }
+            if let Some(message) = parse_live_chat_item(item) {
+                let _ = sender.send(message);
+            } else if let Some(alert) = process_youtube_chat_event(item) {
+                log::info!("YouTube {} from {}", alert.alert_type, alert.user_name);
+                let _ = alert_sender.send(alert);
+            }
+        }
+        backlog = false;
+
+        if page.offline_at.is_some() {
+            log::info!("YouTube broadcast went offline, stopped polling live chat");
+            return;
+        }
+
+        if page.next_page_token.is_some() {
+            page_token = page.next_page_token;
+        }
+        let interval = page
+            .polling_interval_millis
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_POLL_INTERVAL);
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Query, State};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::routing::{get, post};
+    use axum::{Form, Json, Router};
+    use std::collections::HashMap;
+    use tokio::net::TcpListener;
+
+    fn text_item(id: &str, text: &str) -> serde_json::Value {
+        serde_json::json!({
+            "kind": "youtube#liveChatMessage",
+            "id": id,
+            "snippet": {
+                "type": "textMessageEvent",
+                "liveChatId": "chat-1",
+                "authorChannelId": "UC123",
+                "publishedAt": "2024-05-01T12:00:00.123+00:00",
+                "hasDisplayContent": true,
+                "displayMessage": text,
+                "textMessageDetails": { "messageText": text }
+            },
+            "authorDetails": {
+                "channelId": "UC123",
+                "displayName": "@Viewer",
+                "isVerified": false,
+                "isChatOwner": false,
+                "isChatSponsor": true,
+                "isChatModerator": true
+            }
+        })
+    }
+
+    fn super_chat_item(id: &str) -> serde_json::Value {
+        let mut item = text_item(id, "thanks");
+        item["snippet"] = serde_json::json!({
+            "type": "superChatEvent",
+            "liveChatId": "chat-1",
+            "displayMessage": "$5.00 from @Viewer: thanks",
+            "superChatDetails": {
+                "amountMicros": "5000000",
+                "currency": "USD",
+                "amountDisplayString": "$5.00",
+                "userComment": "thanks",
+                "tier": 2
+            }
+        });
+        item
+    }
+
+    #[test]
+    fn parses_text_message_event() {
+        let item: LiveChatItem = serde_json::from_value(text_item("msg-1", "hello there")).unwrap();
+        let chat = parse_live_chat_item(&item).unwrap();
+        assert_eq!(chat.platform, "youtube");
+        assert_eq!(chat.id, "msg-1");
+        assert_eq!(chat.channel, "chat-1");
+        assert_eq!(chat.user_id, "UC123");
+        assert_eq!(chat.user_name, "viewer");
+        assert_eq!(chat.display_name, "@Viewer");
+        assert_eq!(chat.text, "hello there");
+        assert_eq!(
+            chat.badges.iter().map(|b| b.name.as_str()).collect::<Vec<_>>(),
+            vec!["moderator", "member"]
+        );
+        assert!(chat.roles.moderator && chat.roles.subscriber && !chat.roles.broadcaster);
+        assert_eq!(chat.timestamp.unwrap().timestamp_millis(), 1714564800123);
+    }
+
+    #[test]
+    fn ignores_non_text_events() {
+        let mut value = text_item("msg-1", "ignored");
+        value["snippet"]["type"] = serde_json::json!("chatEndedEvent");
+        let item: LiveChatItem = serde_json::from_value(value).unwrap();
+        assert!(parse_live_chat_item(&item).is_none());
+    }
+
+    #[derive(Default)]
+    struct FakeYouTube {
+        /// Pages served in order; the last one repeats
+        pages: Vec<serde_json::Value>,
+        requests: Vec<HashMap<String, String>>,
+        valid_token: String,
+    }
+
+    type Shared = Arc<Mutex<FakeYouTube>>;
+
+    fn authorized(headers: &HeaderMap, token: &str) -> bool {
+        headers.get("Authorization").and_then(|h| h.to_str().ok()) == Some(format!("Bearer {}", token).as_str())
+    }
+
+    async fn broadcasts(State(state): State<Shared>, headers: HeaderMap) -> impl IntoResponse {
+        if !authorized(&headers, &state.lock().await.valid_token) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        Json(serde_json::json!({"items": [{"id": "video-1", "snippet": {"liveChatId": "chat-1"}}]})).into_response()
+    }
+
+    async fn messages(
+        State(state): State<Shared>,
+        headers: HeaderMap,
+        Query(query): Query<HashMap<String, String>>,
+    ) -> impl IntoResponse {
+        let mut state = state.lock().await;
+        if !authorized(&headers, &state.valid_token) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        let index = state.requests.len().min(state.pages.len() - 1);
+        state.requests.push(query);
+        Json(state.pages[index].clone()).into_response()
+    }
+
+    async fn token(State(state): State<Shared>, Form(form): Form<HashMap<String, String>>) -> impl IntoResponse {
+        let field = |name: &str| form.get(name).map(String::as_str);
+        if field("refresh_token") != Some("refresh-1") || field("client_id") != Some("client-1") || field("client_secret") != Some("secret-1") {
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+        state.lock().await.valid_token = "fresh-token".to_string();
+        Json(serde_json::json!({
+            "access_token": "fresh-token",
+            "expires_in": 3599,
+            "token_type": "Bearer"
+        }))
+        .into_response()
+    }
+
+    async fn fake_youtube(state: Shared) -> String {
+        let app = Router::new()
+            .route("/liveBroadcasts", get(broadcasts))
+            .route("/liveChat/messages", get(messages))
+            .route("/token", post(token))
+            .with_state(state);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    fn page(token: &str, items: Vec<serde_json::Value>) -> serde_json::Value {
+        serde_json::json!({"nextPageToken": token, "pollingIntervalMillis": 10, "items": items})
+    }
+
+    async fn next_text(receiver: &mut broadcast::Receiver<ChatMessage>) -> String {
+        tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap().text
+    }
+
+    #[tokio::test]
+    async fn polls_pages_and_refreshes_expired_token() {
+        let state: Shared = Arc::new(Mutex::new(FakeYouTube {
+            pages: vec![
+                page("page-2", vec![text_item("old", "backlog")]),
+                page("page-3", vec![text_item("old", "backlog"), text_item("m1", "first"), super_chat_item("sc1")]),
+                page("page-4", vec![text_item("m1", "first"), text_item("m2", "second")]),
+                serde_json::json!({"pollingIntervalMillis": 10, "offlineAt": "2024-05-01T13:00:00Z", "items": []}),
+            ],
+            valid_token: "fresh-token".to_string(),
+            ..Default::default()
+        }));
+        let url = fake_youtube(state.clone()).await;
+
+        let oauth = GoogleOAuthClient {
+            token_url: format!("{}/token", url),
+            client_id: Some("client-1".to_string()),
+            client_secret: Some("secret-1".to_string()),
+        };
+        let credentials = Arc::new(YouTubeCredentialStore::load(None));
+        credentials
+            .set(YouTubeCredentials {
+                access_token: "stale-token".to_string(),
+                refresh_token: Some("refresh-1".to_string()),
+            })
+            .await
+            .unwrap();
+
+        let (sender, mut receiver) = broadcast::channel(16);
+        let (alert_sender, mut alert_receiver) = broadcast::channel(16);
+        let poller = YouTubeChatPoller::with_endpoints(url, oauth, sender, alert_sender, credentials.clone());
+        assert_eq!(poller.start(None).await.unwrap(), "chat-1");
+
+        // The backlog is skipped and repeated ids are delivered once
+        assert_eq!(next_text(&mut receiver).await, "first");
+        assert_eq!(next_text(&mut receiver).await, "second");
+        let alert = alert_receiver.try_recv().unwrap();
+        assert_eq!(alert.alert_type, "superchat");
+        assert!(alert_receiver.try_recv().is_err());
+
+        let stored = credentials.get().await.unwrap();
+        assert_eq!(stored.access_token, "fresh-token");
+        assert_eq!(stored.refresh_token.as_deref(), Some("refresh-1"));
+
+        // offlineAt ends the loop
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let state = state.lock().await;
+        assert_eq!(state.requests.len(), 4);
+        assert_eq!(state.requests[0].get("pageToken"), None);
+        assert_eq!(state.requests[1]["pageToken"], "page-2");
+        assert_eq!(state.requests[3]["pageToken"], "page-4");
+        assert_eq!(state.requests[0]["liveChatId"], "chat-1");
+        assert!(receiver.try_recv().is_err());
+    }
+}
@@ -0,0 +1,347 @@
+use chrono::{DateTime, Utc};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::NsReader;
+use serde::Serialize;
+
+const ATOM_NS: &[u8] = b"http://www.w3.org/2005/Atom";
+const YT_NS: &[u8] = b"http://www.youtube.com/xml/schemas/2015";
+const TOMBSTONE_NS: &[u8] = b"http://purl.org/atompub/tombstones/1.0";
+
+/// A video entry announced by the YouTube PubSubHubbub hub
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct YouTubeVideo {
+    pub video_id: String,
+    pub channel_id: String,
+    pub channel_title: String,
+    pub title: String,
+    pub link: Option<String>,
+    pub published: Option<DateTime<Utc>>,
+    pub updated: Option<DateTime<Utc>>,
+}
+
+/// A `<at:deleted-entry>` tombstone sent when a video is removed or made private
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct YouTubeDeletedVideo {
+    pub video_id: String,
+    pub channel_id: Option<String>,
+    pub channel_title: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum YouTubeFeedEntry {
+    Video(YouTubeVideo),
+    Deleted(YouTubeDeletedVideo),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    VideoId,
+    ChannelId,
+    Title,
+    AuthorName,
+    AuthorUri,
+    Published,
+    Updated,
+}
+
+enum Current {
+    None,
+    Video(YouTubeVideo),
+    Deleted(YouTubeDeletedVideo),
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Extract the channel id from an author uri like `https://www.youtube.com/channel/UCxyz`
+fn channel_id_from_uri(uri: &str) -> Option<String> {
+    let id = uri.trim().rsplit_once("/channel/")?.1.trim_end_matches('/');
+    if id.is_empty() {
+        None
+    } else {
+        Some(id.to_string())
+    }
+}
+
+fn attribute(element: &BytesStart, name: &[u8]) -> Option<String> {
+    element
+        .try_get_attribute(name)
+        .ok()
+        .flatten()
+        .and_then(|attr| attr.unescape_value().ok())
+        .map(|value| value.into_owned())
+}
+
+fn is_ns(ns: &ResolveResult, expected: &[u8]) -> bool {
+    matches!(ns, ResolveResult::Bound(Namespace(bound)) if *bound == expected)
+}
+
+/// Parse an Atom notification body pushed by the YouTube hub.
+///
+/// Returns every `<entry>` and `<at:deleted-entry>` found in document order.
+/// Entries without a video id are skipped since they cannot be acted upon.
+pub fn parse_youtube_feed(xml: &str) -> Result<Vec<YouTubeFeedEntry>, String> {
+    let mut reader = NsReader::from_str(xml);
+    reader.config_mut().trim_text(false);
+
+    let mut entries = Vec::new();
+    let mut current = Current::None;
+    let mut field: Option<Field> = None;
+    let mut text = String::new();
+    let mut in_author = false;
+
+    loop {
+        let (ns, event) = reader
+            .read_resolved_event()
+            .map_err(|e| format!("Invalid YouTube feed XML: {}", e))?;
+
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                let local = e.local_name();
+                let local = local.as_ref();
+
+                if is_ns(&ns, ATOM_NS) && local == b"entry" {
+                    current = if is_empty { Current::None } else { Current::Video(YouTubeVideo::default()) };
+                    continue;
+                }
+
+                if is_ns(&ns, TOMBSTONE_NS) && local == b"deleted-entry" {
+                    let deleted = YouTubeDeletedVideo {
+                        video_id: attribute(e, b"ref")
+                            .map(|r| r.trim_start_matches("yt:video:").to_string())
+                            .unwrap_or_default(),
+                        deleted_at: attribute(e, b"when").as_deref().and_then(parse_timestamp),
+                        ..Default::default()
+                    };
+                    if is_empty {
+                        if !deleted.video_id.is_empty() {
+                            entries.push(YouTubeFeedEntry::Deleted(deleted));
+                        }
+                        current = Current::None;
+                    } else {
+                        current = Current::Deleted(deleted);
+                    }
+                    continue;
+                }
+
+                if matches!(current, Current::None) {
+                    continue;
+                }
+
+                if (is_ns(&ns, ATOM_NS) && local == b"author") || (is_ns(&ns, TOMBSTONE_NS) && local == b"by") {
+                    in_author = !is_empty;
+                    continue;
+                }
+
+                if is_ns(&ns, ATOM_NS) && local == b"link" {
+                    if let Current::Video(ref mut video) = current {
+                        let rel = attribute(e, b"rel");
+                        if rel.is_none() || rel.as_deref() == Some("alternate") {
+                            video.link = attribute(e, b"href");
+                        }
+                    }
+                    continue;
+                }
+
+                let next = if is_ns(&ns, YT_NS) {
+                    match local {
+                        b"videoId" => Some(Field::VideoId),
+                        b"channelId" => Some(Field::ChannelId),
+                        _ => None,
+                    }
+                } else if is_ns(&ns, ATOM_NS) {
+                    match local {
+                        b"name" if in_author => Some(Field::AuthorName),
+                        b"uri" if in_author => Some(Field::AuthorUri),
+                        b"title" if !in_author => Some(Field::Title),
+                        b"published" if !in_author => Some(Field::Published),
+                        b"updated" if !in_author => Some(Field::Updated),
+                        _ => None,
+                    }
+                } else {
+                    None
+                };
+
+                if !is_empty {
+                    field = next;
+                    text.clear();
+                }
+            }
+            Event::Text(ref e) if field.is_some() => {
+                let decoded = e
+                    .decode()
+                    .map_err(|e| format!("Invalid text in YouTube feed: {}", e))?;
+                text.push_str(&decoded);
+            }
+            Event::CData(ref e) if field.is_some() => {
+                let decoded = e
+                    .decode()
+                    .map_err(|e| format!("Invalid CDATA in YouTube feed: {}", e))?;
+                text.push_str(&decoded);
+            }
+            Event::GeneralRef(ref e) if field.is_some() => {
+                let name = e
+                    .decode()
+                    .map_err(|e| format!("Invalid entity in YouTube feed: {}", e))?;
+                let entity = format!("&{};", name);
+                let resolved = quick_xml::escape::unescape(&entity)
+                    .map_err(|e| format!("Unknown entity in YouTube feed: {}", e))?;
+                text.push_str(&resolved);
+            }
+            Event::End(ref e) => {
+                let local = e.local_name();
+                let local = local.as_ref();
+
+                if let Some(done) = field.take() {
+                    let value = text.trim().to_string();
+                    match current {
+                        Current::Video(ref mut video) => match done {
+                            Field::VideoId => video.video_id = value,
+                            Field::ChannelId => video.channel_id = value,
+                            Field::Title => video.title = value,
+                            Field::AuthorName => video.channel_title = value,
+                            Field::AuthorUri => {
+                                if video.channel_id.is_empty() {
+                                    video.channel_id = channel_id_from_uri(&value).unwrap_or_default();
+                                }
+                            }
+                            Field::Published => video.published = parse_timestamp(&value),
+                            Field::Updated => video.updated = parse_timestamp(&value),
+                        },
+                        Current::Deleted(ref mut deleted) => match done {
+                            Field::AuthorName => deleted.channel_title = Some(value),
+                            Field::AuthorUri => deleted.channel_id = channel_id_from_uri(&value),
+                            _ => {}
+                        },
+                        Current::None => {}
+                    }
+                    text.clear();
+                    continue;
+                }
+
+                if (is_ns(&ns, ATOM_NS) && local == b"author") || (is_ns(&ns, TOMBSTONE_NS) && local == b"by") {
+                    in_author = false;
+                } else if (is_ns(&ns, ATOM_NS) && local == b"entry")
+                    || (is_ns(&ns, TOMBSTONE_NS) && local == b"deleted-entry")
+                {
+                    match std::mem::replace(&mut current, Current::None) {
+                        Current::Video(video) if !video.video_id.is_empty() => {
+                            entries.push(YouTubeFeedEntry::Video(video));
+                        }
+                        Current::Deleted(deleted) if !deleted.video_id.is_empty() => {
+                            entries.push(YouTubeFeedEntry::Deleted(deleted));
+                        }
+                        _ => {}
+                    }
+                    in_author = false;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEW_VIDEO: &str = r#"<?xml version='1.0' encoding='UTF-8'?>
+<feed xmlns:yt="http://www.youtube.com/xml/schemas/2015" xmlns="http://www.w3.org/2005/Atom">
+  <link rel="hub" href="https://pubsubhubbub.appspot.com"/>
+  <link rel="self" href="https://www.youtube.com/xml/feeds/videos.xml?channel_id=UCabc123"/>
+  <title>YouTube video feed</title>
+  <updated>2024-03-09T19:05:24.552394234+00:00</updated>
+  <entry>
+    <id>yt:video:dQw4w9WgXcQ</id>
+    <yt:videoId>dQw4w9WgXcQ</yt:videoId>
+    <yt:channelId>UCabc123</yt:channelId>
+    <title>Стрим &amp; общение</title>
+    <link rel="alternate" href="https://www.youtube.com/watch?v=dQw4w9WgXcQ"/>
+    <author>
+     <name>Echo Channel</name>
+     <uri>https://www.youtube.com/channel/UCabc123</uri>
+    </author>
+    <published>2024-03-09T19:00:00+00:00</published>
+    <updated>2024-03-09T19:05:24.552394234+00:00</updated>
+  </entry>
+</feed>"#;
+
+    const DELETED_VIDEO: &str = r#"<?xml version='1.0' encoding='UTF-8'?>
+<feed xmlns:at="http://purl.org/atompub/tombstones/1.0" xmlns="http://www.w3.org/2005/Atom">
+  <at:deleted-entry ref="yt:video:dQw4w9WgXcQ" when="2024-03-10T08:00:00.000000+00:00">
+    <link href="https://www.youtube.com/watch?v=dQw4w9WgXcQ"/>
+    <at:by>
+     <name>Echo Channel</name>
+     <uri>https://www.youtube.com/channel/UCabc123</uri>
+    </at:by>
+  </at:deleted-entry>
+</feed>"#;
+
+    #[test]
+    fn parses_video_entry() {
+        let entries = parse_youtube_feed(NEW_VIDEO).unwrap();
+        assert_eq!(entries.len(), 1);
+        let YouTubeFeedEntry::Video(video) = &entries[0] else {
+            panic!("expected video entry");
+        };
+        assert_eq!(video.video_id, "dQw4w9WgXcQ");
+        assert_eq!(video.channel_id, "UCabc123");
+        assert_eq!(video.channel_title, "Echo Channel");
+        assert_eq!(video.title, "Стрим & общение");
+        assert_eq!(video.link.as_deref(), Some("https://www.youtube.com/watch?v=dQw4w9WgXcQ"));
+        assert_eq!(video.published, parse_timestamp("2024-03-09T19:00:00Z"));
+        assert!(video.updated.is_some());
+    }
+
+    #[test]
+    fn parses_deleted_entry() {
+        let entries = parse_youtube_feed(DELETED_VIDEO).unwrap();
+        assert_eq!(
+            entries,
+            vec![YouTubeFeedEntry::Deleted(YouTubeDeletedVideo {
+                video_id: "dQw4w9WgXcQ".to_string(),
+                channel_id: Some("UCabc123".to_string()),
+                channel_title: Some("Echo Channel".to_string()),
+                deleted_at: parse_timestamp("2024-03-10T08:00:00Z"),
+            })]
+        );
+    }
+
+    #[test]
+    fn namespace_prefixes_are_not_hardcoded() {
+        let xml = NEW_VIDEO
+            .replace("xmlns:yt=", "xmlns:youtube=")
+            .replace("<yt:", "<youtube:")
+            .replace("</yt:", "</youtube:");
+        let entries = parse_youtube_feed(&xml).unwrap();
+        assert!(matches!(&entries[0], YouTubeFeedEntry::Video(v) if v.video_id == "dQw4w9WgXcQ"));
+    }
+
+    #[test]
+    fn channel_id_falls_back_to_author_uri() {
+        let xml = NEW_VIDEO.replace("<yt:channelId>UCabc123</yt:channelId>", "");
+        let entries = parse_youtube_feed(&xml).unwrap();
+        assert!(matches!(&entries[0], YouTubeFeedEntry::Video(v) if v.channel_id == "UCabc123"));
+    }
+
+    #[test]
+    fn entry_without_video_id_is_skipped() {
+        let xml = NEW_VIDEO.replace("<yt:videoId>dQw4w9WgXcQ</yt:videoId>", "");
+        assert!(parse_youtube_feed(&xml).unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_xml_is_an_error() {
+        assert!(parse_youtube_feed("<feed><entry></feed>").is_err());
+    }
+}
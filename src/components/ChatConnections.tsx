@@ -7,21 +7,25 @@ import { ChatConnection, ChatSource } from '@/types/chatSource';
 import { useToast } from '@/hooks/use-toast';
 import { TWITCH_CLIENT_ID, YOUTUBE_CLIENT_ID, OAUTH_REDIRECT_URI, generateOAuthState, validateOAuthState } from '@/config/security';
 import { 
-  connectToTwitchChat, 
-  disconnectFromTwitchChat,
   hasTwitchOAuthToken,
   saveTwitchOAuthToken,
   getTwitchUsername,
-  clearTwitchOAuthToken,
-  isTwitchConnected
+  clearTwitchOAuthToken
 } from '@/services/twitchService';
+import {
+  connectTwitchChat,
+  disconnectTwitchChat,
+  setYoutubeCredentials,
+  connectYoutubeChat,
+  disconnectYoutubeChat
+} from '@/lib/tauri-api';
 import { 
-  connectToYouTubeLiveChat,
   hasYoutubeOAuthToken,
   clearYoutubeOAuthToken,
   fetchYouTubeLiveBroadcasts,
   checkLiveStreamingEnabled,
   getValidYoutubeToken,
+  getStoredTokens,
   saveYoutubeTokens,
   YouTubeTokens
 } from '@/services/youtubeService';
@@ -44,8 +48,6 @@ const ChatConnections: React.FC<ChatConnectionsProps> = ({
   const [isTwitchStreamConnected, setIsTwitchStreamConnected] = useState(false);
   const [isYoutubeStreamConnected, setIsYoutubeStreamConnected] = useState(false);
   const { toast } = useToast();
-  // Reference to store YouTube disconnect functions
-  const youtubeDisconnectFns = useRef<Record<string, () => void>>({});
   
   // Reference to store current connections for callbacks to avoid stale closure
   const connectionsRef = useRef(connections);
@@ -58,19 +60,6 @@ const ChatConnections: React.FC<ChatConnectionsProps> = ({
     connectionsRef.current = connections;
   }, [connections]);
   
-  useEffect(() => {
-    return () => {
-      Object.values(youtubeDisconnectFns.current).forEach(disconnect => {
-        try {
-          disconnect();
-        } catch (e) {
-          console.error('Error cleaning up YouTube connection:', e);
-        }
-      });
-      youtubeDisconnectFns.current = {};
-    };
-  }, []);
-
   // Add effect to listen for auth callbacks from Electron
   useEffect(() => {
     // Create a wrapper function that can be referenced for both adding and removing
@@ -438,46 +427,29 @@ const ChatConnections: React.FC<ChatConnectionsProps> = ({
           const updatedConnections = [...connections, newConnection];
           onConnectionChange(updatedConnections);
 
-          // Connect to Twitch chat with improved error handling
-          connectToTwitchChat(
-            username,
-            (username, message) => {
-              // Message handler will be implemented in the parent component
-            },
-            (connected, error) => {
-              // Update connection status with proper state management
-              // Use the ref to get current connections to avoid stale closure
-              const currentConnections = connectionsRef.current;
-              
-              const updatedList = currentConnections.map(conn => 
-                conn.id === connectionId 
-                  ? { ...conn, isConnected: connected, error: error } 
-                  : conn
-              );
-              
-              // Only update if we found the connection
-              const connectionExists = currentConnections.some(conn => conn.id === connectionId);
-              if (connectionExists) {
-                onConnectionChange(updatedList);
-              } else {
-                console.warn(`Connection ${connectionId} not found in current connections`);
-              }
-
-              if (connected) {
-                toast({
-                  title: "Connected to Twitch",
-                  description: `Now listening to ${username}'s chat`
-                });
-              } else if (error && !error.includes('RECONNECT')) {
-                // Don't show toast for reconnection attempts
-                toast({
-                  title: "Twitch Connection Error",
-                  description: error,
-                  variant: "destructive"
-                });
-              }
-            }
-          );
+          // The backend client joins the channel and keeps reconnecting on its own;
+          // its messages go through the filters and into the speech queue there
+          try {
+            await connectTwitchChat(username);
+            const updatedList = connectionsRef.current.map(conn =>
+              conn.id === connectionId
+                ? { ...conn, isConnected: true, error: undefined }
+                : conn
+            );
+            onConnectionChange(updatedList);
+            toast({
+              title: "Connected to Twitch",
+              description: `Now listening to ${username}'s chat`
+            });
+          } catch (connectionError) {
+            console.error("Error starting Twitch chat:", connectionError);
+            onConnectionChange(connectionsRef.current.filter(conn => conn.id !== connectionId));
+            toast({
+              title: "Twitch Connection Error",
+              description: String(connectionError),
+              variant: "destructive"
+            });
+          }
         } else {
           toast({
             title: "Already Connected",
@@ -520,7 +492,7 @@ const ChatConnections: React.FC<ChatConnectionsProps> = ({
       // Disconnect each connection with proper error handling
       const disconnectPromises = twitchConnections.map(async (conn) => {
         try {
-          await disconnectFromTwitchChat(conn.channelName);
+          await disconnectTwitchChat();
           
           // Remove from UI immediately after successful disconnect
           const updatedConnections = connections.filter(c => c.id !== conn.id);
@@ -658,37 +630,14 @@ const ChatConnections: React.FC<ChatConnectionsProps> = ({
           const updatedConnections = [...connections, newConnection];
           onConnectionChange(updatedConnections);
 
-          // Connect to YouTube chat with timeout protection
+          // The backend poller reads the chat from here on, refreshing the token itself when it expires
           try {
-            const { disconnect } = await connectToYouTubeLiveChat(
-              broadcastId,
-              (message) => {
-              },
-              (error) => {
-                console.error("YouTube chat connection error:", error);
-                
-                // Update connection status with error using current connections ref
-                const currentConnections = connectionsRef.current;
-                const updatedList = currentConnections.map(conn => 
-                  conn.id === connectionId 
-                    ? { ...conn, isConnected: false, error: error.message } 
-                    : conn
-                );
-                onConnectionChange(updatedList);
-                setIsYoutubeStreamConnected(false);
-
-                toast({
-                  title: "Connection Error",
-                  description: error.message || "Could not connect to YouTube chat.",
-                  variant: "destructive",
-                  duration: 5000
-                });
-              }
-            );
-
-            
-            // Store the disconnect function for later use
-            youtubeDisconnectFns.current[connectionId] = disconnect;
+            const token = await getValidYoutubeToken();
+            if (!token) {
+              throw new Error('Please log in to YouTube first.');
+            }
+            await setYoutubeCredentials(token, getStoredTokens()?.refresh_token || null);
+            await connectYoutubeChat(broadcastId);
             
             // Update connection status on successful connection using current connections ref
             const currentConnections = connectionsRef.current;
@@ -712,11 +661,11 @@ const ChatConnections: React.FC<ChatConnectionsProps> = ({
             const updatedList = currentConnections.filter(conn => conn.id !== connectionId);
             onConnectionChange(updatedList);
             
-            // Show user-friendly error message
-            if (connectionError instanceof Error) {
+            // Show user-friendly error message; backend errors arrive as plain strings
+            if (connectionError) {
               toast({
                 title: "Connection Error",
-                description: connectionError.message,
+                description: connectionError instanceof Error ? connectionError.message : String(connectionError),
                 variant: "destructive",
                 duration: 5000
               });
@@ -788,15 +737,9 @@ const ChatConnections: React.FC<ChatConnectionsProps> = ({
     
     try {
       if (connection.type === 'twitch') {
-        await disconnectFromTwitchChat(connection.channelName);
+        await disconnectTwitchChat();
       } else if (connection.type === 'youtube') {
-        // Call the stored disconnect function for this specific connection
-        const disconnect = youtubeDisconnectFns.current[connection.id];
-        if (disconnect) {
-          disconnect();
-          // Remove the disconnect function from storage
-          delete youtubeDisconnectFns.current[connection.id];
-        }
+        await disconnectYoutubeChat();
       }
       
       toast({
@@ -1140,17 +1083,9 @@ const ChatConnections: React.FC<ChatConnectionsProps> = ({
     try {
       
       if (connection.type === 'twitch') {
-        await disconnectFromTwitchChat(connection.channelName);
+        await disconnectTwitchChat();
       } else if (connection.type === 'youtube') {
-        // Call the stored disconnect function for this specific connection
-        const disconnect = youtubeDisconnectFns.current[connection.id];
-        if (disconnect) {
-          disconnect();
-          // Remove the disconnect function from storage
-          delete youtubeDisconnectFns.current[connection.id];
-        } else {
-          console.warn(`No disconnect function found for YouTube connection: ${connection.id}`);
-        }
+        await disconnectYoutubeChat();
       }
       
       toast({
@@ -160,7 +160,7 @@ const TwitchOAuthButton: React.FC<TwitchOAuthButtonProps> = ({ onAuthChange }) =
     setIsAuthenticating(true);
     
     // Twitch OAuth implicit flow
-    const scopes = ['chat:read', 'chat:edit'];
+    const scopes = ['chat:read', 'chat:edit', 'moderator:read:followers'];
     const authUrl = new URL('https://id.twitch.tv/oauth2/authorize');
     authUrl.searchParams.append('client_id', TWITCH_CLIENT_ID);
     
@@ -1,3 +1,4 @@
+import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
 import { open } from '@tauri-apps/plugin-shell';
 
@@ -29,7 +30,61 @@ export interface AlertData {
   message: string;
   amount?: string;
   currency?: string;
+  spoken_amount?: string;
   count?: number;
+  tier?: string;
+  is_gift?: boolean;
+  cumulative_months?: number;
+  streak_months?: number;
+  user_message?: string;
+}
+
+export interface EventSubRevocationData {
+  subscription_id: string;
+  subscription_type: string;
+  version: string;
+  reason: string | { other: string };
+  condition: Record<string, unknown>;
+  revoked_at: string;
+}
+
+export type QueuePriority = 'chat' | 'alert';
+export type QueueDropPolicy = 'drop_oldest' | 'drop_newest';
+export type QueueFinishReason = 'completed' | 'skipped' | 'cleared' | 'failed';
+
+export type SpeechPartData =
+  | { type: 'text'; text: string }
+  | { type: 'pause'; millis: number }
+  | { type: 'say_as'; interpret_as: string; value: string; spoken: string };
+
+// Rendered as SSML for providers that support it, plain text for the rest
+export interface SpeechData {
+  parts: SpeechPartData[];
+  prosody: { rate: string | null; pitch: string | null };
+}
+
+export interface QueueItemData {
+  id: number;
+  platform: string;
+  user_name: string;
+  text: string;
+  priority: QueuePriority;
+  enqueued_at: string;
+  speech: SpeechData | null;
+  voice: string | null;
+}
+
+export interface QueueSnapshotData {
+  current: QueueItemData | null;
+  pending: QueueItemData[];
+  paused: boolean;
+  max_length: number;
+  drop_policy: QueueDropPolicy;
+}
+
+export interface QueueItemFinishedData {
+  item: QueueItemData;
+  reason: QueueFinishReason;
 }
 
 export const openExternalAuth = async (url: string, redirectUrl: string): Promise<void> => {
@@ -78,4 +133,439 @@ export const onAlert = (callback: (data: AlertData) => void): (() => void) => {
   return () => {
     unlisten.then(fn => fn()).catch(console.error);
   };
-};
\ No newline at end of file
+};
+
+export const onEventSubRevoked = (callback: (data: EventSubRevocationData) => void): (() => void) => {
+  
+  if (!isTauriAvailable()) {
+    console.warn('TauriAPI: Tauri not available, returning no-op revocation callback');
+    return () => {};
+  }
+  
+  const unlisten = listen<EventSubRevocationData>('eventsub-revoked', (event) => {
+    const data = event.payload;
+    callback(data);
+  });
+  
+  return () => {
+    unlisten.then(fn => fn()).catch(console.error);
+  };
+};
+
+// Joins the channel from the backend, anonymously unless the stored Twitch login has chat:read;
+// the client reconnects on its own until disconnected
+export const connectTwitchChat = (channel: string): Promise<void> =>
+  invoke<void>('connect_twitch_chat', { channel });
+
+export const disconnectTwitchChat = (): Promise<void> =>
+  invoke<void>('disconnect_twitch_chat');
+
+// Hands the backend poller the current tokens, e.g. after a restart or a refresh in the webview
+export const setYoutubeCredentials = (accessToken: string, refreshToken: string | null): Promise<void> =>
+  invoke<void>('set_youtube_credentials', { accessToken, refreshToken });
+
+// Polls the chat of the given broadcast, or of the active one when omitted; resolves to the live chat id
+export const connectYoutubeChat = (videoId?: string): Promise<string> =>
+  invoke<string>('connect_youtube_chat', { videoId: videoId ?? null });
+
+export const disconnectYoutubeChat = (): Promise<void> =>
+  invoke<void>('disconnect_youtube_chat');
+
+const listenTo = <T,>(eventName: string, callback: (data: T) => void): (() => void) => {
+  
+  if (!isTauriAvailable()) {
+    console.warn(`TauriAPI: Tauri not available, returning no-op ${eventName} callback`);
+    return () => {};
+  }
+  
+  const unlisten = listen<T>(eventName, (event) => {
+    callback(event.payload);
+  });
+  
+  return () => {
+    unlisten.then(fn => fn()).catch(console.error);
+  };
+};
+
+export const onQueueUpdated = (callback: (data: QueueSnapshotData) => void): (() => void) =>
+  listenTo<QueueSnapshotData>('queue-updated', callback);
+
+export const onQueueItemStarted = (callback: (data: QueueItemData) => void): (() => void) =>
+  listenTo<QueueItemData>('item-started', callback);
+
+export const onQueueItemFinished = (callback: (data: QueueItemFinishedData) => void): (() => void) =>
+  listenTo<QueueItemFinishedData>('item-finished', callback);
+
+// The backend assigns the id and timestamp
+export interface NewQueueItemData {
+  platform: string;
+  user_name: string;
+  text: string;
+  priority: QueuePriority;
+  speech?: SpeechData | null;
+  voice?: string | null;
+}
+
+export const getTtsQueue = (): Promise<QueueSnapshotData> =>
+  invoke<QueueSnapshotData>('get_tts_queue');
+
+// Resolves to null when the drop policy rejected the item
+export const enqueueTtsMessage = (item: NewQueueItemData): Promise<number | null> =>
+  invoke<number | null>('enqueue_tts_message', { item });
+
+// The player calls this when it is done with the item from `item-started`, which starts the next one
+export const finishTtsItem = (id: number, failed: boolean): Promise<void> =>
+  invoke<void>('finish_tts_item', { id, failed });
+
+export const skipTtsItem = (): Promise<void> =>
+  invoke<void>('skip_tts_item');
+
+export const pauseTtsQueue = (): Promise<void> =>
+  invoke<void>('pause_tts_queue');
+
+export const resumeTtsQueue = (): Promise<void> =>
+  invoke<void>('resume_tts_queue');
+
+export const clearTtsQueue = (): Promise<void> =>
+  invoke<void>('clear_tts_queue');
+
+export const setTtsQueueLimits = (maxLength: number, dropPolicy: QueueDropPolicy): Promise<void> =>
+  invoke<void>('set_tts_queue_limits', { maxLength, dropPolicy });
+
+export type ElevenLabsErrorKind =
+  | 'missing_api_key'
+  | 'unauthorized'
+  | 'quota_exceeded'
+  | 'rate_limited'
+  | 'invalid_request'
+  | 'api'
+  | 'network';
+
+export interface ElevenLabsErrorData {
+  kind: ElevenLabsErrorKind;
+  message?: string;
+  status?: number;
+  retry_after_secs?: number | null;
+}
+
+export interface ElevenLabsSpeechRequest {
+  text: string;
+  voice_id?: string;
+  model_id?: string;
+  stability?: number;
+  similarity_boost?: number;
+}
+
+export const setElevenLabsApiKey = (apiKey: string): Promise<void> =>
+  invoke<void>('set_elevenlabs_api_key', { apiKey });
+
+export const hasElevenLabsApiKey = (): Promise<boolean> =>
+  isTauriAvailable() ? invoke<boolean>('has_elevenlabs_api_key') : Promise.resolve(false);
+
+export const elevenLabsTextToSpeech = (request: ElevenLabsSpeechRequest): Promise<ArrayBuffer> =>
+  invoke<ArrayBuffer>('elevenlabs_text_to_speech', { request });
+
+export type LocalTtsErrorKind = 'binary_not_found' | 'voice_not_found' | 'invalid_request' | 'timeout' | 'failed';
+
+export interface LocalTtsErrorData {
+  kind: LocalTtsErrorKind;
+  binary?: string;
+  voice?: string;
+  message?: string;
+  secs?: number;
+}
+
+export interface LocalVoiceData {
+  id: string;
+  name: string;
+  language: string;
+  engine: 'espeak' | 'piper';
+  gender: string | null;
+}
+
+export const listLocalVoices = (): Promise<LocalVoiceData[]> =>
+  invoke<LocalVoiceData[]>('list_local_voices');
+
+export const localTextToSpeech = (text: string, voice?: string): Promise<ArrayBuffer> =>
+  invoke<ArrayBuffer>('local_text_to_speech', { text, voice });
+
+export type TtsErrorKind = 'unavailable' | 'quota_exceeded' | 'rate_limited' | 'timeout' | 'invalid_request' | 'failed';
+
+export interface TtsErrorData {
+  provider: string;
+  kind: TtsErrorKind;
+  message: string;
+}
+
+export interface TtsChainErrorData {
+  message: string;
+  attempts: TtsErrorData[];
+}
+
+export interface TtsProviderVoiceData {
+  provider: string;
+  id: string;
+  name: string;
+  language: string | null;
+}
+
+export interface TtsProviderHealthData {
+  provider: string;
+  available: boolean;
+  detail: string | null;
+}
+
+// Tries each provider of the backend fallback chain in turn
+export const synthesizeSpeech = (text: string, voice?: string, speech?: SpeechData): Promise<ArrayBuffer> =>
+  invoke<ArrayBuffer>('synthesize_speech', { request: { text, voice, speech } });
+
+export const listTtsVoices = (): Promise<TtsProviderVoiceData[]> =>
+  invoke<TtsProviderVoiceData[]>('list_tts_voices');
+
+export const getTtsProviderHealth = (): Promise<TtsProviderHealthData[]> =>
+  invoke<TtsProviderHealthData[]>('get_tts_provider_health');
+
+export const getTtsFallbackChain = (): Promise<string[]> =>
+  invoke<string[]>('get_tts_fallback_chain');
+
+export const setTtsFallbackChain = (providers: string[]): Promise<void> =>
+  invoke<void>('set_tts_fallback_chain', { providers });
+
+export interface AudioCacheStatsData {
+  hits: number;
+  misses: number;
+  evictions: number;
+  entries: number;
+  total_bytes: number;
+  max_bytes: number;
+}
+
+export const getAudioCacheStats = (): Promise<AudioCacheStatsData | null> =>
+  invoke<AudioCacheStatsData | null>('get_audio_cache_stats');
+
+export const purgeAudioCache = (): Promise<void> =>
+  invoke<void>('purge_audio_cache');
+
+export interface PronunciationRuleData {
+  id: number;
+  pattern: string;
+  replacement: string;
+  kind: 'literal' | 'regex';
+  case_sensitive: boolean;
+  language: string | null;
+}
+
+export type DictionaryFormat = 'json' | 'csv';
+
+export const listPronunciationRules = (): Promise<PronunciationRuleData[]> =>
+  invoke<PronunciationRuleData[]>('list_pronunciation_rules');
+
+// The backend assigns the id
+export const addPronunciationRule = (rule: Omit<PronunciationRuleData, 'id'>): Promise<PronunciationRuleData> =>
+  invoke<PronunciationRuleData>('add_pronunciation_rule', { rule });
+
+export const updatePronunciationRule = (rule: PronunciationRuleData): Promise<void> =>
+  invoke<void>('update_pronunciation_rule', { rule });
+
+export const removePronunciationRule = (id: number): Promise<void> =>
+  invoke<void>('remove_pronunciation_rule', { id });
+
+export const importPronunciationDictionary = (
+  data: string,
+  format: DictionaryFormat,
+  replace: boolean
+): Promise<number> =>
+  invoke<number>('import_pronunciation_dictionary', { data, format, replace });
+
+export const exportPronunciationDictionary = (format: DictionaryFormat): Promise<string> =>
+  invoke<string>('export_pronunciation_dictionary', { format });
+
+// Shows what the TTS engine will actually be asked to say
+export const previewSpeechText = (text: string, voice?: string): Promise<string> =>
+  invoke<string>('preview_speech_text', { text, voice });
+
+export interface ViewerVoiceChoice {
+  name: string;
+  voice: string;
+}
+
+export interface ViewerVoiceConfigData {
+  pool: string[];
+  choices: ViewerVoiceChoice[];
+  // Keyed by `platform:user_name`
+  overrides: Record<string, string>;
+}
+
+export type VoiceCommandOutcome =
+  | { result: 'selected'; choice: string }
+  | { result: 'reset' }
+  | { result: 'unknown'; requested: string; choices: string[] };
+
+export interface VoiceCommandData {
+  platform: string;
+  user_name: string;
+  outcome: VoiceCommandOutcome;
+}
+
+export const getViewerVoiceConfig = (): Promise<ViewerVoiceConfigData> =>
+  invoke<ViewerVoiceConfigData>('get_viewer_voice_config');
+
+export const setViewerVoicePool = (pool: string[]): Promise<void> =>
+  invoke<void>('set_viewer_voice_pool', { pool });
+
+export const setViewerVoiceChoices = (choices: ViewerVoiceChoice[]): Promise<void> =>
+  invoke<void>('set_viewer_voice_choices', { choices });
+
+// Pass null to hand the viewer back to the automatic pool voice
+export const setViewerVoiceOverride = (platform: string, userName: string, voice: string | null): Promise<void> =>
+  invoke<void>('set_viewer_voice_override', { platform, userName, voice });
+
+export const getViewerVoice = (platform: string, userName: string): Promise<string | null> =>
+  invoke<string | null>('get_viewer_voice', { platform, userName });
+
+export const onVoiceCommand = (callback: (data: VoiceCommandData) => void): (() => void) =>
+  listenTo<VoiceCommandData>('voice-command', callback);
+
+export type FilterAction = 'allow' | 'deny' | 'rewrite';
+
+export interface FilterConditionsData {
+  prefix?: string | null;
+  regex?: string | null;
+  script?: 'cyrillic' | 'latin' | null;
+  // Guessed from the letters used: 'ru', 'uk' or 'en'
+  language?: string | null;
+  badges?: string[];
+  users?: string[];
+  min_length?: number | null;
+  max_length?: number | null;
+}
+
+export interface FilterRewriteData {
+  strip_prefix?: boolean;
+  pattern?: string | null;
+  replacement?: string;
+}
+
+export interface FilterRuleData {
+  name: string;
+  enabled: boolean;
+  action: FilterAction;
+  when: FilterConditionsData;
+  rewrite?: FilterRewriteData | null;
+}
+
+// Rules run in order; the first allow or deny wins, otherwise default_action applies
+export interface FilterConfigData {
+  rules: FilterRuleData[];
+  default_action: 'allow' | 'deny';
+}
+
+export type FilterDecisionData =
+  | { decision: 'allow'; text: string; rule: string | null }
+  | { decision: 'deny'; rule: string | null };
+
+export const getMessageFilter = (): Promise<FilterConfigData> =>
+  invoke<FilterConfigData>('get_message_filter');
+
+export const setMessageFilter = (config: FilterConfigData): Promise<void> =>
+  invoke<void>('set_message_filter', { config });
+
+export const testMessageFilter = (text: string, userName?: string, badges: string[] = []): Promise<FilterDecisionData> =>
+  invoke<FilterDecisionData>('test_message_filter', { text, userName, badges });
+
+// Ordered from least to most trusted; each role includes the ones before it
+export type ChatRole = 'everyone' | 'follower' | 'subscriber' | 'vip' | 'moderator' | 'broadcaster';
+
+export interface CommandPermissionData {
+  prefix: string;
+  min_role: ChatRole;
+}
+
+export interface PermissionPolicyData {
+  speak: ChatRole;
+  commands: CommandPermissionData[];
+  follower_min_days: number;
+}
+
+export const getPermissionPolicy = (): Promise<PermissionPolicyData> =>
+  invoke<PermissionPolicyData>('get_permission_policy');
+
+export const setPermissionPolicy = (policy: PermissionPolicyData): Promise<void> =>
+  invoke<void>('set_permission_policy', { policy });
+
+// Zero turns a limit off
+export interface SpamGuardConfigData {
+  user_cooldown_secs: number;
+  global_per_minute: number;
+  duplicate_window_secs: number;
+  // Between 0 and 1
+  similarity_threshold: number;
+  copypasta_min_users: number;
+  copypasta_min_chars: number;
+}
+
+export type SuppressReason =
+  | { reason: 'user_cooldown'; retry_after_secs: number }
+  | { reason: 'global_rate_limit' }
+  | { reason: 'duplicate' }
+  | { reason: 'copy_pasta' }
+  | { reason: 'profanity' }
+  | { reason: 'muted'; until: string };
+
+export interface SuppressedMessageData {
+  platform: string;
+  user_name: string;
+  message_id: string;
+  text: string;
+  reason: SuppressReason;
+}
+
+export const getSpamGuardConfig = (): Promise<SpamGuardConfigData> =>
+  invoke<SpamGuardConfigData>('get_spam_guard_config');
+
+export const setSpamGuardConfig = (config: SpamGuardConfigData): Promise<void> =>
+  invoke<void>('set_spam_guard_config', { config });
+
+export const onMessageSuppressed = (callback: (data: SuppressedMessageData) => void): (() => void) =>
+  listenTo<SuppressedMessageData>('message-suppressed', callback);
+
+export type ProfanityAction = 'beep' | 'skip_word' | 'drop_message';
+
+export interface ProfanityConfigData {
+  enabled: boolean;
+  action: ProfanityAction;
+  beep_text: string;
+  // Same syntax as the bundled lists: `word`, `stem*`, `*root*`, `!exception`
+  extra_words: string[];
+}
+
+export const getProfanityConfig = (): Promise<ProfanityConfigData> =>
+  invoke<ProfanityConfigData>('get_profanity_config');
+
+export const setProfanityConfig = (config: ProfanityConfigData): Promise<void> =>
+  invoke<void>('set_profanity_config', { config });
+
+export type ModerationActionData =
+  | { action: 'skip'; skipped: string | null }
+  | { action: 'stop'; pause_minutes: number | null }
+  | { action: 'mute'; user_name: string; until: string }
+  | { action: 'unmute'; user_name: string };
+
+export type ModerationAuditEntry = ModerationActionData & {
+  at: string;
+  platform: string;
+  moderator: string;
+};
+
+// Keyed by `platform:user_name`, values are when each mute ends
+export const listTtsMutes = (): Promise<Record<string, string>> =>
+  invoke<Record<string, string>>('list_tts_mutes');
+
+export const unmuteTtsUser = (platform: string, userName: string): Promise<void> =>
+  invoke<void>('unmute_tts_user', { platform, userName });
+
+export const getModerationAudit = (): Promise<ModerationAuditEntry[]> =>
+  invoke<ModerationAuditEntry[]>('get_moderation_audit');
+
+export const onModerationAction = (callback: (data: ModerationAuditEntry) => void): (() => void) =>
+  listenTo<ModerationAuditEntry>('moderation-action', callback);
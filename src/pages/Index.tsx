@@ -21,13 +21,25 @@ const AlertSettings = lazy(() => import('@/components/AlertSettings'));
 
 import { Message } from '@/types/message';
 import { ChatConnection } from '@/types/chatSource';
-import { playMessageAudio, TTSProvider, getAvailableBrowserVoices } from '@/services/ttsService';
-import { hasTwitchOAuthToken, connectToTwitchChat, disconnectFromTwitchChat } from '@/services/twitchService';
-import { hasYoutubeOAuthToken, connectToYouTubeLiveChat } from '@/services/youtubeService';
+import { playQueueItem, TTSProvider, getAvailableBrowserVoices } from '@/services/ttsService';
+import { AlertService } from '@/services/alertsService';
+import {
+  hasElevenLabsApiKey,
+  setElevenLabsApiKey,
+  enqueueTtsMessage,
+  finishTtsItem,
+  getTtsQueue,
+  onQueueItemStarted,
+  onQueueItemFinished,
+  onQueueUpdated,
+  type QueueItemData,
+} from '@/lib/tauri-api';
+import { hasTwitchOAuthToken } from '@/services/twitchService';
+import { hasYoutubeOAuthToken } from '@/services/youtubeService';
 
 const Index = () => {
   const [messages, setMessages] = useState<Message[]>([]);
-  const [apiKey, setApiKey] = useState<string>('');
+  const [hasApiKey, setHasApiKey] = useState<boolean>(false);
   const [volume, setVolume] = useState<number>(0.7);
   const [isProcessing, setIsProcessing] = useState<boolean>(false);
   const [activeTab, setActiveTab] = useState<string>('chat');
@@ -41,11 +53,21 @@ const Index = () => {
   const [youtubeConnections, setYoutubeConnections] = useState<Record<string, { disconnect: () => void }>>({});
   const [ttsInitialized, setTtsInitialized] = useState<boolean>(false);
 
-  // Load API key, volume, and TTS provider from localStorage
+  // Load API key state, volume, and TTS provider
   useEffect(() => {
-    const savedApiKey = localStorage.getItem('elevenLabsApiKey');
-    if (savedApiKey) {
-      setApiKey(savedApiKey);
+    // Keys saved by older versions move to the backend and leave localStorage
+    const legacyApiKey = localStorage.getItem('elevenLabsApiKey');
+    if (legacyApiKey) {
+      setElevenLabsApiKey(legacyApiKey)
+        .then(() => {
+          localStorage.removeItem('elevenLabsApiKey');
+          setHasApiKey(true);
+        })
+        .catch(error => console.error('Failed to migrate ElevenLabs API key:', error));
+    } else {
+      hasElevenLabsApiKey()
+        .then(setHasApiKey)
+        .catch(error => console.error('Failed to check ElevenLabs API key:', error));
     }
     
     const savedVolume = localStorage.getItem('ttsVolume');
@@ -117,30 +139,27 @@ const Index = () => {
     }
   }, [activeTab, ttsInitialized, selectedVoice]);
 
-  // Set up message handler for chat messages - only when active tab is connections
-  const handleExternalMessage = useRef((username: string, content: string) => {
-    // Check if the message is in Russian or starts with !г
-    const hasCyrillic = /[\u0400-\u04FF]/.test(content);
-    const isCommand = content.startsWith('!г ');
-    
-    if (hasCyrillic || isCommand) {
-      const formattedContent = isCommand ? content : `!г ${content}`;
-      handleSendMessage(formattedContent, username);
-    }
-  });
-
   // NOTE: Connection establishment is now handled in ChatConnections.tsx
   // This avoids the reconnection issue that was caused by this useEffect
   // running every time chatConnections array changed and calling connect functions again
 
   const handleApiKeySubmit = useCallback((key: string) => {
-    setApiKey(key);
-    localStorage.setItem('elevenLabsApiKey', key);
-    toast({
-      id: 'api-key-saved',
-      title: "API Key Saved",
-      description: "Your ElevenLabs API key has been saved"
-    });
+    setElevenLabsApiKey(key)
+      .then(() => {
+        setHasApiKey(true);
+        toast({
+          id: 'api-key-saved',
+          title: "API Key Saved",
+          description: "Your ElevenLabs API key has been saved"
+        });
+      })
+      .catch(error => {
+        toast({
+          title: "Failed to Save API Key",
+          description: String(error),
+          variant: "destructive"
+        });
+      });
   }, [toast]);
 
   const handleVolumeChange = useCallback((value: number) => {
@@ -157,7 +176,7 @@ const Index = () => {
       id: 'tts-provider-changed',
       title: `Switched to ${newProvider === 'browser' ? 'Browser TTS' : 'ElevenLabs'}`,
       description: newProvider === 'elevenlabs' 
-        ? "Using ElevenLabs for TTS (requires API key)" 
+        ? "Using ElevenLabs when a key is set, the offline voices otherwise" 
         : "Using browser's built-in TTS (unlimited usage)"
     });
   }, [toast]);
@@ -167,90 +186,131 @@ const Index = () => {
     localStorage.setItem('selectedVoice', voice);
   }, []);
 
-  // Process message queue
-  const processMessageQueue = async (newMessage: Message) => {
-    if (ttsProvider === 'elevenlabs' && !apiKey) {
-      toast({
-        title: "API Key Required",
-        description: "Please set your ElevenLabs API key in the settings tab",
-        variant: "destructive"
-      });
-      
-      // Update message status to error
-      setMessages(currentMessages => 
-        currentMessages.map(msg => 
-          msg.id === newMessage.id ? { ...msg, status: 'error' } : msg
-        )
-      );
-      
-      // Switch to settings tab
-      setActiveTab('settings');
-      return;
-    }
+  // The queue lives in the backend; this player speaks whatever it hands out and reports back
+  const playerSettings = useRef({ volume, ttsProvider, selectedVoice });
+  const playback = useRef<{ id: number; controller: AbortController } | null>(null);
 
-    if (isProcessing) return;
-    
-    try {
-      setIsProcessing(true);
-      
-      // Update message status to playing
-      setMessages(currentMessages => 
-        currentMessages.map(msg => 
-          msg.id === newMessage.id ? { ...msg, status: 'playing' } : msg
-        )
-      );
-      
-      // Play the audio
-      await playMessageAudio(
-        newMessage,
-        apiKey,
-        () => {},
-        () => {
-          // Update message status to completed when done
-          setMessages(currentMessages => 
-            currentMessages.map(msg => 
-              msg.id === newMessage.id ? { ...msg, status: 'completed' } : msg
-            )
-          );
-          setIsProcessing(false);
-        },
-        volume,
-        ttsProvider,
-        selectedVoice
-      );
-      
-    } catch (error) {
-      console.error('Error processing message:', error);
-      
-      toast({
-        title: "Error Playing Message",
-        description: error instanceof Error ? error.message : "Unknown error occurred",
-        variant: "destructive"
-      });
-      
-      // Update message status to error
-      setMessages(currentMessages => 
-        currentMessages.map(msg => 
-          msg.id === newMessage.id ? { ...msg, status: 'error' } : msg
+  useEffect(() => {
+    playerSettings.current = { volume, ttsProvider, selectedVoice };
+  }, [volume, ttsProvider, selectedVoice]);
+
+  const setMessageStatus = useCallback((item: QueueItemData, status: Message['status']) => {
+    const id = item.id.toString();
+    setMessages(currentMessages => currentMessages.some(msg => msg.id === id)
+      ? currentMessages.map(msg => msg.id === id ? { ...msg, status } : msg)
+      : [...currentMessages, {
+          id,
+          content: item.text,
+          timestamp: Date.parse(item.enqueued_at) || Date.now(),
+          username: item.user_name,
+          status
+        }]
+    );
+  }, []);
+
+  useEffect(() => {
+    const play = async (item: QueueItemData) => {
+      if (playback.current?.id === item.id) return;
+      playback.current?.controller.abort();
+      const controller = new AbortController();
+      playback.current = { id: item.id, controller };
+      setMessageStatus(item, 'playing');
+
+      const settings = playerSettings.current;
+      let itemVolume = settings.volume;
+      let speak = true;
+      if (item.priority === 'alert') {
+        const alertSettings = AlertService.getInstance().getSettings();
+        speak = alertSettings.enabled;
+        itemVolume = alertSettings.volume;
+      }
+
+      let failed = false;
+      if (speak) {
+        try {
+          await playQueueItem(item, controller.signal, itemVolume, settings.ttsProvider, settings.selectedVoice);
+        } catch (error) {
+          console.error('Error playing queue item:', error);
+          failed = true;
+          toast({
+            title: "Error Playing Message",
+            description: error instanceof Error ? error.message : "Unknown error occurred",
+            variant: "destructive"
+          });
+        }
+      }
+
+      // Skipped or cleared: the backend has already moved on
+      if (controller.signal.aborted) return;
+      playback.current = null;
+      finishTtsItem(item.id, failed).catch(error => console.error('Failed to finish queue item:', error));
+    };
+
+    const stopStarted = onQueueItemStarted(item => {
+      play(item);
+    });
+    const stopFinished = onQueueItemFinished(({ item, reason }) => {
+      if (playback.current?.id === item.id && (reason === 'skipped' || reason === 'cleared')) {
+        playback.current.controller.abort();
+        playback.current = null;
+      }
+      setMessages(currentMessages =>
+        currentMessages.map(msg =>
+          msg.id === item.id.toString() ? { ...msg, status: reason === 'failed' ? 'error' : 'completed' } : msg
         )
       );
-      
-      setIsProcessing(false);
-    }
-  };
+    });
+    const stopUpdated = onQueueUpdated(snapshot => setIsProcessing(snapshot.current !== null));
+
+    // Pick up an item that started before this page was loaded
+    getTtsQueue()
+      .then(snapshot => {
+        setIsProcessing(snapshot.current !== null);
+        if (snapshot.current) play(snapshot.current);
+      })
+      .catch(error => console.error('Failed to load TTS queue:', error));
 
-  const handleSendMessage = useCallback((content: string, username?: string) => {
-    const newMessage: Message = {
-      id: Date.now().toString(),
-      content,
-      timestamp: Date.now(),
-      username: username || 'You',
-      status: 'pending'
+    return () => {
+      stopStarted();
+      stopFinished();
+      stopUpdated();
+      playback.current?.controller.abort();
+      playback.current = null;
     };
-    
-    setMessages(prevMessages => [...prevMessages, newMessage]);
-    processMessageQueue(newMessage);
-  }, []);
+  }, [toast, setMessageStatus]);
+
+  const handleSendMessage = useCallback((content: string) => {
+    const text = content.replace(/^!г\s*/i, '');
+    enqueueTtsMessage({ platform: 'local', user_name: 'You', text, priority: 'chat' })
+      .then(id => {
+        if (id === null) {
+          toast({
+            title: "Queue Full",
+            description: "The speech queue is full, the message was dropped",
+            variant: "destructive"
+          });
+          return;
+        }
+        const newMessage: Message = {
+          id: id.toString(),
+          content: text,
+          timestamp: Date.now(),
+          username: 'You',
+          status: 'pending'
+        };
+        // The item may already have started playing
+        setMessages(currentMessages =>
+          currentMessages.some(msg => msg.id === newMessage.id) ? currentMessages : [...currentMessages, newMessage]
+        );
+      })
+      .catch(error => {
+        toast({
+          title: "Error Queueing Message",
+          description: String(error),
+          variant: "destructive"
+        });
+      });
+  }, [toast]);
 
   return (
     <div className="min-h-screen p-4 md:p-8 bg-stream-bg flex flex-col">
@@ -374,7 +434,7 @@ const Index = () => {
                       <div className="grid place-items-center">
                         <ApiKeyInput 
                           onApiKeySubmit={handleApiKeySubmit} 
-                          apiKeySet={!!apiKey} 
+                          apiKeySet={hasApiKey} 
                         />
                       </div>
                     )}
@@ -399,7 +459,7 @@ const Index = () => {
                               <span className="text-stream-accent font-semibold">2.</span> Go to Connections and connect to Twitch or YouTube
                             </p>
                             <p>
-                              <span className="text-stream-accent font-semibold">3.</span> Messages starting with <code className="bg-muted px-1 rounded text-stream-accent">!г</code> will be read aloud
+                              <span className="text-stream-accent font-semibold">3.</span> Russian messages and messages starting with <code className="bg-muted px-1 rounded text-stream-accent">!г</code> will be read aloud; change this with the message filter rules
                             </p>
                             <p className="text-muted-foreground italic mt-4">
                               Example: <code className="bg-muted px-1 rounded text-stream-accent">!г Привет!</code>
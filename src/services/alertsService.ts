@@ -137,27 +137,8 @@ export class AlertService {
   }
 
   private processAlert(alert: AlertData): void {
-    // Create a temporary audio element for TTS
-    const utterance = new SpeechSynthesisUtterance(alert.message);
-    utterance.volume = this.settings.volume;
-    utterance.rate = 1.0;
-    utterance.pitch = 1.0;
-
-    // Try to use a different voice for alerts
-    const voices = speechSynthesis.getVoices();
-    if (voices.length > 1) {
-      // Use a different voice than chat TTS if available
-      utterance.voice = voices.find(voice => 
-        voice.name !== (speechSynthesis.getVoices()[0]?.name)
-      ) || voices[1];
-    }
-
-    speechSynthesis.speak(utterance);
-
-    // Also send to custom TTS system if available
-    if (window.speechSynthesis) {
-    }
-
+    // Speech comes from the backend queue; its player in Index applies `enabled` and `volume`
+    
     // Notify listeners
     this.listeners.forEach(listener => listener(alert));
   }
@@ -1,50 +1,9 @@
 
-import { Message } from '@/types/message';
-
-interface TTSRequestOptions {
-  text: string;
-  apiKey: string;
-  voice_id?: string;
-  model_id?: string;
-}
-
-// Default values for the Russian voice
-const DEFAULT_VOICE_ID = 'IKne3meq5aSn9XLyUdCD'; // Charlie, but can be changed
-const DEFAULT_MODEL_ID = 'eleven_multilingual_v2'; // Good for Russian language
+import { synthesizeSpeech, type QueueItemData, type TtsChainErrorData } from '@/lib/tauri-api';
 
 // New type for TTS providers
 export type TTSProvider = 'browser' | 'elevenlabs';
 
-export async function generateSpeechFromText(options: TTSRequestOptions): Promise<ArrayBuffer> {
-  const { text, apiKey, voice_id = DEFAULT_VOICE_ID, model_id = DEFAULT_MODEL_ID } = options;
-  
-  // ElevenLabs API for text-to-speech
-  const url = `https://api.elevenlabs.io/v1/text-to-speech/${voice_id}`;
-  
-  const response = await fetch(url, {
-    method: 'POST',
-    headers: {
-      'Content-Type': 'application/json',
-      'xi-api-key': apiKey,
-    },
-    body: JSON.stringify({
-      text,
-      model_id,
-      voice_settings: {
-        stability: 0.5,
-        similarity_boost: 0.5,
-      },
-    }),
-  });
-  
-  if (!response.ok) {
-    const errorText = await response.text();
-    throw new Error(`Failed to generate speech: ${response.status} ${errorText}`);
-  }
-  
-  return await response.arrayBuffer();
-}
-
 // Cross-platform TTS service
 export class CrossPlatformTTS {
   private static isSpeechSynthesisSupported(): boolean {
@@ -163,59 +122,87 @@ export function useBrowserTTS(
   return CrossPlatformTTS.useBrowserTTS(text, onPlaybackStart, onPlaybackEnd, volume, voiceName);
 }
 
-export async function playMessageAudio(
-  message: Message, 
-  apiKey: string, 
-  onPlaybackStart: () => void,
-  onPlaybackEnd: () => void,
+// What a browser voice should say for a queue item; SSML-only hints are dropped
+function speechPlainText(item: QueueItemData): string {
+  if (!item.speech) {
+    return item.text;
+  }
+  return item.speech.parts
+    .map(part => part.type === 'text' ? part.text : part.type === 'say_as' ? part.spoken : ' ')
+    .join('')
+    .replace(/\s+/g, ' ')
+    .trim();
+}
+
+function playAudio(audioData: ArrayBuffer, volume: number, signal: AbortSignal): Promise<void> {
+  // Local engines return WAV, ElevenLabs returns MP3
+  const header = new TextDecoder().decode(new Uint8Array(audioData, 0, Math.min(4, audioData.byteLength)));
+  const audioBlob = new Blob([audioData], { type: header === 'RIFF' ? 'audio/wav' : 'audio/mpeg' });
+  const audioUrl = URL.createObjectURL(audioBlob);
+  const audio = new Audio(audioUrl);
+  audio.volume = volume;
+
+  return new Promise((resolve, reject) => {
+    const done = (error?: Error) => {
+      signal.removeEventListener('abort', stop);
+      URL.revokeObjectURL(audioUrl);
+      if (error) {
+        reject(error);
+      } else {
+        resolve();
+      }
+    };
+    const stop = () => {
+      audio.pause();
+      done();
+    };
+
+    signal.addEventListener('abort', stop);
+    audio.onended = () => done();
+    audio.onerror = () => done(new Error('Failed to play synthesized audio'));
+    audio.play().catch(error => done(error instanceof Error ? error : new Error(String(error))));
+  });
+}
+
+// Speak one item handed out by the backend queue (`item-started`).
+// Aborting `signal` stops playback, e.g. when the item was skipped; the caller reports back with finishTtsItem.
+// Viewer voices are backend voice ids (`espeak:ru`, `elevenlabs:<id>`) the browser cannot speak,
+// so an item carrying one goes through the backend chain even when the browser provider is selected.
+export async function playQueueItem(
+  item: QueueItemData,
+  signal: AbortSignal,
   volume: number = 1.0,
   provider: TTSProvider = 'browser',
   voiceName?: string
 ): Promise<void> {
-  try {
-    // Extract the actual text without the !г (Russian r) command
-    const textToSpeak = message.content.replace(/^!г\s*/i, '');
-    
-    if (provider === 'browser') {
-      // Use browser's built-in speech synthesis (no token limits)
-      await useBrowserTTS(textToSpeak, onPlaybackStart, onPlaybackEnd, volume, voiceName);
-    } else {
-      // Use ElevenLabs (original implementation)
-      onPlaybackStart();
-      
-      // Generate speech from the text
-      const audioData = await generateSpeechFromText({
-        text: textToSpeak,
-        apiKey
-      });
-      
-      // Create and play the audio
-      const audioBlob = new Blob([audioData], { type: 'audio/mpeg' });
-      const audioUrl = URL.createObjectURL(audioBlob);
-      const audio = new Audio(audioUrl);
-      
-      // Set volume
-      audio.volume = volume;
-      
-      // Return a promise that resolves when audio playback ends
-      return new Promise((resolve) => {
-        audio.onended = () => {
-          URL.revokeObjectURL(audioUrl);
-          onPlaybackEnd();
-          resolve();
-        };
-        
-        audio.play().catch(error => {
-          console.error('Error playing audio:', error);
-          onPlaybackEnd();
-          resolve();
-        });
-      });
+  if (provider === 'browser' && !item.voice) {
+    const cancel = () => window.speechSynthesis.cancel();
+    signal.addEventListener('abort', cancel);
+    try {
+      await useBrowserTTS(speechPlainText(item), () => {}, () => {}, volume, voiceName);
+    } catch (error) {
+      // Cancelling an utterance reports it as interrupted
+      if (!signal.aborted) {
+        throw error;
+      }
+    } finally {
+      signal.removeEventListener('abort', cancel);
     }
+    return;
+  }
+
+  // Backend fallback chain: ElevenLabs first, then the local engines
+  let audioData: ArrayBuffer;
+  try {
+    audioData = await synthesizeSpeech(item.text, item.voice ?? undefined, item.speech ?? undefined);
   } catch (error) {
-    console.error('Error in playMessageAudio:', error);
-    onPlaybackEnd();
+    const data = error as TtsChainErrorData;
+    throw new Error(data.message ?? String(error));
+  }
+  if (signal.aborted) {
+    return;
   }
+  await playAudio(audioData, volume, signal);
 }
 
 // Helper function to get available browser voices (legacy compatibility)